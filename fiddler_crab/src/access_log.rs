@@ -0,0 +1,412 @@
+/*
+Access log: one line per answered request
+
+    access_log_path = "/var/log/fiddler_crab/access.log"   # unset = no access log
+    access_log_format = "combined"   # common | combined | json
+    access_log_max_bytes = 10485760  # rotate before the file grows past this (0 = never)
+    access_log_max_files = 5         # rotated files kept: access.log.1 (newest) .. access.log.5
+
+common:   127.0.0.1 - - [18/Oct/2026:11:09:22 +0000] "POST /echo_input_data HTTP/1.1" 200 31
+combined: the common line + "referer" "user-agent" endpoint queue_wait_ms processing_ms
+json:     {"ts":"...","request_id":7,"client":"127.0.0.1","method":"POST",...}
+
+The handler (or a reader thread) only builds the record and hands it over
+with try_send on a bounded channel; a writer thread formats and writes it.
+If the writer falls behind (slow disk), records are dropped and counted
+(fiddler_crab_access_log_lines_dropped_total) instead of ever making the
+serial handler or a reader wait.
+
+Logged: every request that went through the queue, including 504s and
+module errors, and every request answered without the queue: probes,
+404, 405 and unreadable requests (400/413/431/501; their request line is
+"-" if it could not be read). Those have no request id, no endpoint and
+zero queue and processing time. Not logged: connections dropped unread
+while the queue is full.
+*/
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+
+use crate::config::ServerConfig;
+use crate::json::JsonValue;
+use crate::logger::{log_event, LogLevel};
+use crate::metrics::ACCESS_LOG_DROPPED_COUNTER;
+use crate::time_format::UtcDateTime;
+
+/// Records the handler may get ahead of the writer before records are dropped
+const ACCESS_LOG_CHANNEL_CAPACITY: usize = 1024;
+
+/// Line format of the access log
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccessLogFormat {
+    /// Common Log Format
+    Common,
+    /// Combined Log Format, plus endpoint, queue wait and processing time
+    Combined,
+    /// One JSON object per line
+    JsonLines,
+}
+
+impl AccessLogFormat {
+    /// Parses an access_log_format config value
+    pub fn from_config_value(value: &str) -> Result<Self, String> {
+        match value {
+            "common" => Ok(AccessLogFormat::Common),
+            "combined" => Ok(AccessLogFormat::Combined),
+            "json" => Ok(AccessLogFormat::JsonLines),
+            other => Err(format!("access_log_format must be common, combined or json, got '{}'", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccessLogFormat::Common => "common",
+            AccessLogFormat::Combined => "combined",
+            AccessLogFormat::JsonLines => "json",
+        }
+    }
+}
+
+/// Everything one access-log line says about a finished request
+#[derive(Debug, Clone)]
+pub struct AccessLogRecord {
+    /// None for requests answered without the queue (they never get an id)
+    pub request_id: Option<usize>,
+    /// When the response was ready
+    pub completed_at: SystemTime,
+    pub client: SocketAddr,
+    /// Method, path and version are empty if the request could not be read
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub http_version: String,
+    /// None if no endpoint module handled the request
+    pub endpoint: Option<String>,
+    pub status: u16,
+    /// Response body bytes
+    pub bytes: usize,
+    /// From reading the request to the handler picking it up
+    pub queue_wait: Duration,
+    /// Endpoint module time, watchdog included
+    pub processing_time: Duration,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// Set by start_access_log_writer when access_log_path is configured
+static ACCESS_LOG_SENDER: OnceLock<SyncSender<AccessLogRecord>> = OnceLock::new();
+
+/// Whether an access log is being written (lets the handler skip building records)
+pub fn access_log_enabled() -> bool {
+    ACCESS_LOG_SENDER.get().is_some()
+}
+
+/// Hands a record to the writer thread without waiting; drops (and counts) it if the writer is behind
+pub fn record_access(access_log_record: AccessLogRecord) {
+    let Some(access_log_sender) = ACCESS_LOG_SENDER.get() else {
+        return;
+    };
+    match access_log_sender.try_send(access_log_record) {
+        Ok(()) => {}
+        Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+            ACCESS_LOG_DROPPED_COUNTER.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Opens the access log and starts its writer thread, if access_log_path is set
+///
+/// # Arguments
+/// * `config` - The installed server config
+///
+/// # Returns
+/// * `Result<(), String>` - Err if the file cannot be opened (so a bad path stops startup)
+pub fn start_access_log_writer(config: &ServerConfig) -> Result<(), String> {
+    let Some(access_log_path) = &config.access_log_path else {
+        return Ok(());
+    };
+    let access_log_file = AccessLogFile::open(
+        access_log_path.clone(),
+        config.access_log_max_bytes,
+        config.access_log_max_files,
+    )?;
+    let access_log_format = config.access_log_format;
+    let (access_log_sender, access_log_receiver) = sync_channel(ACCESS_LOG_CHANNEL_CAPACITY);
+    std::thread::Builder::new()
+        .name("access-log".to_string())
+        .spawn(move || write_access_log(access_log_receiver, access_log_file, access_log_format))
+        .map_err(|e| format!("could not start access log thread: {}", e))?;
+    ACCESS_LOG_SENDER
+        .set(access_log_sender)
+        .map_err(|_| "access log writer was already started".to_string())
+}
+
+/// Writer thread: formats and writes records until the sender is gone
+fn write_access_log(
+    access_log_receiver: Receiver<AccessLogRecord>,
+    mut access_log_file: AccessLogFile,
+    access_log_format: AccessLogFormat,
+) {
+    for access_log_record in access_log_receiver {
+        let access_log_line = format_access_log_line(access_log_format, &access_log_record);
+        if let Err(e) = access_log_file.write_line(&access_log_line) {
+            log_event(
+                LogLevel::Warn,
+                "could not write access log",
+                &[("path", &access_log_file.path), ("error", &e)],
+            );
+        }
+    }
+}
+
+/// The open access log, with what size-based rotation needs to know
+struct AccessLogFile {
+    path: String,
+    file: File,
+    current_bytes: u64,
+    /// Rotate before a write would take the file past this (0 = never)
+    max_bytes: u64,
+    /// Rotated files kept as PATH.1 .. PATH.N (0 = the full file is just removed)
+    max_files: usize,
+}
+
+impl AccessLogFile {
+    fn open(path: String, max_bytes: u64, max_files: usize) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("cannot open access log {}: {}", path, e))?;
+        let current_bytes = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        Ok(AccessLogFile { path, file, current_bytes, max_bytes, max_files })
+    }
+
+    /// Appends one line (newline added), rotating first if the line would not fit
+    fn write_line(&mut self, access_log_line: &str) -> Result<(), String> {
+        let line_bytes = access_log_line.len() as u64 + 1;
+        if self.max_bytes > 0 && self.current_bytes > 0 && self.current_bytes + line_bytes > self.max_bytes {
+            self.rotate()?;
+        }
+        let mut line_with_newline = String::with_capacity(access_log_line.len() + 1);
+        line_with_newline.push_str(access_log_line);
+        line_with_newline.push('\n');
+        self.file
+            .write_all(line_with_newline.as_bytes())
+            .map_err(|e| e.to_string())?;
+        self.current_bytes += line_bytes;
+        Ok(())
+    }
+
+    /// PATH.N-1 -> PATH.N, .., PATH -> PATH.1 (the oldest is overwritten), then a fresh PATH
+    fn rotate(&mut self) -> Result<(), String> {
+        for file_number in (1..self.max_files).rev() {
+            let _ = std::fs::rename(
+                format!("{}.{}", self.path, file_number),
+                format!("{}.{}", self.path, file_number + 1),
+            );
+        }
+        let moved = if self.max_files > 0 {
+            std::fs::rename(&self.path, format!("{}.1", self.path))
+        } else {
+            std::fs::remove_file(&self.path)
+        };
+        moved.map_err(|e| format!("could not rotate: {}", e))?;
+        *self = AccessLogFile::open(self.path.clone(), self.max_bytes, self.max_files)?;
+        Ok(())
+    }
+}
+
+/// Escapes a value for a quoted CLF field: \" \\ and \xHH for control characters
+fn clf_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Milliseconds with microsecond precision, e.g. 12.345
+fn duration_ms(duration: Duration) -> f64 {
+    duration.as_micros() as f64 / 1000.0
+}
+
+/// Formats one record as an access-log line (without the trailing newline)
+///
+/// # Arguments
+/// * `access_log_format` - common, combined or json
+/// * `access_log_record` - The finished request
+///
+/// # Returns
+/// * `String` - The line
+pub fn format_access_log_line(access_log_format: AccessLogFormat, access_log_record: &AccessLogRecord) -> String {
+    let target = match &access_log_record.query {
+        Some(query) => format!("{}?{}", access_log_record.path, query),
+        None => access_log_record.path.clone(),
+    };
+    if access_log_format == AccessLogFormat::JsonLines {
+        let optional_string = |value: &Option<String>| match value {
+            Some(value) => JsonValue::String(value.clone()),
+            None => JsonValue::Null,
+        };
+        // Unread request line: null rather than ""
+        let string_or_null = |value: &str| match value {
+            "" => JsonValue::Null,
+            value => JsonValue::String(value.to_string()),
+        };
+        let request_id = match access_log_record.request_id {
+            Some(request_id) => JsonValue::Number(request_id as f64),
+            None => JsonValue::Null,
+        };
+        return JsonValue::object(vec![
+            ("ts", JsonValue::String(UtcDateTime::from_system_time(access_log_record.completed_at).rfc3339())),
+            ("request_id", request_id),
+            ("client", JsonValue::String(access_log_record.client.ip().to_string())),
+            ("method", string_or_null(&access_log_record.method)),
+            ("path", string_or_null(&access_log_record.path)),
+            ("query", optional_string(&access_log_record.query)),
+            ("http_version", string_or_null(&access_log_record.http_version)),
+            ("endpoint", optional_string(&access_log_record.endpoint)),
+            ("status", JsonValue::Number(access_log_record.status as f64)),
+            ("bytes", JsonValue::Number(access_log_record.bytes as f64)),
+            ("queue_wait_ms", JsonValue::Number(duration_ms(access_log_record.queue_wait))),
+            ("processing_ms", JsonValue::Number(duration_ms(access_log_record.processing_time))),
+            ("referer", optional_string(&access_log_record.referer)),
+            ("user_agent", optional_string(&access_log_record.user_agent)),
+        ])
+        .to_json_string();
+    }
+
+    // Common Log Format: host ident authuser [date] "request line" status bytes
+    let bytes = match access_log_record.bytes {
+        0 => "-".to_string(),
+        bytes => bytes.to_string(),
+    };
+    // "-" for a request line that could not be read, as Apache does
+    let request_line = match access_log_record.method.as_str() {
+        "" => "-".to_string(),
+        method => format!(
+            "{} {} {}",
+            clf_escape(method),
+            clf_escape(&target),
+            clf_escape(&access_log_record.http_version)
+        ),
+    };
+    let mut access_log_line = format!(
+        "{} - - [{}] \"{}\" {} {}",
+        access_log_record.client.ip(),
+        UtcDateTime::from_system_time(access_log_record.completed_at).clf(),
+        request_line,
+        access_log_record.status,
+        bytes,
+    );
+    if access_log_format == AccessLogFormat::Combined {
+        let quoted_or_dash = |value: &Option<String>| match value {
+            Some(value) => format!("\"{}\"", clf_escape(value)),
+            None => "\"-\"".to_string(),
+        };
+        access_log_line.push_str(&format!(
+            " {} {} {} {:.3} {:.3}",
+            quoted_or_dash(&access_log_record.referer),
+            quoted_or_dash(&access_log_record.user_agent),
+            access_log_record.endpoint.as_deref().unwrap_or("-"),
+            duration_ms(access_log_record.queue_wait),
+            duration_ms(access_log_record.processing_time),
+        ));
+    }
+    access_log_line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    fn finished_request_record() -> AccessLogRecord {
+        AccessLogRecord {
+            request_id: Some(7),
+            completed_at: UNIX_EPOCH + Duration::from_secs(1_709_251_200),
+            client: "127.0.0.1:50000".parse().unwrap(),
+            method: "GET".to_string(),
+            path: "/echo_input_data".to_string(),
+            query: Some("input_string=a%20b".to_string()),
+            http_version: "HTTP/1.1".to_string(),
+            endpoint: Some("echo_input_data".to_string()),
+            status: 200,
+            bytes: 31,
+            queue_wait: Duration::from_micros(1500),
+            processing_time: Duration::from_micros(250),
+            referer: None,
+            user_agent: Some("curl/8.5 \"test\"".to_string()),
+        }
+    }
+
+    #[test]
+    fn formats_common_combined_and_json_lines() {
+        let access_log_record = finished_request_record();
+        let common_line = "127.0.0.1 - - [01/Mar/2024:00:00:00 +0000] \"GET /echo_input_data?input_string=a%20b HTTP/1.1\" 200 31";
+        assert_eq!(format_access_log_line(AccessLogFormat::Common, &access_log_record), common_line);
+        assert_eq!(
+            format_access_log_line(AccessLogFormat::Combined, &access_log_record),
+            format!("{} \"-\" \"curl/8.5 \\\"test\\\"\" echo_input_data 1.500 0.250", common_line)
+        );
+        let json_line = format_access_log_line(AccessLogFormat::JsonLines, &access_log_record);
+        assert!(json_line.starts_with("{\"ts\":\"2024-03-01T00:00:00.000Z\",\"request_id\":7,\"client\":\"127.0.0.1\""));
+        assert!(json_line.contains("\"status\":200,\"bytes\":31,\"queue_wait_ms\":1.5,\"processing_ms\":0.25,\"referer\":null"));
+    }
+
+    #[test]
+    fn formats_requests_answered_by_the_stream_loop() {
+        let unreadable_request_record = AccessLogRecord {
+            request_id: None,
+            method: String::new(),
+            path: String::new(),
+            query: None,
+            http_version: String::new(),
+            endpoint: None,
+            status: 400,
+            bytes: 0,
+            queue_wait: Duration::ZERO,
+            processing_time: Duration::ZERO,
+            user_agent: None,
+            ..finished_request_record()
+        };
+        assert_eq!(
+            format_access_log_line(AccessLogFormat::Combined, &unreadable_request_record),
+            "127.0.0.1 - - [01/Mar/2024:00:00:00 +0000] \"-\" 400 - \"-\" \"-\" - 0.000 0.000"
+        );
+        let json_line = format_access_log_line(AccessLogFormat::JsonLines, &unreadable_request_record);
+        assert!(json_line.contains("\"request_id\":null,\"client\":\"127.0.0.1\",\"method\":null,\"path\":null,\"query\":null,\"http_version\":null,\"endpoint\":null,\"status\":400"), "{}", json_line);
+    }
+
+    #[test]
+    fn rotates_before_the_size_limit() {
+        let path = std::env::temp_dir().join(format!("fiddler_crab_access_log_test_{}.log", std::process::id()));
+        let path = path.display().to_string();
+        let cleanup = |path: &str| {
+            for suffix in ["", ".1", ".2", ".3"] {
+                let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+            }
+        };
+        cleanup(&path);
+
+        // 9 bytes per line with its newline: two lines fit in 20, the third rotates
+        let mut access_log_file = AccessLogFile::open(path.clone(), 20, 2).unwrap();
+        for line in ["line-one", "line-two", "line-333", "line-444", "line-555"] {
+            access_log_file.write_line(line).unwrap();
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "line-555\n");
+        assert_eq!(std::fs::read_to_string(format!("{}.1", path)).unwrap(), "line-333\nline-444\n");
+        assert_eq!(std::fs::read_to_string(format!("{}.2", path)).unwrap(), "line-one\nline-two\n");
+        assert!(!std::path::Path::new(&format!("{}.3", path)).exists());
+        cleanup(&path);
+    }
+}
@@ -0,0 +1,118 @@
+/*
+Admission gate: decide to drop a connection before doing any work on it
+
+The stream-loop asks the gate right after accept(), before reading a
+single byte, assigning a request ID, or touching stream_map. A dropped
+connection is simply closed: no read, no parse, no allocation, no log.
+
+Cool-off: once the queue has been found full, the gate keeps dropping
+every connection for a while without even looking at the queue size,
+so a flood is ignored as cheaply as possible (one clock read each).
+
+Drops are counted in a plain field (one add, no atomics); the stream-loop
+moves the count to the shared DROPPED_COUNTER only when it is idle, so
+the drop path itself never touches shared state.
+*/
+use std::time::{Duration, Instant};
+
+/// What to do with a freshly accepted connection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AdmissionDecision {
+    /// Read the request and queue it
+    Admit,
+    /// Close the connection without touching it
+    Drop,
+}
+
+/// Decides, per accepted connection, whether the server does any work at all
+///
+/// Owned by the stream-loop (single thread), so it needs no atomics.
+#[derive(Debug)]
+pub struct AdmissionGate {
+    /// Queue length at which connections start being dropped
+    max_queue_size: usize,
+    /// How long to drop everything after a full-queue event (zero = no cool-off)
+    cool_off: Duration,
+    /// End of the current cool-off window, if in one
+    cool_off_until: Option<Instant>,
+    /// Connections dropped since the last take_dropped_count
+    dropped_count: usize,
+}
+
+impl AdmissionGate {
+    /// Creates a gate
+    ///
+    /// # Arguments
+    /// * `max_queue_size` - Drop connections while this many requests are queued
+    /// * `cool_off` - After a full-queue drop, drop everything for this long
+    ///   (Duration::ZERO disables the cool-off)
+    pub fn new(max_queue_size: usize, cool_off: Duration) -> Self {
+        AdmissionGate {
+            max_queue_size,
+            cool_off,
+            cool_off_until: None,
+            dropped_count: 0,
+        }
+    }
+
+    /// Decides whether to admit a connection, before anything is read from it
+    ///
+    /// # Arguments
+    /// * `queued_request_count` - Requests in the stream-loop's queue plus connections still being read
+    ///   (not consulted during a cool-off window)
+    ///
+    /// # Returns
+    /// * `AdmissionDecision` - Admit, or Drop (close without reading)
+    pub fn check(&mut self, queued_request_count: usize) -> AdmissionDecision {
+        if let Some(cool_off_until) = self.cool_off_until {
+            if Instant::now() < cool_off_until {
+                self.dropped_count += 1;
+                return AdmissionDecision::Drop;
+            }
+            self.cool_off_until = None;
+        }
+
+        if queued_request_count >= self.max_queue_size {
+            if !self.cool_off.is_zero() {
+                self.cool_off_until = Some(Instant::now() + self.cool_off);
+            }
+            self.dropped_count += 1;
+            return AdmissionDecision::Drop;
+        }
+
+        AdmissionDecision::Admit
+    }
+
+    /// Returns the number of connections dropped since the last call, and resets it
+    pub fn take_dropped_count(&mut self) -> usize {
+        std::mem::take(&mut self.dropped_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn admits_below_max_and_drops_at_max() {
+        let mut admission_gate = AdmissionGate::new(2, Duration::ZERO);
+        assert_eq!(admission_gate.check(0), AdmissionDecision::Admit);
+        assert_eq!(admission_gate.check(1), AdmissionDecision::Admit);
+        assert_eq!(admission_gate.check(2), AdmissionDecision::Drop);
+        // no cool-off: admits again as soon as there is room
+        assert_eq!(admission_gate.check(1), AdmissionDecision::Admit);
+        assert_eq!(admission_gate.take_dropped_count(), 1);
+        assert_eq!(admission_gate.take_dropped_count(), 0);
+    }
+
+    #[test]
+    fn cool_off_drops_even_when_queue_has_room() {
+        let mut admission_gate = AdmissionGate::new(1, Duration::from_millis(50));
+        assert_eq!(admission_gate.check(1), AdmissionDecision::Drop);
+        // queue is empty now, but the window is still open
+        assert_eq!(admission_gate.check(0), AdmissionDecision::Drop);
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(admission_gate.check(0), AdmissionDecision::Admit);
+    }
+}
@@ -0,0 +1,201 @@
+/*
+Command line interface (in-house, no dependencies)
+
+The flags are listed in USAGE_TEXT (printed by --help). Values may be
+given as "--flag VALUE" or "--flag=VALUE". Setting flags override the
+config file and FIDDLER_CRAB_* environment variables (see config.rs);
+action flags (--list-endpoints, --check-config, --version, --help) make
+the binary print something and exit instead of serving.
+*/
+
+/// Usage text printed by --help
+pub const USAGE_TEXT: &str = "\
+Usage: fiddler_crab [OPTIONS]
+
+Options:
+  --config PATH        read settings from a config file
+  --bind ADDR          listen address, e.g. 0.0.0.0:8080
+  --max-queue N        drop connections while N requests are queued
+  --pace-ms N          handler pause after each request, in ms
+  --KEY=VALUE          any config key, e.g. --endpoint.llamacpp.model_path=/m.gguf
+  --list-endpoints     print the routable endpoints and exit
+  --check-config       load and validate the config, print it, and exit
+  --version            print the version and exit
+  --help, -h           print this help and exit
+
+Environment: FIDDLER_CRAB_CONFIG=PATH, FIDDLER_CRAB_<KEY>=VALUE
+";
+
+/// Named flags and the config key each one sets
+const FLAG_CONFIG_KEYS: &[(&str, &str)] = &[
+    ("bind", "bind_address"),
+    ("max-queue", "max_queue_size"),
+    ("pace-ms", "processing_delay_ms"),
+];
+
+/// What the binary was asked to do
+#[derive(Debug, PartialEq)]
+pub enum CliAction {
+    /// Run the server (no action flag given)
+    RunServer,
+    /// Print the endpoint registry and exit
+    ListEndpoints,
+    /// Load and validate the config, print it and exit
+    CheckConfig,
+    /// Print the version and exit
+    PrintVersion,
+    /// Print usage and exit
+    PrintHelp,
+}
+
+/// The parsed command line
+#[derive(Debug)]
+pub struct CommandLine {
+    /// What to do
+    pub action: CliAction,
+    /// Config file from --config, if given
+    pub config_path: Option<String>,
+    /// (config key, value) settings, in the order given
+    pub settings: Vec<(String, String)>,
+}
+
+/// Parses the arguments after the program name
+///
+/// # Arguments
+/// * `args` - Normally `std::env::args().skip(1)`
+///
+/// # Returns
+/// * `Result<CommandLine, String>` - The parsed command line, or what was wrong with it
+pub fn parse_command_line(args: &[String]) -> Result<CommandLine, String> {
+    let mut command_line = CommandLine {
+        action: CliAction::RunServer,
+        config_path: None,
+        settings: Vec::new(),
+    };
+
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        // Action flags take no value; if several are given the last one wins
+        let action = match arg.as_str() {
+            "--list-endpoints" => Some(CliAction::ListEndpoints),
+            "--check-config" => Some(CliAction::CheckConfig),
+            "--version" | "-V" => Some(CliAction::PrintVersion),
+            "--help" | "-h" => Some(CliAction::PrintHelp),
+            _ => None,
+        };
+        if let Some(action) = action {
+            command_line.action = action;
+            continue;
+        }
+
+        let Some(flag) = arg.strip_prefix("--") else {
+            return Err(format!("unexpected argument '{}' (try --help)", arg));
+        };
+        let (flag_name, flag_value) = match flag.split_once('=') {
+            Some((flag_name, flag_value)) => (flag_name, flag_value.to_string()),
+            None => match args_iter.next() {
+                Some(flag_value) => (flag, flag_value.clone()),
+                None => return Err(format!("--{} needs a value", flag)),
+            },
+        };
+        if flag_name.is_empty() {
+            return Err(format!("unexpected argument '{}' (try --help)", arg));
+        }
+
+        if flag_name == "config" {
+            command_line.config_path = Some(flag_value);
+            continue;
+        }
+        // Named flag, or any config key as-is (config.rs rejects unknown keys)
+        let config_key = FLAG_CONFIG_KEYS
+            .iter()
+            .find(|(named_flag, _)| *named_flag == flag_name)
+            .map(|(_, config_key)| *config_key)
+            .unwrap_or(flag_name);
+        command_line.settings.push((config_key.to_string(), flag_value));
+    }
+
+    Ok(command_line)
+}
+
+/// Version string printed by --version
+pub fn version_text() -> String {
+    format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn settings(settings: &[(&str, &str)]) -> Vec<(String, String)> {
+        settings
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn flags_take_equals_or_separate_values_and_map_to_config_keys() {
+        let command_line = parse_command_line(&args(&[
+            "--bind=0.0.0.0:9000",
+            "--max-queue",
+            "50",
+            "--pace-ms=0",
+            "--config",
+            "/etc/fiddler_crab.toml",
+            "--endpoint.llamacpp.model_path=/models/a=b.gguf",
+            "--processing_timeout_ms",
+            "1000",
+        ]))
+        .unwrap();
+        assert_eq!(command_line.action, CliAction::RunServer);
+        assert_eq!(command_line.config_path.as_deref(), Some("/etc/fiddler_crab.toml"));
+        assert_eq!(
+            command_line.settings,
+            settings(&[
+                ("bind_address", "0.0.0.0:9000"),
+                ("max_queue_size", "50"),
+                ("processing_delay_ms", "0"),
+                ("endpoint.llamacpp.model_path", "/models/a=b.gguf"),
+                ("processing_timeout_ms", "1000"),
+            ])
+        );
+    }
+
+    #[test]
+    fn action_flags_set_the_action() {
+        for (arg, expected_action) in [
+            ("--help", CliAction::PrintHelp),
+            ("-h", CliAction::PrintHelp),
+            ("--version", CliAction::PrintVersion),
+            ("-V", CliAction::PrintVersion),
+            ("--list-endpoints", CliAction::ListEndpoints),
+            ("--check-config", CliAction::CheckConfig),
+        ] {
+            assert_eq!(parse_command_line(&args(&[arg])).unwrap().action, expected_action);
+        }
+
+        // Settings still apply alongside an action; the last action wins
+        let command_line = parse_command_line(&args(&["--check-config", "--max-queue=5", "--help"])).unwrap();
+        assert_eq!(command_line.action, CliAction::PrintHelp);
+        assert_eq!(command_line.settings, settings(&[("max_queue_size", "5")]));
+    }
+
+    #[test]
+    fn bad_arguments_are_errors() {
+        assert_eq!(parse_command_line(&args(&["--max-queue"])).unwrap_err(), "--max-queue needs a value");
+        assert_eq!(parse_command_line(&args(&["--config"])).unwrap_err(), "--config needs a value");
+        assert_eq!(parse_command_line(&args(&["serve"])).unwrap_err(), "unexpected argument 'serve' (try --help)");
+        assert_eq!(parse_command_line(&args(&["--=1"])).unwrap_err(), "unexpected argument '--=1' (try --help)");
+
+        // Unknown flags pass through as config keys, which config.rs then rejects
+        let command_line = parse_command_line(&args(&["--max-queu=5"])).unwrap();
+        assert_eq!(command_line.settings, settings(&[("max-queu", "5")]));
+        let load_error = crate::config::load_server_config(None, &command_line.settings, std::iter::empty()).unwrap_err();
+        assert_eq!(load_error, "command line setting max-queu: unknown config key 'max-queu'");
+    }
+}
@@ -0,0 +1,124 @@
+/*
+Client stream: one accepted connection, plain TCP or TLS over TCP
+
+Everything after accept() (reading the request, stream_map, writing the
+response) works on a ClientStream, so HTTPS needs no changes there.
+
+TLS only exists in builds with the `tls` cargo feature (see tls.rs);
+the default build has just the Plain variant and no dependencies.
+HTTPS is switched on by setting both tls_cert_path and tls_key_path.
+*/
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use crate::config::ServerConfig;
+
+/// An accepted connection, as the rest of the server sees it
+pub enum ClientStream {
+    /// Plain HTTP
+    Plain(TcpStream),
+    /// HTTPS: the handshake runs on the first read
+    #[cfg(feature = "tls")]
+    Tls(Box<crate::tls::TlsStream>),
+}
+
+impl ClientStream {
+    /// The underlying TCP socket
+    fn tcp_stream(&self) -> &TcpStream {
+        match self {
+            ClientStream::Plain(tcp_stream) => tcp_stream,
+            #[cfg(feature = "tls")]
+            ClientStream::Tls(tls_stream) => &tls_stream.sock,
+        }
+    }
+
+    /// Address of the client
+    pub fn peer_addr(&self) -> std::io::Result<SocketAddr> {
+        self.tcp_stream().peer_addr()
+    }
+
+    /// Sets the socket read timeout (also bounds the TLS handshake)
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.tcp_stream().set_read_timeout(timeout)
+    }
+
+    /// Sets the socket write timeout
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.tcp_stream().set_write_timeout(timeout)
+    }
+}
+
+impl Read for ClientStream {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            ClientStream::Plain(tcp_stream) => tcp_stream.read(buffer),
+            #[cfg(feature = "tls")]
+            ClientStream::Tls(tls_stream) => tls_stream.read(buffer),
+        }
+    }
+}
+
+impl Write for ClientStream {
+    fn write(&mut self, buffer: &[u8]) -> std::io::Result<usize> {
+        match self {
+            ClientStream::Plain(tcp_stream) => tcp_stream.write(buffer),
+            #[cfg(feature = "tls")]
+            ClientStream::Tls(tls_stream) => tls_stream.write(buffer),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            ClientStream::Plain(tcp_stream) => tcp_stream.flush(),
+            #[cfg(feature = "tls")]
+            ClientStream::Tls(tls_stream) => tls_stream.flush(),
+        }
+    }
+}
+
+/// Checks (and for HTTPS, loads) what client streams need, once at startup
+///
+/// # Arguments
+/// * `config` - The server config; HTTPS is on when tls_cert_path and tls_key_path are both set
+///
+/// # Returns
+/// * `Result<(), String>` - Err if only one of the paths is set, the certificate
+///   or key cannot be loaded, or HTTPS is configured in a build without the `tls` feature
+pub fn prepare_client_streams(config: &ServerConfig) -> Result<(), String> {
+    match (&config.tls_cert_path, &config.tls_key_path) {
+        (None, None) => Ok(()),
+        (Some(cert_path), Some(key_path)) => prepare_tls(cert_path, key_path),
+        _ => Err("tls_cert_path and tls_key_path must be set together".to_string()),
+    }
+}
+
+#[cfg(feature = "tls")]
+fn prepare_tls(cert_path: &str, key_path: &str) -> Result<(), String> {
+    crate::tls::install_tls_server_config(cert_path, key_path)
+}
+
+#[cfg(not(feature = "tls"))]
+fn prepare_tls(_cert_path: &str, _key_path: &str) -> Result<(), String> {
+    Err("HTTPS is configured (tls_cert_path) but this build has no TLS; rebuild with --features tls".to_string())
+}
+
+/// Wraps an accepted TCP stream: TLS if HTTPS was prepared, plain otherwise
+///
+/// No bytes are exchanged here; the TLS handshake happens on the first read.
+pub fn client_stream_from_tcp(tcp_stream: TcpStream) -> Result<ClientStream, String> {
+    #[cfg(feature = "tls")]
+    if crate::tls::tls_is_enabled() {
+        return crate::tls::accept_tls_stream(tcp_stream).map(|tls_stream| ClientStream::Tls(Box::new(tls_stream)));
+    }
+    Ok(ClientStream::Plain(tcp_stream))
+}
+
+#[cfg(feature = "tls")]
+impl Drop for ClientStream {
+    fn drop(&mut self) {
+        if let ClientStream::Tls(tls_stream) = self {
+            crate::tls::close_tls_stream(tls_stream);
+        }
+    }
+}
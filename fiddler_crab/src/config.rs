@@ -0,0 +1,707 @@
+/*
+Runtime configuration (in-house, no dependencies)
+
+One built binary, many deployments: everything that used to be a
+compile-time constant (bind address, queue size, pacing, timeouts) and
+every per-endpoint setting (e.g. llamacpp binary and model paths) is read
+at startup from, in increasing priority:
+
+1. built-in defaults (the DEFAULT_* constants below)
+2. a config file: --config=PATH, or the FIDDLER_CRAB_CONFIG env var
+3. environment variables: FIDDLER_CRAB_<KEY>, e.g. FIDDLER_CRAB_MAX_QUEUE_SIZE=100
+   per-endpoint: FIDDLER_CRAB_ENDPOINT__<NAME>__<KEY>, e.g.
+   FIDDLER_CRAB_ENDPOINT__LLAMACPP__MODEL_PATH=/models/gemma.gguf
+4. command line flags (see cli.rs): --bind, --max-queue, --pace-ms, or any
+   key as --<key>=<value>, e.g. --endpoint.llamacpp.model_path=/models/gemma.gguf
+
+Config file format (a small TOML subset):
+
+    # comment
+    bind_address = "0.0.0.0:8080"
+    max_queue_size = 200
+
+    [endpoint.llamacpp]
+    binary_path = "/opt/llama.cpp/llama-cli"
+    model_path = "/models/gemma-2-2b-it-Q4_K_M.gguf"
+
+- one `key = value` per line; values are bare or "double quoted"
+- [endpoint.NAME] starts a section of settings for that endpoint
+- unknown keys are errors, so a typo does not silently fall back to a default
+- [endpoint.NAME] settings are only split into (NAME, key, value) here; the
+  endpoint registry checks NAME, the key and its value when it is installed
+  (see check_endpoint_settings in endpoint_modules/mod.rs)
+
+The loaded config is stored once in SERVER_CONFIG; any thread reads it
+through server_config() (no locking, it never changes after startup).
+*/
+use std::sync::OnceLock;
+
+use crate::access_log::AccessLogFormat;
+use crate::logger::{LogFormat, LogLevel};
+
+/// Default listen address
+pub const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1:8080";
+
+/// Default number of queued requests after which new connections are dropped
+pub const DEFAULT_MAX_QUEUE_SIZE: usize = 500;
+
+/// Default pause after each processed request in the handler (pacing)
+pub const DEFAULT_PROCESSING_DELAY_MS: u64 = 100;
+
+/// Default stream-loop pause when no connection is waiting
+pub const DEFAULT_REQUEST_HANDLER_PAUSE_MS: u64 = 10;
+
+/// Default: no single read from a client waits longer than this
+pub const DEFAULT_STREAM_READ_TIMEOUT_MS: u64 = 5000;
+
+/// Default: a slow client cannot hold the responder longer than this
+pub const DEFAULT_STREAM_WRITE_TIMEOUT_MS: u64 = 5000;
+
+/// Default: after a full-queue drop, drop everything unread for this long (0 = off)
+pub const DEFAULT_QUEUE_FULL_COOL_OFF_MS: u64 = 250;
+
+/// Default deadline for one endpoint module invocation before a 504
+pub const DEFAULT_PROCESSING_TIMEOUT_MS: u64 = 120_000;
+
+/// Default: /readyz reports not ready once this many requests are waiting (0 = max_queue_size)
+pub const DEFAULT_READINESS_QUEUE_THRESHOLD: usize = 0;
+
+/// Default: log events at info and above
+pub const DEFAULT_LOG_LEVEL: LogLevel = LogLevel::Info;
+
+/// Default: key=value log lines
+pub const DEFAULT_LOG_FORMAT: LogFormat = LogFormat::KeyValue;
+
+/// Default: Combined Log Format plus endpoint and timings
+pub const DEFAULT_ACCESS_LOG_FORMAT: AccessLogFormat = AccessLogFormat::Combined;
+
+/// Default: rotate the access log at 10 MiB
+pub const DEFAULT_ACCESS_LOG_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Default: keep 5 rotated access logs
+pub const DEFAULT_ACCESS_LOG_MAX_FILES: usize = 5;
+
+/// Prefix of every environment variable the server reads
+const ENV_PREFIX: &str = "FIDDLER_CRAB_";
+
+/// Environment variable naming the config file (when --config is not given)
+const ENV_CONFIG_PATH: &str = "FIDDLER_CRAB_CONFIG";
+
+/// Prefix of per-endpoint environment variables (after ENV_PREFIX)
+const ENV_ENDPOINT_PREFIX: &str = "ENDPOINT__";
+
+/// Set once at startup by install_server_config
+static SERVER_CONFIG: OnceLock<ServerConfig> = OnceLock::new();
+
+/// Everything the server reads at startup instead of compile-time constants
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Address the listener binds, e.g. "127.0.0.1:8080"
+    pub bind_address: String,
+    /// Connections are dropped while this many requests are queued
+    pub max_queue_size: usize,
+    /// Handler pause after each processed request, in ms
+    pub processing_delay_ms: u64,
+    /// Stream-loop pause when no connection is waiting, in ms
+    pub request_handler_pause_ms: u64,
+    /// Read timeout on accepted streams (each read), in ms
+    pub stream_read_timeout_ms: u64,
+    /// Deadline for reading a whole request, head and body, in ms (past it: 408)
+    pub request_read_timeout_ms: u64,
+    /// Write timeout on streams held by the responder, in ms
+    pub stream_write_timeout_ms: u64,
+    /// Admission-gate cool-off after a full-queue drop, in ms (0 = off)
+    pub queue_full_cool_off_ms: u64,
+    /// Deadline for one module invocation, in ms (per endpoint: endpoint.NAME.processing_timeout_ms)
+    pub processing_timeout_ms: u64,
+    /// Waiting requests (queued plus the handler's unfinished batch) at which /readyz says not ready (0 = max_queue_size)
+    pub readiness_queue_threshold: usize,
+    /// Cap on request line plus headers, in bytes
+    pub max_header_bytes: usize,
+    /// Cap on the request body, in bytes
+    pub max_body_bytes: usize,
+    /// PEM certificate chain for HTTPS (needs the `tls` feature); None = plain HTTP
+    pub tls_cert_path: Option<String>,
+    /// PEM private key for HTTPS, set together with tls_cert_path
+    pub tls_key_path: Option<String>,
+    /// Least severe log events written (off = none)
+    pub log_level: LogLevel,
+    /// kv or json log lines
+    pub log_format: LogFormat,
+    /// Debug log lines show request bodies instead of their length
+    pub log_request_bodies: bool,
+    /// File for one line per finished request; None = no access log
+    pub access_log_path: Option<String>,
+    /// common, combined or json access-log lines
+    pub access_log_format: AccessLogFormat,
+    /// Access log is rotated before it grows past this, in bytes (0 = never)
+    pub access_log_max_bytes: u64,
+    /// Rotated access logs kept (PATH.1 .. PATH.N)
+    pub access_log_max_files: usize,
+    /// Per-endpoint settings: (endpoint name, key, value), in the order given
+    pub endpoint_settings: Vec<(String, String, String)>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        let request_limits = crate::http_request::HttpRequestLimits::default();
+        ServerConfig {
+            bind_address: DEFAULT_BIND_ADDRESS.to_string(),
+            max_queue_size: DEFAULT_MAX_QUEUE_SIZE,
+            processing_delay_ms: DEFAULT_PROCESSING_DELAY_MS,
+            request_handler_pause_ms: DEFAULT_REQUEST_HANDLER_PAUSE_MS,
+            stream_read_timeout_ms: DEFAULT_STREAM_READ_TIMEOUT_MS,
+            request_read_timeout_ms: request_limits.max_read_time.as_millis() as u64,
+            stream_write_timeout_ms: DEFAULT_STREAM_WRITE_TIMEOUT_MS,
+            queue_full_cool_off_ms: DEFAULT_QUEUE_FULL_COOL_OFF_MS,
+            processing_timeout_ms: DEFAULT_PROCESSING_TIMEOUT_MS,
+            readiness_queue_threshold: DEFAULT_READINESS_QUEUE_THRESHOLD,
+            max_header_bytes: request_limits.max_header_bytes,
+            max_body_bytes: request_limits.max_body_bytes,
+            tls_cert_path: None,
+            tls_key_path: None,
+            log_level: DEFAULT_LOG_LEVEL,
+            log_format: DEFAULT_LOG_FORMAT,
+            log_request_bodies: false,
+            access_log_path: None,
+            access_log_format: DEFAULT_ACCESS_LOG_FORMAT,
+            access_log_max_bytes: DEFAULT_ACCESS_LOG_MAX_BYTES,
+            access_log_max_files: DEFAULT_ACCESS_LOG_MAX_FILES,
+            endpoint_settings: Vec::new(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Waiting requests at which /readyz reports not ready
+    pub fn readiness_queue_limit(&self) -> usize {
+        match self.readiness_queue_threshold {
+            0 => self.max_queue_size,
+            threshold => threshold,
+        }
+    }
+
+    /// Names of the endpoints that have any [endpoint.NAME] settings, in first-seen order
+    pub fn endpoint_names(&self) -> Vec<&str> {
+        let mut endpoint_names: Vec<&str> = Vec::new();
+        for (endpoint_name, _, _) in &self.endpoint_settings {
+            if !endpoint_names.contains(&endpoint_name.as_str()) {
+                endpoint_names.push(endpoint_name);
+            }
+        }
+        endpoint_names
+    }
+
+    /// Returns a per-endpoint setting, e.g. ("llamacpp", "model_path")
+    ///
+    /// When a key was given more than once, the last (highest-priority) value wins.
+    pub fn endpoint_setting(&self, endpoint_name: &str, key: &str) -> Option<&str> {
+        self.endpoint_settings
+            .iter()
+            .rev()
+            .find(|(name, setting_key, _)| name == endpoint_name && setting_key == key)
+            .map(|(_, _, value)| value.as_str())
+    }
+
+    /// Watchdog deadline for one invocation of an endpoint module
+    ///
+    /// endpoint.NAME.processing_timeout_ms if set (validated by check_endpoint_settings), else processing_timeout_ms.
+    pub fn processing_timeout_for(&self, endpoint_name: &str) -> std::time::Duration {
+        let timeout_ms = self
+            .endpoint_setting(endpoint_name, "processing_timeout_ms")
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(self.processing_timeout_ms);
+        std::time::Duration::from_millis(timeout_ms)
+    }
+
+    /// Header and body size caps and the read deadline for reading requests off the socket
+    pub fn http_request_limits(&self) -> crate::http_request::HttpRequestLimits {
+        crate::http_request::HttpRequestLimits {
+            max_header_bytes: self.max_header_bytes,
+            max_body_bytes: self.max_body_bytes,
+            max_read_time: std::time::Duration::from_millis(self.request_read_timeout_ms),
+        }
+    }
+
+    /// Renders the effective config in config-file format (used by --check-config)
+    pub fn to_config_text(&self) -> String {
+        let mut config_text = format!(
+            "bind_address = {}\n\
+             max_queue_size = {}\n\
+             processing_delay_ms = {}\n\
+             request_handler_pause_ms = {}\n\
+             stream_read_timeout_ms = {}\n\
+             request_read_timeout_ms = {}\n\
+             stream_write_timeout_ms = {}\n\
+             queue_full_cool_off_ms = {}\n\
+             processing_timeout_ms = {}\n\
+             readiness_queue_threshold = {}\n\
+             max_header_bytes = {}\n\
+             max_body_bytes = {}\n\
+             log_level = {}\n\
+             log_format = {}\n\
+             log_request_bodies = {}\n\
+             access_log_format = {}\n\
+             access_log_max_bytes = {}\n\
+             access_log_max_files = {}\n",
+            quote_value(&self.bind_address),
+            self.max_queue_size,
+            self.processing_delay_ms,
+            self.request_handler_pause_ms,
+            self.stream_read_timeout_ms,
+            self.request_read_timeout_ms,
+            self.stream_write_timeout_ms,
+            self.queue_full_cool_off_ms,
+            self.processing_timeout_ms,
+            self.readiness_queue_threshold,
+            self.max_header_bytes,
+            self.max_body_bytes,
+            quote_value(self.log_level.as_str()),
+            quote_value(self.log_format.as_str()),
+            self.log_request_bodies,
+            quote_value(self.access_log_format.as_str()),
+            self.access_log_max_bytes,
+            self.access_log_max_files,
+        );
+        if let Some(tls_cert_path) = &self.tls_cert_path {
+            config_text.push_str(&format!("tls_cert_path = {}\n", quote_value(tls_cert_path)));
+        }
+        if let Some(tls_key_path) = &self.tls_key_path {
+            config_text.push_str(&format!("tls_key_path = {}\n", quote_value(tls_key_path)));
+        }
+        if let Some(access_log_path) = &self.access_log_path {
+            config_text.push_str(&format!("access_log_path = {}\n", quote_value(access_log_path)));
+        }
+        for endpoint_name in self.endpoint_names() {
+            config_text.push_str(&format!("\n[endpoint.{}]\n", endpoint_name));
+            let mut written_keys: Vec<&str> = Vec::new();
+            for (name, key, _) in &self.endpoint_settings {
+                if name != endpoint_name || written_keys.contains(&key.as_str()) {
+                    continue;
+                }
+                written_keys.push(key);
+                let value = self.endpoint_setting(endpoint_name, key).unwrap_or_default();
+                config_text.push_str(&format!("{} = {}\n", key, quote_value(value)));
+            }
+        }
+        config_text
+    }
+
+    /// Sets one setting by its config-file key
+    ///
+    /// Server keys are parsed here; endpoint.NAME.KEY settings are only
+    /// stored, and checked later by check_endpoint_settings.
+    ///
+    /// # Arguments
+    /// * `key` - e.g. "max_queue_size", or "endpoint.llamacpp.model_path"
+    /// * `value` - Unquoted value text
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - Err for an unknown server key, an unparseable value
+    ///   or a malformed endpoint.NAME.KEY
+    pub fn apply_setting(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if let Some(endpoint_key) = key.strip_prefix("endpoint.") {
+            let Some((endpoint_name, setting_key)) = endpoint_key.split_once('.') else {
+                return Err(format!("endpoint setting '{}' must be endpoint.NAME.KEY", key));
+            };
+            if endpoint_name.is_empty() || setting_key.is_empty() {
+                return Err(format!("endpoint setting '{}' must be endpoint.NAME.KEY", key));
+            }
+            self.endpoint_settings.push((
+                endpoint_name.to_string(),
+                setting_key.to_string(),
+                value.to_string(),
+            ));
+            return Ok(());
+        }
+
+        match key {
+            "bind_address" => {
+                if value.is_empty() {
+                    return Err("bind_address must not be empty".to_string());
+                }
+                self.bind_address = value.to_string();
+            }
+            "max_queue_size" => {
+                self.max_queue_size = parse_number(key, value)?;
+                if self.max_queue_size == 0 {
+                    return Err("max_queue_size must be at least 1".to_string());
+                }
+            }
+            "processing_delay_ms" => self.processing_delay_ms = parse_number(key, value)?,
+            "request_handler_pause_ms" => self.request_handler_pause_ms = parse_number(key, value)?,
+            "stream_read_timeout_ms" => self.stream_read_timeout_ms = parse_nonzero_timeout(key, value)?,
+            "request_read_timeout_ms" => self.request_read_timeout_ms = parse_nonzero_timeout(key, value)?,
+            "stream_write_timeout_ms" => self.stream_write_timeout_ms = parse_nonzero_timeout(key, value)?,
+            "queue_full_cool_off_ms" => self.queue_full_cool_off_ms = parse_number(key, value)?,
+            "processing_timeout_ms" => self.processing_timeout_ms = parse_nonzero_timeout(key, value)?,
+            "readiness_queue_threshold" => self.readiness_queue_threshold = parse_number(key, value)?,
+            "max_header_bytes" => self.max_header_bytes = parse_number(key, value)?,
+            "max_body_bytes" => self.max_body_bytes = parse_number(key, value)?,
+            "tls_cert_path" => self.tls_cert_path = parse_optional_path(value),
+            "tls_key_path" => self.tls_key_path = parse_optional_path(value),
+            "log_level" => self.log_level = LogLevel::from_config_value(value)?,
+            "log_format" => self.log_format = LogFormat::from_config_value(value)?,
+            "log_request_bodies" => self.log_request_bodies = parse_bool(key, value)?,
+            "access_log_path" => self.access_log_path = parse_optional_path(value),
+            "access_log_format" => self.access_log_format = AccessLogFormat::from_config_value(value)?,
+            "access_log_max_bytes" => self.access_log_max_bytes = parse_number(key, value)?,
+            "access_log_max_files" => self.access_log_max_files = parse_number(key, value)?,
+            _ => return Err(format!("unknown config key '{}'", key)),
+        }
+        Ok(())
+    }
+}
+
+/// Returns the server config (the defaults if none was installed)
+pub fn server_config() -> &'static ServerConfig {
+    SERVER_CONFIG.get_or_init(ServerConfig::default)
+}
+
+/// Stores the loaded config for server_config(); only the first call has effect
+///
+/// # Returns
+/// * `Result<(), String>` - Err if a config was already installed
+pub fn install_server_config(config: ServerConfig) -> Result<(), String> {
+    SERVER_CONFIG
+        .set(config)
+        .map_err(|_| "server config was already installed".to_string())
+}
+
+/// Builds the config from defaults, config file, environment and flags
+///
+/// # Arguments
+/// * `config_path_flag` - Config file from --config, if given (wins over FIDDLER_CRAB_CONFIG)
+/// * `flag_settings` - (config key, value) pairs from the command line, in order
+/// * `env_vars` - Environment (name, value) pairs, normally `std::env::vars()`
+///
+/// # Returns
+/// * `Result<ServerConfig, String>` - The config, or the first problem found
+pub fn load_server_config(
+    config_path_flag: Option<&str>,
+    flag_settings: &[(String, String)],
+    env_vars: impl Iterator<Item = (String, String)>,
+) -> Result<ServerConfig, String> {
+    let env_vars: Vec<(String, String)> = env_vars.filter(|(name, _)| name.starts_with(ENV_PREFIX)).collect();
+    let mut config = ServerConfig::default();
+
+    // 2. Config file: the flag wins over the environment variable
+    let config_path: Option<String> = match config_path_flag {
+        Some(config_path) => Some(config_path.to_string()),
+        None => env_vars
+            .iter()
+            .find(|(name, _)| name == ENV_CONFIG_PATH)
+            .map(|(_, value)| value.clone()),
+    };
+    if let Some(config_path) = config_path {
+        let config_text = std::fs::read_to_string(&config_path)
+            .map_err(|e| format!("cannot read config file {}: {}", config_path, e))?;
+        let file_settings = parse_config_text(&config_text).map_err(|e| format!("{} {}", config_path, e))?;
+        for (line_number, key, value) in file_settings {
+            config
+                .apply_setting(&key, &value)
+                .map_err(|e| format!("{} line {}: {}", config_path, line_number, e))?;
+        }
+    }
+
+    // 3. Environment variables
+    for (name, value) in &env_vars {
+        if name == ENV_CONFIG_PATH {
+            continue;
+        }
+        let key = config_key_from_env_name(&name[ENV_PREFIX.len()..]);
+        config
+            .apply_setting(&key, value)
+            .map_err(|e| format!("environment variable {}: {}", name, e))?;
+    }
+
+    // 4. Command line flags
+    for (key, value) in flag_settings {
+        config
+            .apply_setting(key, value)
+            .map_err(|e| format!("command line setting {}: {}", key, e))?;
+    }
+
+    Ok(config)
+}
+
+/// Parses config file text into (line number, key, value) settings
+///
+/// Keys inside an [endpoint.NAME] section come back as "endpoint.NAME.key".
+///
+/// # Returns
+/// * `Result<Vec<(usize, String, String)>, String>` - Settings in file order, or the first syntax error
+pub fn parse_config_text(config_text: &str) -> Result<Vec<(usize, String, String)>, String> {
+    let mut settings: Vec<(usize, String, String)> = Vec::new();
+    let mut current_section: Option<String> = None;
+
+    for (line_index, raw_line) in config_text.lines().enumerate() {
+        let line_number = line_index + 1;
+        let line = strip_comment(raw_line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(section) = line.strip_prefix('[') {
+            let Some(section) = section.strip_suffix(']') else {
+                return Err(format!("line {}: unclosed section header", line_number));
+            };
+            let section = section.trim();
+            match section.strip_prefix("endpoint.") {
+                Some(endpoint_name) if !endpoint_name.is_empty() && !endpoint_name.contains('.') => {
+                    current_section = Some(section.to_string());
+                }
+                _ => return Err(format!("line {}: unknown section [{}]", line_number, section)),
+            }
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("line {}: expected key = value", line_number));
+        };
+        let key = key.trim();
+        if key.is_empty() || !key.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'_') {
+            return Err(format!("line {}: invalid key '{}'", line_number, key));
+        }
+        let value = unquote_value(value.trim())
+            .ok_or_else(|| format!("line {}: unterminated quoted value", line_number))?;
+
+        let full_key = match &current_section {
+            Some(section) => format!("{}.{}", section, key),
+            None => key.to_string(),
+        };
+        settings.push((line_number, full_key, value));
+    }
+
+    Ok(settings)
+}
+
+/// Removes a trailing # comment that is not inside a quoted value
+fn strip_comment(line: &str) -> &str {
+    let mut in_quotes = false;
+    for (index, character) in line.char_indices() {
+        match character {
+            '"' => in_quotes = !in_quotes,
+            '#' if !in_quotes => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Returns the value without surrounding double quotes; None if a quote is unclosed
+///
+/// Inside quotes, \" \\ \n and \t are unescaped (e.g. for multi-line prompt templates).
+fn unquote_value(value: &str) -> Option<String> {
+    let Some(quoted) = value.strip_prefix('"') else {
+        return Some(value.to_string());
+    };
+    let inner = quoted.strip_suffix('"')?;
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut characters = inner.chars();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            unquoted.push(character);
+            continue;
+        }
+        match characters.next() {
+            Some('n') => unquoted.push('\n'),
+            Some('t') => unquoted.push('\t'),
+            Some('"') => unquoted.push('"'),
+            Some('\\') => unquoted.push('\\'),
+            // Unknown escapes are kept as written
+            Some(other) => {
+                unquoted.push('\\');
+                unquoted.push(other);
+            }
+            None => unquoted.push('\\'),
+        }
+    }
+    Some(unquoted)
+}
+
+/// Writes a value as a double-quoted config string (the inverse of unquote_value)
+fn quote_value(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for character in value.chars() {
+        match character {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            _ => quoted.push(character),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// "MAX_QUEUE_SIZE" -> "max_queue_size"; "ENDPOINT__LLAMACPP__MODEL_PATH" -> "endpoint.llamacpp.model_path"
+fn config_key_from_env_name(env_name: &str) -> String {
+    match env_name.strip_prefix(ENV_ENDPOINT_PREFIX) {
+        Some(endpoint_part) => format!("endpoint.{}", endpoint_part.replacen("__", ".", 1).to_ascii_lowercase()),
+        None => env_name.to_ascii_lowercase(),
+    }
+}
+
+/// Parses an unsigned number setting
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("{} must be a non-negative whole number, got '{}'", key, value))
+}
+
+/// An empty path means "not set" (so an env var or flag can switch HTTPS back off)
+fn parse_optional_path(value: &str) -> Option<String> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+/// Parses a timeout that must not be zero (a zero socket timeout is an error in std)
+fn parse_nonzero_timeout(key: &str, value: &str) -> Result<u64, String> {
+    let timeout_ms: u64 = parse_number(key, value)?;
+    if timeout_ms == 0 {
+        return Err(format!("{} must be at least 1", key));
+    }
+    Ok(timeout_ms)
+}
+
+/// Parses true/false
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("{} must be true or false, got '{}'", key, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env_vars(pairs: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn config_text_parses_sections_quotes_and_comments() {
+        let config_text = "# server\n\
+                           max_queue_size = 200   # trailing comment\n\
+                           bind_address = \"0.0.0.0:8080\"\n\
+                           \n\
+                           [endpoint.llamacpp]\n\
+                           prompt_template = \"say \\\"hi\\\" # not a comment\\n{prompt}\"\n";
+        let settings = parse_config_text(config_text).unwrap();
+        assert_eq!(
+            settings,
+            vec![
+                (2, "max_queue_size".to_string(), "200".to_string()),
+                (3, "bind_address".to_string(), "0.0.0.0:8080".to_string()),
+                (6, "endpoint.llamacpp.prompt_template".to_string(), "say \"hi\" # not a comment\n{prompt}".to_string()),
+            ]
+        );
+
+        for (bad_text, expected_error) in [
+            ("[endpoint.llamacpp\n", "line 1: unclosed section header"),
+            ("[server]\n", "line 1: unknown section [server]"),
+            ("[endpoint.a.b]\n", "line 1: unknown section [endpoint.a.b]"),
+            ("\nmax_queue_size 200\n", "line 2: expected key = value"),
+            ("max-queue = 1\n", "line 1: invalid key 'max-queue'"),
+            ("bind_address = \"open\n", "line 1: unterminated quoted value"),
+        ] {
+            assert_eq!(parse_config_text(bad_text).unwrap_err(), expected_error);
+        }
+    }
+
+    #[test]
+    fn env_names_map_to_config_keys() {
+        assert_eq!(config_key_from_env_name("MAX_QUEUE_SIZE"), "max_queue_size");
+        assert_eq!(config_key_from_env_name("ENDPOINT__LLAMACPP__MODEL_PATH"), "endpoint.llamacpp.model_path");
+        assert_eq!(
+            config_key_from_env_name("ENDPOINT__LLAMACPP_EMBEDDING__BINARY_PATH"),
+            "endpoint.llamacpp_embedding.binary_path"
+        );
+    }
+
+    #[test]
+    fn file_then_environment_then_flags() {
+        let config_path = std::env::temp_dir().join(format!("fiddler_crab_config_test_{}.toml", std::process::id()));
+        std::fs::write(
+            &config_path,
+            "max_queue_size = 10\nprocessing_delay_ms = 1\nlog_level = \"warn\"\n[endpoint.llamacpp]\nmodel_path = \"/from/file\"\n",
+        )
+        .unwrap();
+        let config_path_text = config_path.to_str().unwrap();
+
+        let flag_settings = vec![("max_queue_size".to_string(), "30".to_string())];
+        let config = load_server_config(
+            None,
+            &flag_settings,
+            env_vars(&[
+                ("FIDDLER_CRAB_CONFIG", config_path_text),
+                ("FIDDLER_CRAB_MAX_QUEUE_SIZE", "20"),
+                ("FIDDLER_CRAB_PROCESSING_DELAY_MS", "2"),
+                ("FIDDLER_CRAB_ENDPOINT__LLAMACPP__MODEL_PATH", "/from/env"),
+                ("HOME", "/not/a/setting"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.max_queue_size, 30);
+        assert_eq!(config.processing_delay_ms, 2);
+        assert_eq!(config.log_level, LogLevel::Warn);
+        assert_eq!(config.endpoint_setting("llamacpp", "model_path"), Some("/from/env"));
+        assert_eq!(config.stream_read_timeout_ms, DEFAULT_STREAM_READ_TIMEOUT_MS);
+
+        // --config wins over FIDDLER_CRAB_CONFIG
+        let missing_path_error = load_server_config(
+            Some("/nonexistent/fiddler_crab.toml"),
+            &[],
+            env_vars(&[("FIDDLER_CRAB_CONFIG", config_path_text)]),
+        )
+        .unwrap_err();
+        assert!(missing_path_error.starts_with("cannot read config file /nonexistent/fiddler_crab.toml"));
+
+        // Errors name where the bad setting came from
+        std::fs::write(&config_path, "max_queue_size = 10\nmax_queue_sise = 20\n").unwrap();
+        let file_error = load_server_config(Some(config_path_text), &[], env_vars(&[])).unwrap_err();
+        assert_eq!(file_error, format!("{} line 2: unknown config key 'max_queue_sise'", config_path_text));
+        std::fs::remove_file(&config_path).unwrap();
+
+        let env_error = load_server_config(None, &[], env_vars(&[("FIDDLER_CRAB_MAX_QUEUE_SIZE", "lots")])).unwrap_err();
+        assert_eq!(
+            env_error,
+            "environment variable FIDDLER_CRAB_MAX_QUEUE_SIZE: max_queue_size must be a non-negative whole number, got 'lots'"
+        );
+        let flag_settings = vec![("stream_read_timeout_ms".to_string(), "0".to_string())];
+        let flag_error = load_server_config(None, &flag_settings, env_vars(&[])).unwrap_err();
+        assert_eq!(flag_error, "command line setting stream_read_timeout_ms: stream_read_timeout_ms must be at least 1");
+    }
+
+    #[test]
+    fn config_text_round_trips() {
+        let mut config = ServerConfig::default();
+        config.apply_setting("bind_address", "0.0.0.0:9000").unwrap();
+        config.apply_setting("access_log_path", "/var/log/fiddler crab.log").unwrap();
+        config.apply_setting("endpoint.llamacpp.prompt_template", "a \"b\"\n{prompt}").unwrap();
+        config.apply_setting("endpoint.llamacpp.prompt_template", "last wins {prompt}").unwrap();
+
+        let mut reloaded_config = ServerConfig::default();
+        for (_, key, value) in parse_config_text(&config.to_config_text()).unwrap() {
+            reloaded_config.apply_setting(&key, &value).unwrap();
+        }
+        assert_eq!(reloaded_config.to_config_text(), config.to_config_text());
+        assert_eq!(reloaded_config.endpoint_setting("llamacpp", "prompt_template"), Some("last wins {prompt}"));
+        assert_eq!(
+            config.apply_setting("endpoint.llamacpp", "x").unwrap_err(),
+            "endpoint setting 'endpoint.llamacpp' must be endpoint.NAME.KEY"
+        );
+    }
+}
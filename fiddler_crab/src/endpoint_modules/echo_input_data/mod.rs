@@ -1,5 +1,5 @@
 pub mod input_enum;
 pub mod output_enum;
-pub mod struct;
+pub mod r#struct;
 pub mod parse;
 pub mod module;
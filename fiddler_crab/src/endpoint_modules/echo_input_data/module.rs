@@ -1,24 +1,48 @@
 use super::input_enum::EchoInputDataFields;
 use super::output_enum::EchoInputDataOutputFields;
-use super::struct::EchoInputDataModuleData;
+use super::parse::{parse_echo_input_data, parse_echo_input_data_query};
+use super::r#struct::EchoInputDataModule;
+use crate::endpoint_modules::endpoint_module::EndpointModule;
+use crate::json::JsonValue;
+use crate::RequestUnit;
 
 /// Processes the echo_input_data request by echoing back the input
 /// 
 /// # Arguments
-/// * `module_data` - The EchoInputDataModuleData containing the input to echo
+/// * `input` - The parsed input to echo
 /// 
 /// # Returns
-/// * `Result<EchoInputDataModuleData, String>` - The processed data or an error
-pub fn process_echo_request(mut module_data: EchoInputDataModuleData) 
-    -> Result<EchoInputDataModuleData, String> {
+/// * `Result<EchoInputDataOutputFields, String>` - The echoed output or an error
+pub fn process_echo_request(input: EchoInputDataFields) 
+    -> Result<EchoInputDataOutputFields, String> {
     
     // Extract the input string
-    let input_string = match &module_data.input {
-        EchoInputDataFields::InputString(s) => s.clone(),
-    };
+    let EchoInputDataFields::InputString(input_string) = input;
     
     // Echo it back as output
-    module_data.output = EchoInputDataOutputFields::EchoedString(input_string);
-    
-    Ok(module_data)
+    Ok(EchoInputDataOutputFields::EchoedString(input_string))
+}
+
+impl EndpointModule for EchoInputDataModule {
+    type Input = EchoInputDataFields;
+    type Output = EchoInputDataOutputFields;
+
+    fn parse(request_unit: &RequestUnit) -> Result<Self::Input, String> {
+        if request_unit.method == "GET" {
+            return parse_echo_input_data_query(request_unit.query_parameter("input_string"));
+        }
+        parse_echo_input_data(&request_unit.body, request_unit.request_header("Content-Type"))
+    }
+
+    fn process(input: Self::Input) -> Result<Self::Output, String> {
+        process_echo_request(input)
+    }
+
+    fn serialize_output(output: &Self::Output) -> Result<JsonValue, String> {
+        match output {
+            EchoInputDataOutputFields::EchoedString(s) => Ok(JsonValue::object(vec![
+                ("echoed_string", JsonValue::String(s.clone())),
+            ])),
+        }
+    }
 }
@@ -1,23 +1,43 @@
 use super::input_enum::EchoInputDataFields;
-use super::struct::EchoInputDataModuleData;
-use super::output_enum::EchoInputDataOutputFields;
+use crate::json::{is_json_content_type, parse_json, JsonLimits, JsonValue};
 
-/// Parses raw request body string into the EchoInputDataModuleData structure
+/// Parses the request body into the echo_input_data input fields
+/// 
+/// A JSON body (Content-Type: application/json) must be an object with a
+/// string field "input_string"; any other body is echoed as-is.
 /// 
 /// # Arguments
 /// * `request_body` - The raw string from the request body
+/// * `content_type` - The request's Content-Type header, if any
 /// 
 /// # Returns
-/// * `Result<EchoInputDataModuleData, String>` - The parsed data structure or an error
-pub fn parse_echo_input_data(request_body: &str) -> Result<EchoInputDataModuleData, String> {
-    // For now, simply wrap the input string
-    let input_fields = EchoInputDataFields::InputString(request_body.to_string());
-    
-    // Initialize with empty output
-    let output_fields = EchoInputDataOutputFields::EchoedString(String::new());
-    
-    Ok(EchoInputDataModuleData {
-        input: input_fields,
-        output: output_fields,
-    })
+/// * `Result<EchoInputDataFields, String>` - The parsed input or an error
+pub fn parse_echo_input_data(request_body: &str, content_type: Option<&str>) -> Result<EchoInputDataFields, String> {
+    if !is_json_content_type(content_type) {
+        // Plain body: simply wrap the input string
+        return Ok(EchoInputDataFields::InputString(request_body.to_string()));
+    }
+
+    let json_body = parse_json(request_body, &JsonLimits::default())?;
+    let input_string = json_body
+        .get("input_string")
+        .and_then(JsonValue::as_str)
+        .ok_or("JSON body needs a string field \"input_string\"")?;
+
+    Ok(EchoInputDataFields::InputString(input_string.to_string()))
+}
+
+/// Parses a GET request's query into the echo_input_data input fields
+///
+/// e.g. GET /echo_input_data?input_string=hello%20world echoes "hello world".
+///
+/// # Arguments
+/// * `input_string` - The URL-decoded "input_string" query parameter, if present
+///
+/// # Returns
+/// * `Result<EchoInputDataFields, String>` - The parsed input or an error
+pub fn parse_echo_input_data_query(input_string: Option<&str>) -> Result<EchoInputDataFields, String> {
+    input_string
+        .map(|input_string| EchoInputDataFields::InputString(input_string.to_string()))
+        .ok_or_else(|| "GET needs a query parameter \"input_string\"".to_string())
 }
@@ -1,14 +1,6 @@
-use super::input_enum::EchoInputDataFields;
-use super::output_enum::EchoInputDataOutputFields;
-
-/// Holds both input and output data for the echo_input_data endpoint
-/// 
-/// This struct maintains the state of both the input received and
-/// the output to be sent for a single echo request-response cycle.
-#[derive(Debug)]
-pub struct EchoInputDataModuleData {
-    /// The input received from the request
-    pub input: EchoInputDataFields,
-    /// The output to be sent in the response
-    pub output: EchoInputDataOutputFields,
-}
+/// The echo_input_data endpoint module
+///
+/// Echoes the request body back as the response. This is the type the
+/// registry runs through `run_endpoint_module`; its `EndpointModule` impl
+/// is in module.rs.
+pub struct EchoInputDataModule;
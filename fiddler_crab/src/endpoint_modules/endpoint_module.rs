@@ -0,0 +1,106 @@
+/*
+The EndpointModule trait: one shape for every endpoint module
+
+Each module directory keeps its file convention:
+- input_enum.rs   -> the module's Input type
+- output_enum.rs  -> the module's Output type
+- struct.rs       -> the (empty) struct that implements EndpointModule
+- parse.rs        -> the parse step
+- module.rs       -> the process step and the trait impl
+
+run_endpoint_module::<M> drives any module the same way, so the registry
+can hold `run_endpoint_module::<SomeModule>` as a plain function pointer.
+
+Output goes out as JSON with metadata fields (see convert_output_to_json_string),
+unless the module overrides response_body (script endpoints send their
+stdout as-is).
+*/
+use crate::http_response::build_error_body;
+use crate::json::JsonValue;
+use crate::RequestUnit;
+
+/// Parse -> process -> serialize, with module-specific Input and Output types
+pub trait EndpointModule {
+    /// What the parse step produces from the request (the module's input enum)
+    type Input;
+    /// What the process step produces (the module's output enum)
+    type Output;
+
+    /// Parse step: read the request (body, path, headers) into Input
+    fn parse(request_unit: &RequestUnit) -> Result<Self::Input, String>;
+
+    /// Process step: do the module's work
+    fn process(input: Self::Input) -> Result<Self::Output, String>;
+
+    /// Serialize-output step: turn Output into a JSON value (the "output" field of the response)
+    fn serialize_output(output: &Self::Output) -> Result<JsonValue, String>;
+
+    /// Response step: the Content-Type and body of the 200 answer
+    ///
+    /// By default the serialized output wrapped with metadata, as JSON.
+    fn response_body(request_unit: &RequestUnit, output: Self::Output) -> Result<(String, String), String> {
+        let output_json = Self::serialize_output(&output).map_err(|e| format!("Failed to format response: {}", e))?;
+        Ok((
+            "application/json".to_string(),
+            convert_output_to_json_string(request_unit, output_json),
+        ))
+    }
+}
+
+/// Wraps a module's JSON output with metadata fields, as the response body
+///
+/// e.g. {"endpoint_module":"echo_input_data","request_id":3,"output":{...}}
+///
+/// # Arguments
+/// * `request_unit` - The request the output belongs to (for the metadata)
+/// * `output` - The module's serialized output
+///
+/// # Returns
+/// * `String` - The JSON text of the response body
+pub fn convert_output_to_json_string(request_unit: &RequestUnit, output: JsonValue) -> String {
+    let endpoint_module_name = match &request_unit.endpoint_module_name {
+        Some(name) => JsonValue::String(name.clone()),
+        None => JsonValue::Null,
+    };
+    JsonValue::object(vec![
+        ("endpoint_module", endpoint_module_name),
+        ("request_id", JsonValue::Number(request_unit.id as f64)),
+        ("output", output),
+    ])
+    .to_json_string()
+}
+
+/// Sets a request's response status, Content-Type and body
+fn set_response(request_unit: &mut RequestUnit, status: u16, content_type: String, body: String) {
+    request_unit.response_status = Some(status);
+    request_unit.response_headers = Some(vec![("Content-Type".to_string(), content_type)]);
+    request_unit.response_body = Some(body);
+}
+
+/// Runs any EndpointModule on a queued request and fills in its response fields
+///
+/// # Arguments
+/// * `request_unit` - The queued request
+///
+/// # Returns
+/// * `Result<RequestUnit, String>` - The request with response_body (from
+///   the module's response_body step), response_status and
+///   response_headers set, or the error
+///   from the process or response step.
+///   A parse failure is the client's fault, so it is answered as a 400
+///   (standard error body) rather than returned as Err.
+pub fn run_endpoint_module<M: EndpointModule>(mut request_unit: RequestUnit) -> Result<RequestUnit, String> {
+    let input = match M::parse(&request_unit) {
+        Ok(input) => input,
+        Err(e) => {
+            let error_body = build_error_body(400, &format!("Failed to parse input: {}", e));
+            set_response(&mut request_unit, 400, "application/json".to_string(), error_body);
+            return Ok(request_unit);
+        }
+    };
+    let output = M::process(input).map_err(|e| format!("Failed to process: {}", e))?;
+    let (content_type, body) = M::response_body(&request_unit, output)?;
+    set_response(&mut request_unit, 200, content_type, body);
+
+    Ok(request_unit)
+}
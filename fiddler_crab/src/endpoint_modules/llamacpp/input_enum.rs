@@ -1,6 +1,24 @@
 // endpoint_modules/llamacpp/input_enum.rs
+
+/// One field of a llamacpp request
+///
+/// The parse step returns a Vec of these: Prompt always, the others only if
+/// the request set them. Each optional field maps to llama-cli arguments
+/// (see llamacpp_args_for_fields in module.rs).
 #[derive(Debug)]
 pub enum LlamacppInputFields {
-    Prompt(String), // Example input field 
-    // Add other input fields as needed
-}
\ No newline at end of file
+    /// Text to complete (the JSON "prompt" field, or a non-JSON body as-is)
+    Prompt(String),
+    /// Model name from the endpoint.llamacpp.models allow-list ("model")
+    Model(String),
+    /// Max number of tokens to generate ("n_predict", -1 = until the model stops) -> -n
+    NPredict(i64),
+    /// Sampling temperature ("temperature", >= 0) -> --temp
+    Temperature(f64),
+    /// Nucleus sampling threshold ("top_p", 0 to 1) -> --top-p
+    TopP(f64),
+    /// RNG seed for reproducible output ("seed") -> -s
+    Seed(i64),
+    /// Stop sequences ("stop", a string or array of strings); the completion is cut at the first one
+    Stop(Vec<String>),
+}
@@ -0,0 +1,6 @@
+pub mod input_enum;
+pub mod output_enum;
+pub mod r#struct;
+pub mod parse;
+pub mod module;
+pub mod worker;
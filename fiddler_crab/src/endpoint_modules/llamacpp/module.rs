@@ -1,42 +1,350 @@
 // endpoint_modules/llamacpp/module.rs
-// ... (imports as before) ...
+use crate::config::{server_config, ServerConfig};
+use crate::endpoint_modules::endpoint_module::EndpointModule;
+use crate::endpoint_modules::{EndpointSetting, SettingValue};
+use crate::json::JsonValue;
+use crate::subprocess::{run_subprocess, SubprocessSpec};
+use crate::RequestUnit;
+use super::input_enum::LlamacppInputFields;
+use super::output_enum::LlamacppOutputFields;
+use super::parse::parse_llamacpp_request;
+use super::r#struct::LlamacppModule;
+use super::worker::worker_completion;
 
-pub fn llamacpp_endpoint_function(request_unit: RequestUnit) -> Result<RequestUnit, String> {
-    // 1. Parse the request body using the parse_llamacpp_request function
-    let module_data_result = parse_llamacpp_request(request_unit.body.clone()); 
+/// Keys read from [endpoint.llamacpp] (here and in worker.rs), besides the subprocess settings
+pub const LLAMACPP_ENDPOINT_SETTINGS: &[EndpointSetting] = &[
+    EndpointSetting { key: "binary_path", value: SettingValue::Text },
+    EndpointSetting { key: "model_path", value: SettingValue::Text },
+    EndpointSetting { key: "models", value: SettingValue::Text },
+    EndpointSetting { key: "prompt_template", value: SettingValue::Text },
+    EndpointSetting { key: "mode", value: SettingValue::OneOf(&["cli", "server"]) },
+    EndpointSetting { key: "server_binary_path", value: SettingValue::Text },
+    EndpointSetting { key: "server_args", value: SettingValue::Text },
+    EndpointSetting { key: "server_port", value: SettingValue::Port },
+    EndpointSetting { key: "server_startup_timeout_ms", value: SettingValue::Number },
+];
 
-    // 2. Handle potential parsing errors
-    let module_data = match module_data_result {
-        Ok(data) => data,
-        Err(err) => return Err(format!("Failed to parse request: {}", err)),
-    };
+/// llama-cli binary used when endpoint.llamacpp.binary_path is not set (looked up on PATH)
+const DEFAULT_LLAMACPP_BINARY_PATH: &str = "llama-cli";
+
+/// Model name reported when the request names no model and endpoint.llamacpp.model_path is used
+const DEFAULT_LLAMACPP_MODEL_NAME: &str = "default";
 
-    // 3. Extract the prompt from the parsed data
-    let prompt = match module_data.input {
-        LlamacppInputFields::Prompt(s) => s,
+/// Placeholder in endpoint.llamacpp.prompt_template that is replaced by the request's prompt
+const PROMPT_TEMPLATE_PLACEHOLDER: &str = "{prompt}";
+
+/// Reads the named models a request may ask for
+///
+/// Configured as `models = "name=path, name=path"` in [endpoint.llamacpp].
+///
+/// # Arguments
+/// * `config` - The server config (normally server_config())
+///
+/// # Returns
+/// * `Result<Vec<(String, String)>, String>` - (name, model path) pairs, empty if
+///   `models` is not set; Err if an entry is not name=path
+pub fn llamacpp_model_allow_list(config: &ServerConfig) -> Result<Vec<(String, String)>, String> {
+    let Some(models_setting) = config.endpoint_setting("llamacpp", "models") else {
+        return Ok(Vec::new());
     };
+    models_setting
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once('=') {
+            Some((name, path)) if !name.trim().is_empty() && !path.trim().is_empty() => {
+                Ok((name.trim().to_string(), path.trim().to_string()))
+            }
+            _ => Err(format!("endpoint.llamacpp.models entry '{}' is not name=path", entry)),
+        })
+        .collect()
+}
+
+/// Picks the model file: the named model from the allow-list, or model_path
+///
+/// # Returns
+/// * `Result<(String, String), String>` - (model name, model path)
+fn resolve_llamacpp_model(config: &ServerConfig, requested_model_name: Option<&str>) -> Result<(String, String), String> {
+    match requested_model_name {
+        Some(model_name) => llamacpp_model_allow_list(config)?
+            .into_iter()
+            .find(|(name, _)| name == model_name)
+            .ok_or_else(|| format!("model '{}' is not in endpoint.llamacpp.models", model_name)),
+        None => config
+            .endpoint_setting("llamacpp", "model_path")
+            .map(|model_path| (DEFAULT_LLAMACPP_MODEL_NAME.to_string(), model_path.to_string()))
+            .ok_or_else(|| {
+                "llamacpp model is not configured (set endpoint.llamacpp.model_path or name a model from endpoint.llamacpp.models)"
+                    .to_string()
+            }),
+    }
+}
+
+/// Wraps the prompt in endpoint.llamacpp.prompt_template, if one is set
+///
+/// e.g. prompt_template = "<start_of_turn>user\n{prompt}<end_of_turn>\n<start_of_turn>model\n"
+fn apply_prompt_template(config: &ServerConfig, prompt: &str) -> Result<String, String> {
+    match config.endpoint_setting("llamacpp", "prompt_template") {
+        None => Ok(prompt.to_string()),
+        Some(prompt_template) if prompt_template.contains(PROMPT_TEMPLATE_PLACEHOLDER) => {
+            Ok(prompt_template.replace(PROMPT_TEMPLATE_PLACEHOLDER, prompt))
+        }
+        Some(_) => Err(format!(
+            "endpoint.llamacpp.prompt_template has no {} placeholder",
+            PROMPT_TEMPLATE_PLACEHOLDER
+        )),
+    }
+}
+
+/// Maps the optional request fields to llama-cli arguments
+///
+/// Each stop sequence becomes a `-r` (reverse prompt), so llama-cli halts
+/// there instead of generating up to `-n` tokens; the caller still cuts the
+/// completion at the stop sequences, since llama-cli prints the one it hit.
+/// Prompt and Model are handled by the caller.
+fn llamacpp_args_for_fields(input_fields: &[LlamacppInputFields]) -> Vec<String> {
+    let mut llamacpp_args = Vec::new();
+    for input_field in input_fields {
+        let (flag, value) = match input_field {
+            LlamacppInputFields::NPredict(n_predict) => ("-n", n_predict.to_string()),
+            LlamacppInputFields::Temperature(temperature) => ("--temp", temperature.to_string()),
+            LlamacppInputFields::TopP(top_p) => ("--top-p", top_p.to_string()),
+            LlamacppInputFields::Seed(seed) => ("-s", seed.to_string()),
+            LlamacppInputFields::Stop(stop_sequences) => {
+                for stop_sequence in stop_sequences {
+                    llamacpp_args.push("-r".to_string());
+                    llamacpp_args.push(stop_sequence.clone());
+                }
+                continue;
+            }
+            LlamacppInputFields::Prompt(_) | LlamacppInputFields::Model(_) => continue,
+        };
+        llamacpp_args.push(flag.to_string());
+        llamacpp_args.push(value);
+    }
+    llamacpp_args
+}
+
+/// Removes the prompt llama-cli echoes before the completion
+///
+/// llama-cli echoes the prompt (unless run with --no-display-prompt),
+/// so a leading copy of it is removed from stdout.
+fn strip_echoed_prompt<'a>(stdout: &'a str, prompt: &str) -> &'a str {
+    match stdout.strip_prefix(prompt) {
+        Some(after_prompt) => after_prompt,
+        // llama-cli may add a leading space or newline in front of the echoed prompt
+        None => stdout.trim_start().strip_prefix(prompt.trim_start()).unwrap_or(stdout),
+    }
+}
+
+/// Cuts the completion at the first stop sequence found in it
+fn cut_at_stop_sequences(completion: &str, stop_sequences: &[String]) -> String {
+    let stop_index = stop_sequences
+        .iter()
+        .filter_map(|stop_sequence| completion.find(stop_sequence.as_str()))
+        .min()
+        .unwrap_or(completion.len());
+    completion[..stop_index].to_string()
+}
+
+/// Builds the llama-server /completion request body for server mode
+///
+/// The sampling fields keep the names llama-server uses, so they pass through as-is.
+fn worker_completion_request(prompt: &str, input_fields: &[LlamacppInputFields]) -> JsonValue {
+    let mut members = vec![("prompt", JsonValue::String(prompt.to_string()))];
+    for input_field in input_fields {
+        match input_field {
+            LlamacppInputFields::NPredict(n_predict) => members.push(("n_predict", JsonValue::Number(*n_predict as f64))),
+            LlamacppInputFields::Temperature(temperature) => members.push(("temperature", JsonValue::Number(*temperature))),
+            LlamacppInputFields::TopP(top_p) => members.push(("top_p", JsonValue::Number(*top_p))),
+            LlamacppInputFields::Seed(seed) => members.push(("seed", JsonValue::Number(*seed as f64))),
+            LlamacppInputFields::Stop(stop_sequences) => members.push((
+                "stop",
+                JsonValue::Array(stop_sequences.iter().cloned().map(JsonValue::String).collect()),
+            )),
+            LlamacppInputFields::Prompt(_) | LlamacppInputFields::Model(_) => {}
+        }
+    }
+    JsonValue::object(members)
+}
+
+/// Runs llama-cli once for this request (mode = "cli", the default)
+fn cli_completion(model_path: String, prompt: &str, input_fields: &[LlamacppInputFields]) -> Result<String, String> {
+    let binary_path = server_config()
+        .endpoint_setting("llamacpp", "binary_path")
+        .unwrap_or(DEFAULT_LLAMACPP_BINARY_PATH);
+
+    // Extra args, output cap, timeout and rlimits from [endpoint.llamacpp]
+    let mut llamacpp_args = vec!["-m".to_string(), model_path, "-p".to_string(), prompt.to_string()];
+    llamacpp_args.extend(llamacpp_args_for_fields(input_fields));
+    let subprocess_spec = SubprocessSpec::new(binary_path, llamacpp_args).apply_endpoint_settings("llamacpp")?;
+    let output = run_subprocess(&subprocess_spec).map_err(|e| format!("Llama.cpp execution error: {}", e))?;
+
+    Ok(strip_echoed_prompt(&output.stdout_as_string(), prompt).to_string())
+}
+
+pub fn llamacpp_endpoint_function(input_fields: Vec<LlamacppInputFields>) -> Result<Vec<LlamacppOutputFields>, String> {
+    // 1. Extract the prompt, model choice and stop sequences from the parsed data
+    let mut prompt = None;
+    let mut requested_model_name = None;
+    let mut stop_sequences: Vec<String> = Vec::new();
+    for input_field in &input_fields {
+        match input_field {
+            LlamacppInputFields::Prompt(text) => prompt = Some(text.as_str()),
+            LlamacppInputFields::Model(model_name) => requested_model_name = Some(model_name.as_str()),
+            LlamacppInputFields::Stop(sequences) => stop_sequences.extend(sequences.iter().cloned()),
+            _ => {}
+        }
+    }
+    let prompt = apply_prompt_template(server_config(), prompt.ok_or("no prompt in the parsed input")?)?;
+
+    // 2. Model from the request's allow-listed name or model_path
+    let (model_name, model_path) = resolve_llamacpp_model(server_config(), requested_model_name)?;
 
-    // 4. Execute Llama.cpp (your original code for calling the external program)
-    let output = Command::new("/home/oops/code/llama_cpp/llama.cpp/llama-cli")
-        .stderr(std::process::Stdio::null()) 
-        .arg("-m")
-        .arg("/home/oops/jan/models/gemma-2-2b-it/gemma-2-2b-it-Q4_K_M.gguf")
-        .arg("-p")
-        .arg(prompt)
-        .output()
-        .expect("Failed to execute llama-cli");
-
-    // 5. Handle the output from Llama.cpp
-    let output_text = if output.status.success() {
-        String::from_utf8_lossy(&output.stdout).to_string()
-    } else {
-        return Err(format!("Llama.cpp execution error: {:?}", output.stderr)); 
+    // 3. Execute Llama.cpp: a llama-cli run per request, or the persistent llama-server worker
+    let completion = match server_config().endpoint_setting("llamacpp", "mode").unwrap_or("cli") {
+        "cli" => cli_completion(model_path, &prompt, &input_fields)?,
+        "server" => worker_completion(
+            &model_path,
+            &worker_completion_request(&prompt, &input_fields),
+            server_config().processing_timeout_for("llamacpp"),
+        )
+        .map_err(|e| format!("Llama.cpp worker error: {}", e))?,
+        other => return Err(format!("endpoint.llamacpp.mode must be \"cli\" or \"server\", got '{}'", other)),
     };
 
-    // 6. Update the RequestUnit with the output
-    let mut updated_request_unit = request_unit;
-    updated_request_unit.response_body = Some(output_text);
-    // ... set other response fields (status, headers) as needed ...
+    // 4. Handle the output from Llama.cpp: keep only what the model generated
+    // (llama-cli and llama-server already stop at a stop sequence; this drops the sequence itself)
+    let completion = cut_at_stop_sequences(&completion, &stop_sequences);
+    Ok(vec![
+        LlamacppOutputFields::Model(model_name),
+        LlamacppOutputFields::Prompt(prompt),
+        LlamacppOutputFields::Completion(completion),
+    ])
+}
+
+impl EndpointModule for LlamacppModule {
+    type Input = Vec<LlamacppInputFields>;
+    type Output = Vec<LlamacppOutputFields>;
+
+    fn parse(request_unit: &RequestUnit) -> Result<Self::Input, String> {
+        parse_llamacpp_request(&request_unit.body, request_unit.request_header("Content-Type"))
+    }
+
+    fn process(input: Self::Input) -> Result<Self::Output, String> {
+        llamacpp_endpoint_function(input)
+    }
+
+    fn serialize_output(output: &Self::Output) -> Result<JsonValue, String> {
+        Ok(JsonValue::object(
+            output
+                .iter()
+                .map(|output_field| match output_field {
+                    LlamacppOutputFields::Model(s) => ("model", JsonValue::String(s.clone())),
+                    LlamacppOutputFields::Prompt(s) => ("prompt", JsonValue::String(s.clone())),
+                    LlamacppOutputFields::Completion(s) => ("completion", JsonValue::String(s.clone())),
+                })
+                .collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn llamacpp_config(llamacpp_settings: &[(&str, &str)]) -> ServerConfig {
+        let mut config = ServerConfig::default();
+        for (key, value) in llamacpp_settings {
+            config.apply_setting(&format!("endpoint.llamacpp.{}", key), value).unwrap();
+        }
+        config
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(name, path)| (name.to_string(), path.to_string())).collect()
+    }
+
+    #[test]
+    fn model_allow_list_parses_name_path_entries() {
+        assert_eq!(llamacpp_model_allow_list(&llamacpp_config(&[])), Ok(Vec::new()));
+        let config = llamacpp_config(&[("models", " small=/m/small.gguf, ,big = /m/big.gguf ")]);
+        assert_eq!(
+            llamacpp_model_allow_list(&config),
+            Ok(pairs(&[("small", "/m/small.gguf"), ("big", "/m/big.gguf")]))
+        );
+
+        for (models_setting, bad_entry) in [("small", "small"), ("small=/m/s.gguf, =/m/b.gguf", "=/m/b.gguf"), ("big=", "big=")] {
+            let config = llamacpp_config(&[("models", models_setting)]);
+            assert_eq!(
+                llamacpp_model_allow_list(&config),
+                Err(format!("endpoint.llamacpp.models entry '{}' is not name=path", bad_entry))
+            );
+        }
+    }
+
+    #[test]
+    fn model_is_resolved_from_the_allow_list_or_model_path() {
+        let config = llamacpp_config(&[("models", "small=/m/small.gguf"), ("model_path", "/m/default.gguf")]);
+        assert_eq!(
+            resolve_llamacpp_model(&config, Some("small")),
+            Ok(("small".to_string(), "/m/small.gguf".to_string()))
+        );
+        assert_eq!(
+            resolve_llamacpp_model(&config, None),
+            Ok(("default".to_string(), "/m/default.gguf".to_string()))
+        );
+        assert_eq!(
+            resolve_llamacpp_model(&config, Some("huge")),
+            Err("model 'huge' is not in endpoint.llamacpp.models".to_string())
+        );
+        assert!(resolve_llamacpp_model(&llamacpp_config(&[]), None).unwrap_err().starts_with("llamacpp model is not configured"));
+    }
+
+    #[test]
+    fn prompt_template_wraps_the_prompt_and_needs_the_placeholder() {
+        assert_eq!(apply_prompt_template(&llamacpp_config(&[]), "hi"), Ok("hi".to_string()));
+        let config = llamacpp_config(&[("prompt_template", "<user>{prompt}</user><model>")]);
+        assert_eq!(apply_prompt_template(&config, "hi"), Ok("<user>hi</user><model>".to_string()));
+        let config = llamacpp_config(&[("prompt_template", "<user></user>")]);
+        assert_eq!(
+            apply_prompt_template(&config, "hi"),
+            Err("endpoint.llamacpp.prompt_template has no {prompt} placeholder".to_string())
+        );
+    }
+
+    #[test]
+    fn echoed_prompt_is_stripped_from_the_output() {
+        assert_eq!(strip_echoed_prompt("Hello there, friend", "Hello there"), ", friend");
+        // A leading space or newline in front of the echo
+        assert_eq!(strip_echoed_prompt("\n Hello there, friend", "Hello there"), ", friend");
+        // No echo (--no-display-prompt): the output is kept whole
+        assert_eq!(strip_echoed_prompt("General Kenobi", "Hello there"), "General Kenobi");
+    }
+
+    #[test]
+    fn completion_is_cut_at_the_earliest_stop_sequence() {
+        let stops = |stops: &[&str]| -> Vec<String> { stops.iter().map(|stop| stop.to_string()).collect() };
+        assert_eq!(cut_at_stop_sequences("one two three", &stops(&["three", "two"])), "one ");
+        // Overlapping stops: the one that starts first wins, whatever its length
+        assert_eq!(cut_at_stop_sequences("abcdef", &stops(&["cde", "bcdefg", "bc"])), "a");
+        assert_eq!(cut_at_stop_sequences("abcdef", &stops(&["zz"])), "abcdef");
+        assert_eq!(cut_at_stop_sequences("abcdef", &[]), "abcdef");
+    }
 
-    Ok(updated_request_unit) 
-}
\ No newline at end of file
+    #[test]
+    fn sampling_fields_map_to_llama_cli_arguments() {
+        let llamacpp_args = llamacpp_args_for_fields(&[
+            LlamacppInputFields::Prompt("hi".to_string()),
+            LlamacppInputFields::NPredict(16),
+            LlamacppInputFields::Temperature(0.5),
+            LlamacppInputFields::TopP(0.9),
+            LlamacppInputFields::Seed(7),
+            LlamacppInputFields::Stop(vec!["\n\n".to_string(), "User:".to_string()]),
+        ]);
+        assert_eq!(
+            llamacpp_args,
+            ["-n", "16", "--temp", "0.5", "--top-p", "0.9", "-s", "7", "-r", "\n\n", "-r", "User:"]
+        );
+    }
+}
@@ -1,6 +1,12 @@
 // endpoint_modules/llamacpp/output_enum.rs
-#[derive(Debug)] 
+
+/// One field of a llamacpp response (the module returns a Vec of these)
+#[derive(Debug)]
 pub enum LlamacppOutputFields {
-    OutputText(String), // Example output field
-    // Add other output fields as needed
-}
\ No newline at end of file
+    /// Name of the model that ran ("default" for endpoint.llamacpp.model_path)
+    Model(String),
+    /// The prompt as sent to llama-cli (after endpoint.llamacpp.prompt_template)
+    Prompt(String),
+    /// Generated text only: the echoed prompt removed, cut at the first stop sequence
+    Completion(String),
+}
@@ -1,19 +1,158 @@
 // endpoint_modules/llamacpp/parse.rs
-// ... (imports as before) ...
+use super::input_enum::LlamacppInputFields;
+use super::module::llamacpp_model_allow_list;
+use crate::config::server_config;
+use crate::json::{is_json_content_type, parse_json, JsonLimits, JsonValue};
 
-pub fn parse_llamacpp_request(request_body: String) -> Result<LlamacppModuleData, String> {
-    // 1. Remove null bytes from the request body (your specified parsing logic)
-    let request_data_without_nulls = request_body.replace('\0', "");
+/// Parses the request body into the llamacpp input fields
+///
+/// A JSON body (Content-Type: application/json) must be an object with a
+/// string field "prompt", and may set "model", "n_predict", "temperature",
+/// "top_p", "seed" and "stop"; any other body is the prompt as-is.
+///
+/// # Returns
+/// * `Result<Vec<LlamacppInputFields>, String>` - Prompt first, then each optional
+///   field that was set; Err (answered as 400) for a wrong type, an out-of-range
+///   value, or a model that is not in the endpoint.llamacpp.models allow-list
+pub fn parse_llamacpp_request(request_body: &str, content_type: Option<&str>) -> Result<Vec<LlamacppInputFields>, String> {
+    if !is_json_content_type(content_type) {
+        // The body is read to exactly Content-Length bytes, so it is the prompt as sent
+        // (no trailing buffer padding to strip)
+        return Ok(vec![LlamacppInputFields::Prompt(request_body.to_string())]);
+    }
 
-    // 2. Assuming the request body after removing null bytes is the prompt
-    let input = LlamacppInputFields::Prompt(request_data_without_nulls);
+    let json_body = parse_json(request_body, &JsonLimits::default())?;
+    let prompt = json_body
+        .get("prompt")
+        .and_then(JsonValue::as_str)
+        .ok_or("JSON body needs a string field \"prompt\"")?;
+    let mut input_fields = vec![LlamacppInputFields::Prompt(prompt.to_string())];
 
-    // 3. Construct the LlamacppModuleData struct
-    let module_data = LlamacppModuleData {
-        input,
-        output: LlamacppOutputFields::OutputText(String::new()), 
-        // ... other metadata fields ...
+    if let Some(model) = json_body.get("model") {
+        let model_name = model.as_str().ok_or("\"model\" must be a string")?;
+        let allow_list = llamacpp_model_allow_list(server_config())?;
+        if !allow_list.iter().any(|(name, _)| name == model_name) {
+            let model_names: Vec<&str> = allow_list.iter().map(|(name, _)| name.as_str()).collect();
+            return Err(format!(
+                "unknown model '{}' (available: {})",
+                model_name,
+                if model_names.is_empty() { "none".to_string() } else { model_names.join(", ") }
+            ));
+        }
+        input_fields.push(LlamacppInputFields::Model(model_name.to_string()));
+    }
+    if let Some(n_predict) = json_body.get("n_predict") {
+        let n_predict = integer_field(n_predict, "n_predict", -1, i32::MAX as i64)?;
+        input_fields.push(LlamacppInputFields::NPredict(n_predict));
+    }
+    if let Some(temperature) = json_body.get("temperature") {
+        let temperature = number_field(temperature, "temperature", 0.0, f64::MAX)?;
+        input_fields.push(LlamacppInputFields::Temperature(temperature));
+    }
+    if let Some(top_p) = json_body.get("top_p") {
+        let top_p = number_field(top_p, "top_p", 0.0, 1.0)?;
+        input_fields.push(LlamacppInputFields::TopP(top_p));
+    }
+    if let Some(seed) = json_body.get("seed") {
+        // llama.cpp seeds are u32; -1 asks for a random seed
+        let seed = integer_field(seed, "seed", -1, u32::MAX as i64)?;
+        input_fields.push(LlamacppInputFields::Seed(seed));
+    }
+    if let Some(stop) = json_body.get("stop") {
+        input_fields.push(LlamacppInputFields::Stop(stop_sequences(stop)?));
+    }
+
+    Ok(input_fields)
+}
+
+/// Reads a JSON number in [minimum, maximum]
+fn number_field(value: &JsonValue, field_name: &str, minimum: f64, maximum: f64) -> Result<f64, String> {
+    match value.as_f64() {
+        Some(number) if number >= minimum && number <= maximum => Ok(number),
+        Some(_) => Err(format!("\"{}\" must be between {} and {}", field_name, minimum, maximum)),
+        None => Err(format!("\"{}\" must be a number", field_name)),
+    }
+}
+
+/// Reads a JSON number that is a whole number in [minimum, maximum]
+fn integer_field(value: &JsonValue, field_name: &str, minimum: i64, maximum: i64) -> Result<i64, String> {
+    match value.as_f64() {
+        Some(number) if number.fract() == 0.0 && number >= minimum as f64 && number <= maximum as f64 => {
+            Ok(number as i64)
+        }
+        _ => Err(format!("\"{}\" must be an integer from {} to {}", field_name, minimum, maximum)),
+    }
+}
+
+/// Reads "stop": one string or an array of strings, none of them empty
+fn stop_sequences(value: &JsonValue) -> Result<Vec<String>, String> {
+    let stop_values = match value {
+        JsonValue::String(_) => std::slice::from_ref(value),
+        JsonValue::Array(values) => values.as_slice(),
+        _ => return Err("\"stop\" must be a string or an array of strings".to_string()),
     };
+    stop_values
+        .iter()
+        .map(|stop_value| match stop_value.as_str() {
+            Some(stop_sequence) if !stop_sequence.is_empty() => Ok(stop_sequence.to_string()),
+            _ => Err("\"stop\" entries must be non-empty strings".to_string()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(value: f64) -> JsonValue {
+        JsonValue::Number(value)
+    }
+
+    #[test]
+    fn number_field_checks_type_and_range() {
+        assert_eq!(number_field(&number(0.5), "top_p", 0.0, 1.0), Ok(0.5));
+        assert_eq!(number_field(&number(1.0), "top_p", 0.0, 1.0), Ok(1.0));
+        assert_eq!(number_field(&number(1.5), "top_p", 0.0, 1.0), Err("\"top_p\" must be between 0 and 1".to_string()));
+        assert_eq!(number_field(&number(-0.1), "top_p", 0.0, 1.0), Err("\"top_p\" must be between 0 and 1".to_string()));
+        assert_eq!(
+            number_field(&JsonValue::String("hot".to_string()), "temperature", 0.0, f64::MAX),
+            Err("\"temperature\" must be a number".to_string())
+        );
+    }
+
+    #[test]
+    fn integer_field_checks_whole_numbers_and_range() {
+        assert_eq!(integer_field(&number(-1.0), "seed", -1, u32::MAX as i64), Ok(-1));
+        assert_eq!(integer_field(&number(u32::MAX as f64), "seed", -1, u32::MAX as i64), Ok(u32::MAX as i64));
+        let seed_error = Err(format!("\"seed\" must be an integer from -1 to {}", u32::MAX));
+        assert_eq!(integer_field(&number(u32::MAX as f64 + 1.0), "seed", -1, u32::MAX as i64), seed_error);
+        assert_eq!(integer_field(&number(-2.0), "seed", -1, u32::MAX as i64), seed_error);
+        assert_eq!(integer_field(&number(2.5), "seed", -1, u32::MAX as i64), seed_error);
+        assert_eq!(integer_field(&JsonValue::Bool(true), "seed", -1, u32::MAX as i64), seed_error);
+    }
+
+    #[test]
+    fn json_fields_are_parsed_in_order_and_out_of_range_values_rejected() {
+        let input_fields = parse_llamacpp_request(
+            r#"{"prompt": "hi", "n_predict": 16, "temperature": 0.7, "stop": ["\n", "END"]}"#,
+            Some("application/json"),
+        )
+        .unwrap();
+        assert_eq!(
+            format!("{:?}", input_fields),
+            r#"[Prompt("hi"), NPredict(16), Temperature(0.7), Stop(["\n", "END"])]"#
+        );
 
-    Ok(module_data)
-}
\ No newline at end of file
+        for (request_body, expected_error) in [
+            (r#"{"prompt": "hi", "n_predict": -2}"#, "\"n_predict\" must be an integer from -1 to 2147483647"),
+            (r#"{"prompt": "hi", "top_p": 2}"#, "\"top_p\" must be between 0 and 1"),
+            (r#"{"prompt": "hi", "stop": [""]}"#, "\"stop\" entries must be non-empty strings"),
+            (r#"{"prompt": "hi", "stop": 3}"#, "\"stop\" must be a string or an array of strings"),
+            (r#"{"prompt": 3}"#, "JSON body needs a string field \"prompt\""),
+            // The default config has no endpoint.llamacpp.models allow-list
+            (r#"{"prompt": "hi", "model": "big"}"#, "unknown model 'big' (available: none)"),
+        ] {
+            assert_eq!(parse_llamacpp_request(request_body, Some("application/json")).unwrap_err(), expected_error);
+        }
+    }
+}
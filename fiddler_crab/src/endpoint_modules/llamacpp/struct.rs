@@ -1,10 +1,8 @@
 // endpoint_modules/llamacpp/struct.rs
-use super::input_enum::LlamacppInputFields;
-use super::output_enum::LlamacppOutputFields;
 
-#[derive(Debug)] 
-pub struct LlamacppModuleData {
-    pub input: LlamacppInputFields,
-    pub output: LlamacppOutputFields, 
-    // ... other metadata fields (e.g., data types) ...
-}
\ No newline at end of file
+/// The llamacpp endpoint module
+///
+/// Runs llama.cpp's llama-cli on the prompt in the request body, with the
+/// model and sampling options the request chose, and returns the completion.
+/// Its `EndpointModule` impl is in module.rs.
+pub struct LlamacppModule;
@@ -0,0 +1,461 @@
+// endpoint_modules/llamacpp/worker.rs
+/*
+Persistent llama.cpp worker: one llama-server on a loopback port
+
+With `mode = "server"` in [endpoint.llamacpp], the module does not start
+llama-cli (and reload the model) for every request. Instead it keeps one
+llama-server child process and sends each request to its /completion:
+- started lazily by the first request, then reused
+- health-checked (GET /health) before each request
+- restarted if it died, stopped answering, or a request names another model
+- not registered with the watchdog: a request timeout does not kill it
+- killed when fiddler_crab exits (Linux: PR_SET_PDEATHSIG)
+
+State is atomics only, like watchdog.rs. A "worker" thread owns the Child,
+waits on it, and clears WORKER_PID when it exits, so a dead worker is
+noticed without polling and never left as a zombie.
+
+    [endpoint.llamacpp]
+    mode = "server"
+    server_binary_path = "llama-server"
+    server_port = 8181
+    server_args = "--ctx-size 4096 --threads 4"
+    server_startup_timeout_ms = 120000
+*/
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::server_config;
+use crate::json::{parse_json, JsonLimits, JsonValue};
+use crate::logger::{log_event, LogLevel};
+use crate::watchdog::kill_process;
+
+/// llama-server binary used when endpoint.llamacpp.server_binary_path is not set (looked up on PATH)
+const DEFAULT_SERVER_BINARY_PATH: &str = "llama-server";
+
+/// Loopback port used when endpoint.llamacpp.server_port is not set
+const DEFAULT_SERVER_PORT: u16 = 8181;
+
+/// How long a starting worker may take to load its model and answer /health
+const DEFAULT_SERVER_STARTUP_TIMEOUT_MS: u64 = 120_000;
+
+/// Timeout for one /health check
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often a starting worker is health-checked
+const HEALTH_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long to wait for a killed worker to be reaped
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Cap on a worker response, in bytes
+const MAX_WORKER_RESPONSE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Size of each read from the worker's socket
+const WORKER_READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Pid of the running worker (0 = none); cleared by its worker thread when it exits
+static WORKER_PID: AtomicU32 = AtomicU32::new(0);
+
+/// Hash of the model path the running worker was started with
+static WORKER_MODEL_KEY: AtomicU64 = AtomicU64::new(0);
+
+/// Set while one thread starts or stops the worker (an abandoned request thread may still be running)
+static WORKER_STARTING: AtomicBool = AtomicBool::new(false);
+
+/// Worker settings from [endpoint.llamacpp]
+struct WorkerSettings {
+    binary_path: String,
+    port: u16,
+    extra_args: Vec<String>,
+    startup_timeout: Duration,
+}
+
+impl WorkerSettings {
+    fn from_config() -> Result<Self, String> {
+        let endpoint_setting = |key| server_config().endpoint_setting("llamacpp", key);
+        let port = match endpoint_setting("server_port") {
+            Some(value) => value
+                .parse::<u16>()
+                .ok()
+                .filter(|port| *port != 0)
+                .ok_or_else(|| format!("endpoint.llamacpp.server_port must be a port number, got '{}'", value))?,
+            None => DEFAULT_SERVER_PORT,
+        };
+        let startup_timeout_ms = match endpoint_setting("server_startup_timeout_ms") {
+            Some(value) => value
+                .parse::<u64>()
+                .map_err(|_| format!("endpoint.llamacpp.server_startup_timeout_ms must be a number, got '{}'", value))?,
+            None => DEFAULT_SERVER_STARTUP_TIMEOUT_MS,
+        };
+        Ok(WorkerSettings {
+            binary_path: endpoint_setting("server_binary_path")
+                .unwrap_or(DEFAULT_SERVER_BINARY_PATH)
+                .to_string(),
+            port,
+            extra_args: endpoint_setting("server_args")
+                .map(|args| args.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default(),
+            startup_timeout: Duration::from_millis(startup_timeout_ms),
+        })
+    }
+}
+
+/// Sends one completion request to the worker, starting or restarting it as needed
+///
+/// # Arguments
+/// * `model_path` - Model the worker must have loaded (a different one means a restart)
+/// * `completion_request` - JSON body for llama-server's POST /completion
+/// * `response_timeout` - How long the whole call may take, a worker (re)start included
+///   (the module's watchdog deadline, so the call gives up no later than the watchdog)
+///
+/// # Returns
+/// * `Result<String, String>` - The generated text (the "content" field), or why not
+pub fn worker_completion(model_path: &str, completion_request: &JsonValue, response_timeout: Duration) -> Result<String, String> {
+    let deadline = Instant::now() + response_timeout;
+    let settings = WorkerSettings::from_config()?;
+    ensure_worker_running(&settings, model_path)?;
+
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err("no time left for the completion after starting llama-server".to_string());
+    }
+    let response_body = send_worker_request(
+        settings.port,
+        "POST",
+        "/completion",
+        Some(&completion_request.to_json_string()),
+        remaining,
+    )
+    .map_err(|e| format!("llama-server request failed: {}", e))?;
+    let response_json = parse_json(
+        &response_body,
+        &JsonLimits {
+            max_input_bytes: MAX_WORKER_RESPONSE_BYTES,
+            ..JsonLimits::default()
+        },
+    )
+    .map_err(|e| format!("llama-server response is not valid JSON: {}", e))?;
+    response_json
+        .get("content")
+        .and_then(JsonValue::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| "llama-server response has no \"content\" string".to_string())
+}
+
+/// Makes sure a healthy worker with `model_path` loaded is listening on the configured port
+fn ensure_worker_running(settings: &WorkerSettings, model_path: &str) -> Result<(), String> {
+    let model_key = model_key(model_path);
+    let worker_pid = WORKER_PID.load(Ordering::SeqCst);
+    if worker_pid != 0 && WORKER_MODEL_KEY.load(Ordering::SeqCst) == model_key && worker_is_healthy(settings.port) {
+        return Ok(());
+    }
+
+    if WORKER_STARTING
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        // Another (abandoned) request thread is starting it: wait for that instead of racing
+        return wait_for_other_start(settings, model_key);
+    }
+    let start_result = restart_worker(settings, model_path, model_key);
+    WORKER_STARTING.store(false, Ordering::SeqCst);
+    start_result
+}
+
+/// Stops any current worker, starts a new one and waits until it answers /health
+fn restart_worker(settings: &WorkerSettings, model_path: &str, model_key: u64) -> Result<(), String> {
+    stop_worker()?;
+    if TcpStream::connect_timeout(&loopback_address(settings.port), HEALTH_CHECK_TIMEOUT).is_ok() {
+        return Err(format!(
+            "port {} is already in use by another program (set endpoint.llamacpp.server_port)",
+            settings.port
+        ));
+    }
+
+    let worker_pid = spawn_worker(settings, model_path)?;
+    WORKER_MODEL_KEY.store(model_key, Ordering::SeqCst);
+
+    let deadline = Instant::now() + settings.startup_timeout;
+    loop {
+        if WORKER_PID.load(Ordering::SeqCst) != worker_pid {
+            return Err("llama-server exited during startup (check server_binary_path, model_path and server_args)".to_string());
+        }
+        if worker_is_healthy(settings.port) {
+            log_event(
+                LogLevel::Info,
+                "llama-server worker started",
+                &[("pid", &worker_pid.to_string()), ("port", &settings.port.to_string()), ("model_path", model_path)],
+            );
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            kill_process(worker_pid);
+            return Err(format!(
+                "llama-server did not become healthy within {} ms",
+                settings.startup_timeout.as_millis()
+            ));
+        }
+        thread::sleep(HEALTH_POLL_INTERVAL);
+    }
+}
+
+/// Waits while another thread starts the worker, then checks the result
+fn wait_for_other_start(settings: &WorkerSettings, model_key: u64) -> Result<(), String> {
+    let deadline = Instant::now() + settings.startup_timeout;
+    while WORKER_STARTING.load(Ordering::SeqCst) && Instant::now() < deadline {
+        thread::sleep(HEALTH_POLL_INTERVAL);
+    }
+    if WORKER_PID.load(Ordering::SeqCst) != 0
+        && WORKER_MODEL_KEY.load(Ordering::SeqCst) == model_key
+        && worker_is_healthy(settings.port)
+    {
+        Ok(())
+    } else {
+        Err("llama-server is being restarted; try again".to_string())
+    }
+}
+
+/// Kills the current worker, if any, and waits until its worker thread has reaped it
+fn stop_worker() -> Result<(), String> {
+    let worker_pid = WORKER_PID.load(Ordering::SeqCst);
+    if worker_pid == 0 {
+        return Ok(());
+    }
+    kill_process(worker_pid);
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    while WORKER_PID.load(Ordering::SeqCst) == worker_pid {
+        if Instant::now() >= deadline {
+            return Err(format!("old llama-server (pid {}) did not exit", worker_pid));
+        }
+        thread::sleep(HEALTH_POLL_INTERVAL);
+    }
+    Ok(())
+}
+
+/// Starts llama-server from a long-lived worker thread that owns and reaps it
+///
+/// # Returns
+/// * `Result<u32, String>` - The worker's pid (already stored in WORKER_PID)
+fn spawn_worker(settings: &WorkerSettings, model_path: &str) -> Result<u32, String> {
+    let mut command = Command::new(&settings.binary_path);
+    command
+        .arg("-m")
+        .arg(model_path)
+        .arg("--host")
+        .arg(Ipv4Addr::LOCALHOST.to_string())
+        .arg("--port")
+        .arg(settings.port.to_string())
+        .args(&settings.extra_args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // Own process group, so a kill reaches everything the worker started
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    kill_with_parent(&mut command);
+
+    let binary_path = settings.binary_path.clone();
+    let (pid_sender, pid_receiver) = std::sync::mpsc::channel();
+    thread::Builder::new()
+        .name("llamacpp-worker".to_string())
+        .spawn(move || {
+            // Spawned here, not in the request thread: PR_SET_PDEATHSIG fires when
+            // the spawning thread ends, and this thread lives exactly as long as the worker
+            let mut child = match command.spawn() {
+                Ok(child) => child,
+                Err(e) => {
+                    let _ = pid_sender.send(Err(format!("failed to start {}: {}", binary_path, e)));
+                    return;
+                }
+            };
+            let worker_pid = child.id();
+            WORKER_PID.store(worker_pid, Ordering::SeqCst);
+            let _ = pid_sender.send(Ok(worker_pid));
+            let exit_status = match child.wait() {
+                Ok(exit_status) => exit_status.to_string(),
+                Err(e) => e.to_string(),
+            };
+            log_event(
+                LogLevel::Info,
+                "llama-server worker exited",
+                &[("pid", &worker_pid.to_string()), ("status", &exit_status)],
+            );
+            let _ = WORKER_PID.compare_exchange(worker_pid, 0, Ordering::SeqCst, Ordering::SeqCst);
+        })
+        .map_err(|e| format!("could not start llamacpp worker thread: {}", e))?;
+
+    pid_receiver
+        .recv()
+        .map_err(|_| "llamacpp worker thread ended before starting llama-server".to_string())?
+}
+
+/// Asks the kernel to kill the worker if fiddler_crab (the spawning thread) goes away
+#[cfg(target_os = "linux")]
+fn kill_with_parent(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    extern "C" {
+        fn prctl(option: i32, argument: std::ffi::c_ulong, ...) -> i32;
+    }
+    const PR_SET_PDEATHSIG: i32 = 1;
+    const SIGKILL: std::ffi::c_ulong = 9;
+
+    // SAFETY: the closure only calls prctl(2), which is async-signal-safe; it allocates nothing.
+    unsafe {
+        command.pre_exec(|| {
+            if prctl(PR_SET_PDEATHSIG, SIGKILL) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kill_with_parent(_command: &mut Command) {}
+
+/// True if the worker answers GET /health with 200
+fn worker_is_healthy(port: u16) -> bool {
+    send_worker_request(port, "GET", "/health", None, HEALTH_CHECK_TIMEOUT).is_ok()
+}
+
+/// Sends one HTTP/1.1 request to the worker and returns the body of a 200 response
+///
+/// Uses `Connection: close` and reads to end of stream, so no chunked or
+/// keep-alive handling is needed. `timeout` bounds the whole exchange, not
+/// each read, so a worker that trickles its answer cannot outlast the
+/// request's processing deadline (worker_completion passes the watchdog's).
+fn send_worker_request(port: u16, method: &str, path: &str, body: Option<&str>, timeout: Duration) -> Result<String, String> {
+    let deadline = Instant::now() + timeout;
+    let mut tcp_stream = TcpStream::connect_timeout(&loopback_address(port), HEALTH_CHECK_TIMEOUT.min(timeout))
+        .map_err(|e| format!("cannot connect to 127.0.0.1:{}: {}", port, e))?;
+    tcp_stream.set_write_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+
+    let body = body.unwrap_or("");
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: 127.0.0.1:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        port,
+        body.len(),
+        body
+    );
+    tcp_stream.write_all(request.as_bytes()).map_err(|e| e.to_string())?;
+
+    let mut response = Vec::new();
+    let mut chunk = [0u8; WORKER_READ_CHUNK_SIZE];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(format!("no complete answer to {} {} within {} ms", method, path, timeout.as_millis()));
+        }
+        tcp_stream.set_read_timeout(Some(remaining)).map_err(|e| e.to_string())?;
+        match tcp_stream.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(bytes_read) => {
+                if response.len() + bytes_read > MAX_WORKER_RESPONSE_BYTES {
+                    return Err(format!("llama-server response is larger than {} bytes", MAX_WORKER_RESPONSE_BYTES));
+                }
+                response.extend_from_slice(&chunk[..bytes_read]);
+            }
+            // Read timeout: the deadline check above decides
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    let response = String::from_utf8_lossy(&response);
+
+    let (head, response_body) = response
+        .split_once("\r\n\r\n")
+        .ok_or("incomplete HTTP response from llama-server")?;
+    let status_code = head
+        .split_whitespace()
+        .nth(1)
+        .ok_or("malformed HTTP response from llama-server")?;
+    if status_code != "200" {
+        return Err(format!("llama-server answered {} {} with status {}", method, path, status_code));
+    }
+    Ok(response_body.to_string())
+}
+
+/// 127.0.0.1:port
+fn loopback_address(port: u16) -> SocketAddr {
+    SocketAddr::from((Ipv4Addr::LOCALHOST, port))
+}
+
+/// Identifies a model path in an atomic
+fn model_key(model_path: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    model_path.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// A one-shot fake worker: answers the first connection with `response`,
+    /// written in pieces `pause` apart, and returns its port
+    fn fake_worker_answering(response: &'static str, pieces: usize, pause: Duration) -> u16 {
+        let listener = TcpListener::bind(loopback_address(0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut tcp_stream, _) = listener.accept().unwrap();
+            let mut request_head = [0u8; 1024];
+            let _ = tcp_stream.read(&mut request_head);
+            let piece_size = response.len().div_ceil(pieces);
+            for piece in response.as_bytes().chunks(piece_size) {
+                thread::sleep(pause);
+                if tcp_stream.write_all(piece).is_err() {
+                    return;
+                }
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn status_200_gives_the_body_and_other_statuses_are_errors() {
+        let port = fake_worker_answering("HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\n{\"content\":1}", 1, Duration::ZERO);
+        assert_eq!(send_worker_request(port, "POST", "/completion", Some("{}"), Duration::from_secs(5)), Ok("{\"content\":1}".to_string()));
+
+        let port = fake_worker_answering("HTTP/1.1 500 Internal Server Error\r\nContent-Length: 2\r\n\r\n{}", 1, Duration::ZERO);
+        assert_eq!(
+            send_worker_request(port, "POST", "/completion", Some("{}"), Duration::from_secs(5)),
+            Err("llama-server answered POST /completion with status 500".to_string())
+        );
+
+        let port = fake_worker_answering("HTTP/1.1 200 OK\r\nContent-Le", 1, Duration::ZERO);
+        assert_eq!(
+            send_worker_request(port, "GET", "/health", None, Duration::from_secs(5)),
+            Err("incomplete HTTP response from llama-server".to_string())
+        );
+    }
+
+    #[test]
+    fn trickled_answer_past_the_deadline_is_an_error() {
+        // Each piece comes well inside any per-read timeout; the whole answer does not
+        let port = fake_worker_answering("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}", 10, Duration::from_millis(100));
+        let started = Instant::now();
+        let error = send_worker_request(port, "POST", "/completion", Some("{}"), Duration::from_millis(300)).unwrap_err();
+        assert_eq!(error, "no complete answer to POST /completion within 300 ms");
+        assert!(started.elapsed() < Duration::from_secs(1), "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn worker_that_is_gone_cannot_be_reached() {
+        // A port that was free a moment ago: nothing listens there any more
+        let port = TcpListener::bind(loopback_address(0)).unwrap().local_addr().unwrap().port();
+        let error = send_worker_request(port, "GET", "/health", None, Duration::from_secs(1)).unwrap_err();
+        assert!(error.starts_with(&format!("cannot connect to 127.0.0.1:{}", port)), "{}", error);
+        assert!(!worker_is_healthy(port));
+    }
+}
@@ -0,0 +1,8 @@
+// endpoint_modules/llamacpp_embedding/input_enum.rs
+
+/// Defines the input fields for the llamacpp_embedding endpoint
+#[derive(Debug)]
+pub enum LlamacppEmbeddingInputFields {
+    /// Texts to embed, in order (the JSON "input" field, or a non-JSON body as one text)
+    Texts(Vec<String>),
+}
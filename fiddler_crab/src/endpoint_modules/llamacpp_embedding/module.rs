@@ -0,0 +1,144 @@
+// endpoint_modules/llamacpp_embedding/module.rs
+use crate::config::server_config;
+use crate::endpoint_modules::endpoint_module::EndpointModule;
+use crate::endpoint_modules::{EndpointSetting, SettingValue};
+use crate::json::{parse_json, JsonLimits, JsonValue};
+use crate::subprocess::{run_subprocess, SubprocessSpec};
+use crate::RequestUnit;
+use super::input_enum::LlamacppEmbeddingInputFields;
+use super::output_enum::LlamacppEmbeddingOutputFields;
+use super::parse::parse_llamacpp_embedding_request;
+use super::r#struct::LlamacppEmbeddingModule;
+
+/// Keys read from [endpoint.llamacpp_embedding], besides the subprocess settings
+pub const LLAMACPP_EMBEDDING_ENDPOINT_SETTINGS: &[EndpointSetting] = &[
+    EndpointSetting { key: "binary_path", value: SettingValue::Text },
+    EndpointSetting { key: "model_path", value: SettingValue::Text },
+];
+
+/// llama-embedding binary used when endpoint.llamacpp_embedding.binary_path is not set (looked up on PATH)
+const DEFAULT_LLAMACPP_EMBEDDING_BINARY_PATH: &str = "llama-embedding";
+
+/// Joins the texts into llama-embedding's single -p argument (its default separator,
+/// a newline, would split texts that contain newlines)
+pub const TEXT_SEPARATOR: &str = "<#fiddler_crab_sep#>";
+
+/// Reads the vectors out of llama-embedding's `--embd-output-format json` stdout
+///
+/// The output looks like {"data":[{"index":0,"embedding":[0.1, ...]}, ...]}.
+/// Anything printed before the first '{' is skipped.
+///
+/// # Arguments
+/// * `stdout` - The tool's stdout
+/// * `expected_count` - How many texts were sent
+/// * `max_input_bytes` - JSON size limit (the tool's stdout cap)
+///
+/// # Returns
+/// * `Result<Vec<Vec<f64>>, String>` - One vector per text in input order, all the same length
+fn embeddings_from_stdout(stdout: &str, expected_count: usize, max_input_bytes: usize) -> Result<Vec<Vec<f64>>, String> {
+    let json_start = stdout.find('{').ok_or("no JSON in llama-embedding output")?;
+    let json_limits = JsonLimits {
+        max_input_bytes,
+        ..JsonLimits::default()
+    };
+    let json_output = parse_json(&stdout[json_start..], &json_limits)
+        .map_err(|e| format!("llama-embedding output is not valid JSON: {}", e))?;
+    let Some(JsonValue::Array(data)) = json_output.get("data") else {
+        return Err("llama-embedding output has no \"data\" array".to_string());
+    };
+    if data.len() != expected_count {
+        return Err(format!("llama-embedding returned {} vectors for {} texts", data.len(), expected_count));
+    }
+
+    let mut embeddings: Vec<Option<Vec<f64>>> = vec![None; expected_count];
+    for (position, entry) in data.iter().enumerate() {
+        // Entries carry their index; fall back to position if it is missing
+        let index = entry
+            .get("index")
+            .and_then(JsonValue::as_f64)
+            .map(|index| index as usize)
+            .unwrap_or(position);
+        let Some(JsonValue::Array(values)) = entry.get("embedding") else {
+            return Err(format!("llama-embedding output entry {} has no \"embedding\" array", position));
+        };
+        let vector = values
+            .iter()
+            .map(JsonValue::as_f64)
+            .collect::<Option<Vec<f64>>>()
+            .ok_or_else(|| format!("llama-embedding output entry {} has a non-number value", position))?;
+        match embeddings.get_mut(index) {
+            Some(slot @ None) => *slot = Some(vector),
+            _ => return Err(format!("llama-embedding output has a bad or repeated index {}", index)),
+        }
+    }
+
+    let embeddings: Vec<Vec<f64>> = embeddings.into_iter().flatten().collect();
+    if embeddings.iter().any(|vector| vector.is_empty() || vector.len() != embeddings[0].len()) {
+        return Err("llama-embedding returned empty or differently sized vectors".to_string());
+    }
+    Ok(embeddings)
+}
+
+pub fn llamacpp_embedding_endpoint_function(
+    input: LlamacppEmbeddingInputFields,
+) -> Result<LlamacppEmbeddingOutputFields, String> {
+    // 1. Extract the texts from the parsed data
+    let LlamacppEmbeddingInputFields::Texts(texts) = input;
+
+    // 2. Binary and model come from the config ([endpoint.llamacpp_embedding] binary_path, model_path)
+    let binary_path = server_config()
+        .endpoint_setting("llamacpp_embedding", "binary_path")
+        .unwrap_or(DEFAULT_LLAMACPP_EMBEDDING_BINARY_PATH);
+    let Some(model_path) = server_config().endpoint_setting("llamacpp_embedding", "model_path") else {
+        return Err("embedding model is not configured (set endpoint.llamacpp_embedding.model_path)".to_string());
+    };
+
+    // 3. Execute llama-embedding once for all texts: extra args, output cap, timeout and rlimits from the config
+    let embedding_args = vec![
+        "-m".to_string(),
+        model_path.to_string(),
+        "-p".to_string(),
+        texts.join(TEXT_SEPARATOR),
+        "--embd-separator".to_string(),
+        TEXT_SEPARATOR.to_string(),
+        "--embd-output-format".to_string(),
+        "json".to_string(),
+    ];
+    let subprocess_spec =
+        SubprocessSpec::new(binary_path, embedding_args).apply_endpoint_settings("llamacpp_embedding")?;
+    let output = run_subprocess(&subprocess_spec).map_err(|e| format!("llama-embedding execution error: {}", e))?;
+
+    // 4. One vector per text
+    let embeddings = embeddings_from_stdout(&output.stdout_as_string(), texts.len(), subprocess_spec.max_stdout_bytes)?;
+    Ok(LlamacppEmbeddingOutputFields::Embeddings(embeddings))
+}
+
+impl EndpointModule for LlamacppEmbeddingModule {
+    type Input = LlamacppEmbeddingInputFields;
+    type Output = LlamacppEmbeddingOutputFields;
+
+    fn parse(request_unit: &RequestUnit) -> Result<Self::Input, String> {
+        parse_llamacpp_embedding_request(&request_unit.body, request_unit.request_header("Content-Type"))
+    }
+
+    fn process(input: Self::Input) -> Result<Self::Output, String> {
+        llamacpp_embedding_endpoint_function(input)
+    }
+
+    fn serialize_output(output: &Self::Output) -> Result<JsonValue, String> {
+        match output {
+            LlamacppEmbeddingOutputFields::Embeddings(embeddings) => Ok(JsonValue::object(vec![
+                ("dimensions", JsonValue::Number(embeddings.first().map_or(0, Vec::len) as f64)),
+                (
+                    "embeddings",
+                    JsonValue::Array(
+                        embeddings
+                            .iter()
+                            .map(|vector| JsonValue::Array(vector.iter().map(|value| JsonValue::Number(*value)).collect()))
+                            .collect(),
+                    ),
+                ),
+            ])),
+        }
+    }
+}
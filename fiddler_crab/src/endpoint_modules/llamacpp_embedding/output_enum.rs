@@ -0,0 +1,8 @@
+// endpoint_modules/llamacpp_embedding/output_enum.rs
+
+/// Defines the output fields for the llamacpp_embedding endpoint
+#[derive(Debug)]
+pub enum LlamacppEmbeddingOutputFields {
+    /// One vector per input text, in input order
+    Embeddings(Vec<Vec<f64>>),
+}
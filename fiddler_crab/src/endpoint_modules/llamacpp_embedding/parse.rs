@@ -0,0 +1,78 @@
+// endpoint_modules/llamacpp_embedding/parse.rs
+use super::input_enum::LlamacppEmbeddingInputFields;
+use super::module::TEXT_SEPARATOR;
+use crate::json::{is_json_content_type, parse_json, JsonLimits, JsonValue};
+
+/// Parses the request body into the texts to embed
+///
+/// A JSON body (Content-Type: application/json) must be an object whose
+/// "input" field is a string or a non-empty array of strings; any other
+/// body is one text as-is. Either way no text may contain TEXT_SEPARATOR,
+/// which the module uses to pass several texts to llama-embedding at once.
+pub fn parse_llamacpp_embedding_request(
+    request_body: &str,
+    content_type: Option<&str>,
+) -> Result<LlamacppEmbeddingInputFields, String> {
+    if !is_json_content_type(content_type) {
+        if request_body.trim().is_empty() {
+            return Err("request body is empty: nothing to embed".to_string());
+        }
+        if request_body.contains(TEXT_SEPARATOR) {
+            return Err(format!("request body must not contain {}", TEXT_SEPARATOR));
+        }
+        return Ok(LlamacppEmbeddingInputFields::Texts(vec![request_body.to_string()]));
+    }
+
+    let json_body = parse_json(request_body, &JsonLimits::default())?;
+    let texts = match json_body.get("input") {
+        Some(JsonValue::String(text)) => vec![text.clone()],
+        Some(JsonValue::Array(values)) if !values.is_empty() => values
+            .iter()
+            .map(|value| value.as_str().map(str::to_string))
+            .collect::<Option<Vec<String>>>()
+            .ok_or("\"input\" array entries must be strings")?,
+        _ => return Err("JSON body needs a field \"input\": a string or a non-empty array of strings".to_string()),
+    };
+    if texts.iter().any(|text| text.trim().is_empty()) {
+        return Err("\"input\" texts must not be empty".to_string());
+    }
+    if texts.iter().any(|text| text.contains(TEXT_SEPARATOR)) {
+        return Err(format!("\"input\" texts must not contain {}", TEXT_SEPARATOR));
+    }
+
+    Ok(LlamacppEmbeddingInputFields::Texts(texts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed_texts(request_body: &str, content_type: Option<&str>) -> Result<Vec<String>, String> {
+        parse_llamacpp_embedding_request(request_body, content_type).map(|LlamacppEmbeddingInputFields::Texts(texts)| texts)
+    }
+
+    #[test]
+    fn plain_and_json_bodies_give_texts() {
+        assert_eq!(parsed_texts("one text", Some("text/plain")).unwrap(), vec!["one text"]);
+        assert_eq!(parsed_texts("no content type", None).unwrap(), vec!["no content type"]);
+        assert_eq!(parsed_texts(r#"{"input": "a"}"#, Some("application/json")).unwrap(), vec!["a"]);
+        assert_eq!(parsed_texts(r#"{"input": ["a", "b"]}"#, Some("application/json")).unwrap(), vec!["a", "b"]);
+        assert!(parsed_texts("  ", None).is_err());
+        assert!(parsed_texts(r#"{"input": []}"#, Some("application/json")).is_err());
+    }
+
+    #[test]
+    fn separator_in_a_text_is_rejected_for_plain_and_json_bodies() {
+        let plain_body = format!("first{}second", TEXT_SEPARATOR);
+        assert_eq!(
+            parsed_texts(&plain_body, Some("text/plain")).unwrap_err(),
+            format!("request body must not contain {}", TEXT_SEPARATOR)
+        );
+
+        let json_body = format!(r#"{{"input": ["ok", "first{}second"]}}"#, TEXT_SEPARATOR);
+        assert_eq!(
+            parsed_texts(&json_body, Some("application/json")).unwrap_err(),
+            format!("\"input\" texts must not contain {}", TEXT_SEPARATOR)
+        );
+    }
+}
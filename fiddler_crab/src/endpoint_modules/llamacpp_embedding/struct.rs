@@ -0,0 +1,7 @@
+// endpoint_modules/llamacpp_embedding/struct.rs
+
+/// The llamacpp_embedding endpoint module
+///
+/// Runs llama.cpp's llama-embedding on one or more texts and returns one
+/// float vector per text. Its `EndpointModule` impl is in module.rs.
+pub struct LlamacppEmbeddingModule;
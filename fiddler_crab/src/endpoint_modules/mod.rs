@@ -0,0 +1,354 @@
+/*
+Endpoint module registry (compile-time, plus script endpoints from config)
+
+Each endpoint module lives in its own directory here and is listed once
+in ENDPOINT_MODULE_REGISTRY below. Being listed makes it routable: the
+router matches request paths against this table and
+process_request_with_module calls the handler function found here.
+
+To add an endpoint module:
+1. make endpoint_modules/<name>/ with mod.rs, input_enum.rs, output_enum.rs,
+   struct.rs, parse.rs, module.rs, and implement EndpointModule
+   (see endpoint_module.rs)
+2. add `pub mod <name>;` below
+3. add a RegisteredEndpoint line to ENDPOINT_MODULE_REGISTRY with
+   `handler: run_endpoint_module::<YourModule>` and the [endpoint.<name>]
+   keys the module reads in `settings`
+
+Script endpoints need no Rust: each [endpoint.NAME] section with
+type = "script" (see script/module.rs) is appended at startup by
+install_endpoint_registry. Routing and lookup go through
+endpoint_registry(), which is the compiled table plus those.
+
+Every [endpoint.NAME] setting is checked when the registry is installed:
+NAME must be a registered endpoint, the key must be one that endpoint
+reads, and numbers, ports and choices must parse. A typo stops the server
+at startup (and fails --check-config) instead of being silently ignored.
+*/
+pub mod endpoint_module;
+
+pub mod echo_input_data;
+pub mod llamacpp;
+pub mod llamacpp_embedding;
+pub mod script;
+
+use std::sync::OnceLock;
+
+use crate::config::ServerConfig;
+use crate::probes::PROBE_PATHS;
+use crate::subprocess::SUBPROCESS_ENDPOINT_SETTINGS;
+
+use crate::RequestUnit;
+use endpoint_module::run_endpoint_module;
+use echo_input_data::r#struct::EchoInputDataModule;
+use llamacpp::module::LLAMACPP_ENDPOINT_SETTINGS;
+use llamacpp::r#struct::LlamacppModule;
+use llamacpp_embedding::module::LLAMACPP_EMBEDDING_ENDPOINT_SETTINGS;
+use llamacpp_embedding::r#struct::LlamacppEmbeddingModule;
+use script::module::{check_script_endpoint_config, SCRIPT_ENDPOINT_SETTINGS, SCRIPT_ENDPOINT_TYPE};
+use script::r#struct::ScriptModule;
+
+/// Signature every endpoint module exposes to the handler thread
+///
+/// Takes the queued request, returns it with response fields filled in, or an error message.
+pub type EndpointHandlerFn = fn(RequestUnit) -> Result<RequestUnit, String>;
+
+/// What a per-endpoint setting's value must look like
+#[derive(Clone, Copy)]
+pub enum SettingValue {
+    /// Any text (paths, argument lists, templates)
+    Text,
+    /// A non-negative whole number
+    Number,
+    /// A whole number of at least 1 (timeouts)
+    NonZeroNumber,
+    /// A TCP port, 1-65535
+    Port,
+    /// One of the listed words
+    OneOf(&'static [&'static str]),
+}
+
+/// One key an endpoint reads from its [endpoint.NAME] section
+pub struct EndpointSetting {
+    pub key: &'static str,
+    pub value: SettingValue,
+}
+
+/// Keys every endpoint accepts (read by the server, not the module)
+pub const COMMON_ENDPOINT_SETTINGS: &[EndpointSetting] = &[
+    EndpointSetting { key: "processing_timeout_ms", value: SettingValue::NonZeroNumber },
+];
+
+/// One entry in the registry: a module name, where it is served, and its handler
+#[derive(Clone, Copy)]
+pub struct RegisteredEndpoint {
+    /// Module name, same as its directory in endpoint_modules/
+    pub name: &'static str,
+    /// Request path this module is served at, e.g. "/echo_input_data"
+    pub path: &'static str,
+    /// HTTP methods accepted at `path`
+    pub methods: &'static [&'static str],
+    /// Function the handler thread calls for each request
+    pub handler: EndpointHandlerFn,
+    /// Keys the module reads from [endpoint.NAME], besides COMMON_ENDPOINT_SETTINGS
+    pub settings: &'static [&'static [EndpointSetting]],
+}
+
+/// The endpoint lookup table: name -> handler function pointer
+pub const ENDPOINT_MODULE_REGISTRY: &[RegisteredEndpoint] = &[
+    RegisteredEndpoint {
+        name: "echo_input_data",
+        path: "/echo_input_data",
+        methods: &["GET", "POST"],
+        handler: run_endpoint_module::<EchoInputDataModule>,
+        settings: &[],
+    },
+    RegisteredEndpoint {
+        name: "llamacpp",
+        path: "/llamacpp",
+        methods: &["POST"],
+        handler: run_endpoint_module::<LlamacppModule>,
+        settings: &[LLAMACPP_ENDPOINT_SETTINGS, SUBPROCESS_ENDPOINT_SETTINGS],
+    },
+    RegisteredEndpoint {
+        name: "llamacpp_embedding",
+        path: "/llamacpp_embedding",
+        methods: &["POST"],
+        handler: run_endpoint_module::<LlamacppEmbeddingModule>,
+        settings: &[LLAMACPP_EMBEDDING_ENDPOINT_SETTINGS, SUBPROCESS_ENDPOINT_SETTINGS],
+    },
+];
+
+/// Looks up an endpoint module's handler function by module name
+///
+/// # Arguments
+/// * `endpoint_module_name` - e.g. "echo_input_data"
+///
+/// # Returns
+/// * `Option<EndpointHandlerFn>` - The handler, or None if no module has that name
+pub fn lookup_endpoint_module(endpoint_module_name: &str) -> Option<EndpointHandlerFn> {
+    endpoint_registry()
+        .iter()
+        .find(|registered_endpoint| registered_endpoint.name == endpoint_module_name)
+        .map(|registered_endpoint| registered_endpoint.handler)
+}
+
+/// The compiled table plus script endpoints, set once at startup
+static ENDPOINT_REGISTRY: OnceLock<Vec<RegisteredEndpoint>> = OnceLock::new();
+
+/// Every routable endpoint: compiled modules, then script endpoints from config
+///
+/// Before install_endpoint_registry has run (e.g. in tests) this is just ENDPOINT_MODULE_REGISTRY.
+pub fn endpoint_registry() -> &'static [RegisteredEndpoint] {
+    match ENDPOINT_REGISTRY.get() {
+        Some(endpoint_registry) => endpoint_registry,
+        None => ENDPOINT_MODULE_REGISTRY,
+    }
+}
+
+/// Builds the endpoint registry from the compiled table and the config's script endpoints
+///
+/// Call once at startup, after install_server_config. Names, paths and
+/// methods of script endpoints are leaked into 'static strings: they are
+/// read once and live as long as the server, like the compiled table.
+///
+/// # Arguments
+/// * `config` - The installed server config
+///
+/// # Returns
+/// * `Result<(), String>` - Err for an unknown `type`, a bad script endpoint,
+///   a name or path that is already taken, or a setting check_endpoint_settings rejects
+pub fn install_endpoint_registry(config: &ServerConfig) -> Result<(), String> {
+    let mut endpoint_registry = ENDPOINT_MODULE_REGISTRY.to_vec();
+
+    for endpoint_name in config.endpoint_names() {
+        match config.endpoint_setting(endpoint_name, "type") {
+            None => continue,
+            Some(SCRIPT_ENDPOINT_TYPE) => {}
+            Some(other) => {
+                return Err(format!(
+                    "endpoint.{}.type '{}' is unknown (the only configurable type is \"{}\")",
+                    endpoint_name, other, SCRIPT_ENDPOINT_TYPE
+                ))
+            }
+        }
+        check_script_endpoint_config(endpoint_name)?;
+
+        let path = match config.endpoint_setting(endpoint_name, "path") {
+            Some(path) if path.starts_with('/') => path.to_string(),
+            Some(path) => return Err(format!("endpoint.{}.path '{}' must start with /", endpoint_name, path)),
+            None => format!("/{}", endpoint_name),
+        };
+        let methods: Vec<&'static str> = config
+            .endpoint_setting(endpoint_name, "methods")
+            .unwrap_or("POST")
+            .split(',')
+            .map(str::trim)
+            .filter(|method| !method.is_empty())
+            .map(|method| &*Box::leak(method.to_ascii_uppercase().into_boxed_str()))
+            .collect();
+        if methods.is_empty() {
+            return Err(format!("endpoint.{}.methods is empty", endpoint_name));
+        }
+
+        if PROBE_PATHS.contains(&path.trim_end_matches('/')) {
+            return Err(format!("script endpoint {} cannot use {}: it is a built-in probe", endpoint_name, path));
+        }
+        if let Some(taken) = endpoint_registry.iter().find(|registered_endpoint| {
+            registered_endpoint.name == endpoint_name || registered_endpoint.path.trim_end_matches('/') == path.trim_end_matches('/')
+        }) {
+            return Err(format!(
+                "script endpoint {} ({}) clashes with endpoint {} ({})",
+                endpoint_name, path, taken.name, taken.path
+            ));
+        }
+        endpoint_registry.push(RegisteredEndpoint {
+            name: Box::leak(endpoint_name.to_string().into_boxed_str()),
+            path: Box::leak(path.into_boxed_str()),
+            methods: Box::leak(methods.into_boxed_slice()),
+            handler: run_endpoint_module::<ScriptModule>,
+            settings: &[SCRIPT_ENDPOINT_SETTINGS, SUBPROCESS_ENDPOINT_SETTINGS],
+        });
+    }
+    check_endpoint_settings(config, &endpoint_registry)?;
+
+    ENDPOINT_REGISTRY
+        .set(endpoint_registry)
+        .map_err(|_| "endpoint registry is already installed".to_string())
+}
+
+/// Checks every [endpoint.NAME] setting against the endpoint it configures
+///
+/// # Arguments
+/// * `config` - The loaded server config
+/// * `endpoint_registry` - Compiled modules plus the config's script endpoints
+///
+/// # Returns
+/// * `Result<(), String>` - Err naming the first unknown endpoint, unknown key or bad value
+pub fn check_endpoint_settings(config: &ServerConfig, endpoint_registry: &[RegisteredEndpoint]) -> Result<(), String> {
+    for (endpoint_name, key, value) in &config.endpoint_settings {
+        let Some(registered_endpoint) = endpoint_registry
+            .iter()
+            .find(|registered_endpoint| registered_endpoint.name == endpoint_name)
+        else {
+            let endpoint_names: Vec<&str> = endpoint_registry.iter().map(|registered_endpoint| registered_endpoint.name).collect();
+            return Err(format!(
+                "endpoint.{}.{}: there is no endpoint named {} (endpoints: {}; a new one needs type = \"{}\")",
+                endpoint_name, key, endpoint_name, endpoint_names.join(", "), SCRIPT_ENDPOINT_TYPE
+            ));
+        };
+        let mut endpoint_settings = COMMON_ENDPOINT_SETTINGS
+            .iter()
+            .chain(registered_endpoint.settings.iter().flat_map(|settings| settings.iter()));
+        let Some(endpoint_setting) = endpoint_settings.find(|endpoint_setting| endpoint_setting.key == key) else {
+            let known_keys: Vec<&str> = COMMON_ENDPOINT_SETTINGS
+                .iter()
+                .chain(registered_endpoint.settings.iter().flat_map(|settings| settings.iter()))
+                .map(|endpoint_setting| endpoint_setting.key)
+                .collect();
+            return Err(format!(
+                "endpoint.{}.{} is not a setting of endpoint {} (known: {})",
+                endpoint_name, key, endpoint_name, known_keys.join(", ")
+            ));
+        };
+        check_setting_value(endpoint_setting.value, value)
+            .map_err(|expected| format!("endpoint.{}.{} must be {}, got '{}'", endpoint_name, key, expected, value))?;
+    }
+    Ok(())
+}
+
+/// Checks one value; Err describes what was expected
+fn check_setting_value(setting_value: SettingValue, value: &str) -> Result<(), String> {
+    let is_valid = match setting_value {
+        SettingValue::Text => true,
+        SettingValue::Number => value.parse::<u64>().is_ok(),
+        SettingValue::NonZeroNumber => value.parse::<u64>().is_ok_and(|number| number > 0),
+        SettingValue::Port => value.parse::<u16>().is_ok_and(|port| port > 0),
+        SettingValue::OneOf(choices) => choices.contains(&value),
+    };
+    if is_valid {
+        return Ok(());
+    }
+    Err(match setting_value {
+        SettingValue::Text => unreachable!("any text is valid"),
+        SettingValue::Number => "a non-negative whole number".to_string(),
+        SettingValue::NonZeroNumber => "a whole number of at least 1".to_string(),
+        SettingValue::Port => "a port number (1-65535)".to_string(),
+        SettingValue::OneOf(choices) => {
+            let quoted_choices: Vec<String> = choices.iter().map(|choice| format!("\"{}\"", choice)).collect();
+            format!("one of {}", quoted_choices.join(", "))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with(endpoint_settings: &[(&str, &str, &str)]) -> ServerConfig {
+        let mut config = ServerConfig::default();
+        for (endpoint_name, key, value) in endpoint_settings {
+            config
+                .apply_setting(&format!("endpoint.{}.{}", endpoint_name, key), value)
+                .unwrap();
+        }
+        config
+    }
+
+    #[test]
+    fn known_endpoint_settings_pass() {
+        let config = config_with(&[
+            ("llamacpp", "model_path", "/models/gemma.gguf"),
+            ("llamacpp", "mode", "server"),
+            ("llamacpp", "server_port", "8091"),
+            ("llamacpp", "timeout_ms", "60000"),
+            ("llamacpp_embedding", "max_output_bytes", "0"),
+            ("echo_input_data", "processing_timeout_ms", "500"),
+        ]);
+        assert_eq!(check_endpoint_settings(&config, ENDPOINT_MODULE_REGISTRY), Ok(()));
+    }
+
+    #[test]
+    fn unknown_endpoints_keys_and_bad_values_are_rejected() {
+        for (endpoint_setting, expected_error) in [
+            (("llamcpp", "model_path", "/y"), "endpoint.llamcpp.model_path: there is no endpoint named llamcpp"),
+            (("llamacpp", "model_pth", "/x"), "endpoint.llamacpp.model_pth is not a setting of endpoint llamacpp"),
+            (("echo_input_data", "timeout_ms", "10"), "endpoint.echo_input_data.timeout_ms is not a setting of endpoint echo_input_data"),
+            (("llamacpp", "server_port", "abc"), "endpoint.llamacpp.server_port must be a port number (1-65535), got 'abc'"),
+            (("llamacpp", "server_port", "70000"), "endpoint.llamacpp.server_port must be a port number (1-65535), got '70000'"),
+            (("llamacpp", "timeout_ms", "xyz"), "endpoint.llamacpp.timeout_ms must be a whole number of at least 1, got 'xyz'"),
+            (("llamacpp", "processing_timeout_ms", "0"), "endpoint.llamacpp.processing_timeout_ms must be a whole number of at least 1, got '0'"),
+            (("llamacpp", "mode", "daemon"), "endpoint.llamacpp.mode must be one of \"cli\", \"server\", got 'daemon'"),
+            (("llamacpp_embedding", "memory_limit_bytes", "-1"), "endpoint.llamacpp_embedding.memory_limit_bytes must be a non-negative whole number, got '-1'"),
+        ] {
+            let config = config_with(&[endpoint_setting]);
+            let error = check_endpoint_settings(&config, ENDPOINT_MODULE_REGISTRY).unwrap_err();
+            assert!(error.starts_with(expected_error), "{}", error);
+        }
+    }
+
+    #[test]
+    fn script_endpoints_accept_script_and_subprocess_settings() {
+        let mut endpoint_registry = ENDPOINT_MODULE_REGISTRY.to_vec();
+        endpoint_registry.push(RegisteredEndpoint {
+            name: "summarize",
+            path: "/summarize",
+            methods: &["POST"],
+            handler: run_endpoint_module::<ScriptModule>,
+            settings: &[SCRIPT_ENDPOINT_SETTINGS, SUBPROCESS_ENDPOINT_SETTINGS],
+        });
+        let config = config_with(&[
+            ("summarize", "type", "script"),
+            ("summarize", "script_path", "/srv/summarize.sh"),
+            ("summarize", "input", "temp_file"),
+            ("summarize", "timeout_ms", "100"),
+        ]);
+        assert_eq!(check_endpoint_settings(&config, &endpoint_registry), Ok(()));
+
+        let config = config_with(&[("summarize", "input", "argv")]);
+        assert_eq!(
+            check_endpoint_settings(&config, &endpoint_registry).unwrap_err(),
+            "endpoint.summarize.input must be one of \"stdin\", \"temp_file\", got 'argv'"
+        );
+    }
+}
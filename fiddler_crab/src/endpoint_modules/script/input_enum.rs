@@ -0,0 +1,19 @@
+// endpoint_modules/script/input_enum.rs
+
+/// Defines the input fields for a script endpoint
+#[derive(Debug)]
+pub enum ScriptInputFields {
+    /// One request for the script declared under [endpoint.NAME]
+    Request {
+        /// Which script endpoint (its config section)
+        endpoint_name: String,
+        /// Request id, to name the temp input file uniquely
+        request_id: usize,
+        /// Request method, for the script as REQUEST_METHOD
+        method: String,
+        /// Raw query string (without '?'), for the script as QUERY_STRING
+        query: String,
+        /// Request body, passed to the script as-is
+        body: String,
+    },
+}
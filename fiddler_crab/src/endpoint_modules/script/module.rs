@@ -0,0 +1,263 @@
+// endpoint_modules/script/module.rs
+/*
+Script endpoints: an endpoint declared in config instead of in Rust
+
+    [endpoint.summarize]
+    type = "script"
+    interpreter = "python3"                # optional; without it the script is run directly
+    script_path = "/srv/scripts/summarize.py"
+    content_type = "application/json"      # of the script's stdout (default text/plain)
+    input = "stdin"                        # or "temp_file": the body's path is the last argument
+    temp_dir = "/var/lib/fiddler_crab/tmp" # for temp_file (default: the system temp dir)
+    path = "/summarize"                    # default "/NAME"
+    methods = "POST"                       # comma-separated, default POST
+    timeout_ms = 30000                     # and the other subprocess settings
+
+As with CGI, the script also gets REQUEST_METHOD and QUERY_STRING
+(raw, still URL-encoded) in its environment.
+
+The script runs through the subprocess runner, so its timeout, output cap,
+rlimits and the request watchdog all apply: a broken script is a 500 or
+504 for its request, never a stuck server. A non-zero exit is a 500.
+
+A temp input file is created new (never through an existing file or
+link), readable by the server's user only, under a name that cannot be
+guessed ahead of time, and removed once the script has exited.
+*/
+use std::fs::OpenOptions;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{server_config, ServerConfig};
+use crate::endpoint_modules::endpoint_module::EndpointModule;
+use crate::endpoint_modules::{EndpointSetting, SettingValue};
+use crate::json::JsonValue;
+use crate::subprocess::{run_subprocess, SubprocessSpec};
+use crate::RequestUnit;
+use super::input_enum::ScriptInputFields;
+use super::output_enum::ScriptOutputFields;
+use super::parse::parse_script_request;
+use super::r#struct::ScriptModule;
+
+/// Value of `type` that makes an [endpoint.NAME] section a script endpoint
+pub const SCRIPT_ENDPOINT_TYPE: &str = "script";
+
+/// Keys a script endpoint reads from its [endpoint.NAME] section, besides the subprocess settings
+pub const SCRIPT_ENDPOINT_SETTINGS: &[EndpointSetting] = &[
+    EndpointSetting { key: "type", value: SettingValue::OneOf(&[SCRIPT_ENDPOINT_TYPE]) },
+    EndpointSetting { key: "script_path", value: SettingValue::Text },
+    EndpointSetting { key: "interpreter", value: SettingValue::Text },
+    EndpointSetting { key: "content_type", value: SettingValue::Text },
+    EndpointSetting { key: "input", value: SettingValue::OneOf(&["stdin", "temp_file"]) },
+    EndpointSetting { key: "temp_dir", value: SettingValue::Text },
+    EndpointSetting { key: "path", value: SettingValue::Text },
+    EndpointSetting { key: "methods", value: SettingValue::Text },
+];
+
+/// Content-Type of the response when endpoint.NAME.content_type is not set
+const DEFAULT_SCRIPT_CONTENT_TYPE: &str = "text/plain; charset=utf-8";
+
+/// How the request body reaches the script
+enum ScriptInputMode {
+    /// Written to the script's stdin, which is then closed
+    Stdin,
+    /// Written to a temp file whose path is the script's last argument
+    TempFile,
+}
+
+/// Reads endpoint.NAME.input ("stdin" by default)
+fn script_input_mode(endpoint_name: &str) -> Result<ScriptInputMode, String> {
+    match server_config().endpoint_setting(endpoint_name, "input").unwrap_or("stdin") {
+        "stdin" => Ok(ScriptInputMode::Stdin),
+        "temp_file" => Ok(ScriptInputMode::TempFile),
+        other => Err(format!(
+            "endpoint.{}.input must be \"stdin\" or \"temp_file\", got '{}'",
+            endpoint_name, other
+        )),
+    }
+}
+
+/// Checks a script endpoint's settings at startup, so mistakes stop the server instead of failing requests
+///
+/// # Arguments
+/// * `endpoint_name` - The [endpoint.NAME] section with type = "script"
+///
+/// # Returns
+/// * `Result<(), String>` - Err if script_path is missing or not a file, or input is not a known mode
+pub fn check_script_endpoint_config(endpoint_name: &str) -> Result<(), String> {
+    let Some(script_path) = server_config().endpoint_setting(endpoint_name, "script_path") else {
+        return Err(format!("endpoint.{}.script_path is required for a script endpoint", endpoint_name));
+    };
+    if !std::path::Path::new(script_path).is_file() {
+        return Err(format!("endpoint.{}.script_path '{}' is not a file", endpoint_name, script_path));
+    }
+    script_input_mode(endpoint_name)?;
+    if let Some(temp_dir) = server_config().endpoint_setting(endpoint_name, "temp_dir") {
+        if !Path::new(temp_dir).is_dir() {
+            return Err(format!("endpoint.{}.temp_dir '{}' is not a directory", endpoint_name, temp_dir));
+        }
+    }
+    Ok(())
+}
+
+/// Directory for an endpoint's temp input files: endpoint.NAME.temp_dir, else the system temp dir
+fn script_temp_dir(config: &ServerConfig, endpoint_name: &str) -> PathBuf {
+    match config.endpoint_setting(endpoint_name, "temp_dir") {
+        Some(temp_dir) => PathBuf::from(temp_dir),
+        None => std::env::temp_dir(),
+    }
+}
+
+/// Temp file holding a request body; removed when dropped (also if the request times out)
+struct TempInputFile(PathBuf);
+
+impl TempInputFile {
+    /// Creates the file in `directory`, owner read/write only (0600 on Unix)
+    ///
+    /// create_new refuses an existing path, so a file or symlink planted
+    /// under the name is an error rather than something written through;
+    /// the clock's nanoseconds in the name make it hard to guess.
+    fn create(directory: &Path, endpoint_name: &str, request_id: usize, body: &str) -> Result<Self, String> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.subsec_nanos())
+            .unwrap_or(0);
+        let path = directory.join(format!(
+            "fiddler_crab_{}_{}_{}_{:09}.input",
+            std::process::id(),
+            endpoint_name,
+            request_id,
+            nanos
+        ));
+        Self::create_at(path, body)
+    }
+
+    /// Creates the file at exactly `path`, which must not exist yet
+    fn create_at(path: PathBuf, body: &str) -> Result<Self, String> {
+        let mut open_options = OpenOptions::new();
+        open_options.write(true).create_new(true);
+        #[cfg(unix)]
+        open_options.mode(0o600);
+        let mut file = open_options
+            .open(&path)
+            .map_err(|e| format!("could not create temp input file {}: {}", path.display(), e))?;
+        let temp_input_file = TempInputFile(path);
+        file.write_all(body.as_bytes())
+            .map_err(|e| format!("could not write temp input file: {}", e))?;
+        Ok(temp_input_file)
+    }
+}
+
+impl Drop for TempInputFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+pub fn script_endpoint_function(input: ScriptInputFields) -> Result<ScriptOutputFields, String> {
+    // 1. Extract the request from the parsed data
+    let ScriptInputFields::Request { endpoint_name, request_id, method, query, body } = input;
+
+    // 2. Interpreter and script come from [endpoint.NAME]
+    let config = server_config();
+    let script_path = config
+        .endpoint_setting(&endpoint_name, "script_path")
+        .ok_or_else(|| format!("endpoint.{}.script_path is not set", endpoint_name))?;
+    let (program, mut script_args) = match config.endpoint_setting(&endpoint_name, "interpreter") {
+        Some(interpreter) => (interpreter, vec![script_path.to_string()]),
+        None => (script_path, Vec::new()),
+    };
+
+    // 3. Run it with the body on stdin or in a temp file (kept until the script has exited)
+    let mut _temp_input_file = None;
+    let mut stdin_bytes = None;
+    match script_input_mode(&endpoint_name)? {
+        ScriptInputMode::Stdin => stdin_bytes = Some(body.into_bytes()),
+        ScriptInputMode::TempFile => {
+            let temp_input_file = TempInputFile::create(&script_temp_dir(config, &endpoint_name), &endpoint_name, request_id, &body)?;
+            script_args.push(temp_input_file.0.display().to_string());
+            _temp_input_file = Some(temp_input_file);
+        }
+    }
+    let mut subprocess_spec = SubprocessSpec::new(program, script_args).apply_endpoint_settings(&endpoint_name)?;
+    subprocess_spec.stdin_bytes = stdin_bytes;
+    subprocess_spec.env_vars = vec![
+        ("REQUEST_METHOD".to_string(), method),
+        ("QUERY_STRING".to_string(), query),
+    ];
+    let output = run_subprocess(&subprocess_spec).map_err(|e| format!("script {} failed: {}", script_path, e))?;
+
+    // 4. Stdout is the response, with the declared content type
+    Ok(ScriptOutputFields::Stdout {
+        content_type: config
+            .endpoint_setting(&endpoint_name, "content_type")
+            .unwrap_or(DEFAULT_SCRIPT_CONTENT_TYPE)
+            .to_string(),
+        body: output.stdout_as_string(),
+    })
+}
+
+impl EndpointModule for ScriptModule {
+    type Input = ScriptInputFields;
+    type Output = ScriptOutputFields;
+
+    fn parse(request_unit: &RequestUnit) -> Result<Self::Input, String> {
+        parse_script_request(request_unit)
+    }
+
+    fn process(input: Self::Input) -> Result<Self::Output, String> {
+        script_endpoint_function(input)
+    }
+
+    fn serialize_output(output: &Self::Output) -> Result<JsonValue, String> {
+        let ScriptOutputFields::Stdout { content_type, body } = output;
+        Ok(JsonValue::object(vec![
+            ("content_type", JsonValue::String(content_type.clone())),
+            ("stdout", JsonValue::String(body.clone())),
+        ]))
+    }
+
+    /// The script's stdout is the whole response body: no JSON wrapping, and
+    /// the Content-Type is the one declared in config
+    fn response_body(_request_unit: &RequestUnit, output: Self::Output) -> Result<(String, String), String> {
+        let ScriptOutputFields::Stdout { content_type, body } = output;
+        Ok((content_type, body))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn temp_input_file_is_private_new_and_removed_on_drop() {
+        let directory = std::env::temp_dir().join(format!("fiddler_crab_temp_input_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let temp_input_file = TempInputFile::create(&directory, "wordcount", 7, "some words").unwrap();
+        let path = temp_input_file.0.clone();
+        assert!(path.starts_with(&directory), "{}", path.display());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "some words");
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        // An existing file under the name is refused, not written through
+        assert!(TempInputFile::create_at(path.clone(), "other").is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "some words");
+
+        drop(temp_input_file);
+        assert!(!path.exists());
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn temp_dir_setting_picks_the_directory() {
+        let mut config = ServerConfig::default();
+        assert_eq!(script_temp_dir(&config, "wordcount"), std::env::temp_dir());
+        config.apply_setting("endpoint.wordcount.temp_dir", "/srv/fiddler_crab/tmp").unwrap();
+        assert_eq!(script_temp_dir(&config, "wordcount"), PathBuf::from("/srv/fiddler_crab/tmp"));
+    }
+}
@@ -0,0 +1,11 @@
+// endpoint_modules/script/output_enum.rs
+
+/// Defines the output fields for a script endpoint
+#[derive(Debug)]
+pub enum ScriptOutputFields {
+    /// The script's stdout, sent as the response body with the configured Content-Type
+    Stdout {
+        content_type: String,
+        body: String,
+    },
+}
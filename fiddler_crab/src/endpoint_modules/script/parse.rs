@@ -0,0 +1,18 @@
+// endpoint_modules/script/parse.rs
+use super::input_enum::ScriptInputFields;
+use crate::RequestUnit;
+
+/// Reads the request into script input: the body is not interpreted, the script gets it as-is
+pub fn parse_script_request(request_unit: &RequestUnit) -> Result<ScriptInputFields, String> {
+    let endpoint_name = request_unit
+        .endpoint_module_name
+        .clone()
+        .ok_or("request has no endpoint name")?;
+    Ok(ScriptInputFields::Request {
+        endpoint_name,
+        request_id: request_unit.id,
+        method: request_unit.method.clone(),
+        query: request_unit.query.clone().unwrap_or_default(),
+        body: request_unit.body.clone(),
+    })
+}
@@ -0,0 +1,9 @@
+// endpoint_modules/script/struct.rs
+
+/// The script endpoint module type
+///
+/// Not one endpoint but a kind of endpoint: every [endpoint.NAME] section
+/// with `type = "script"` is served by this module, which runs the
+/// configured interpreter and script on the request body and answers with
+/// the script's stdout. Its EndpointModule impl is in module.rs.
+pub struct ScriptModule;
@@ -0,0 +1,575 @@
+/*
+HTTP/1.1 request reading and parsing (in-house, no dependencies)
+
+Reads one request off a stream:
+1. request line: METHOD SP request-target SP HTTP-version
+2. header lines up to the blank line (\r\n\r\n)
+3. a body of exactly Content-Length bytes (no body if absent)
+
+Both the header block and the body are capped so a single client
+cannot make the server buffer an unbounded amount of data, and the whole
+read (head and body) has one deadline, so a client that trickles bytes
+just inside the socket read timeout cannot hold the read for long either.
+
+The query string is kept raw on HttpRequest; parse_query_string turns it
+into URL-decoded (name, value) pairs for endpoint modules.
+*/
+use std::fmt;
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+/// Default cap on the request line plus all header lines, in bytes
+pub const DEFAULT_MAX_HEADER_BYTES: usize = 8 * 1024;
+
+/// Default cap on the request body, in bytes
+pub const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Default deadline for reading a whole request (head and body), in ms
+pub const DEFAULT_MAX_READ_MS: u64 = 10_000;
+
+/// Size of each read from the stream while looking for the end of headers
+const READ_CHUNK_SIZE: usize = 1024;
+
+/// Size and time caps applied while reading a request off the stream
+#[derive(Debug, Clone, Copy)]
+pub struct HttpRequestLimits {
+    /// Max bytes for the request line plus headers (including the blank line)
+    pub max_header_bytes: usize,
+    /// Max bytes for the body, as declared by Content-Length
+    pub max_body_bytes: usize,
+    /// Max time for the whole read, head and body together
+    pub max_read_time: Duration,
+}
+
+impl Default for HttpRequestLimits {
+    fn default() -> Self {
+        HttpRequestLimits {
+            max_header_bytes: DEFAULT_MAX_HEADER_BYTES,
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            max_read_time: Duration::from_millis(DEFAULT_MAX_READ_MS),
+        }
+    }
+}
+
+/// A parsed HTTP/1.x request
+///
+/// This is the typed form of what came in on the socket; the stream-loop
+/// copies the parts it needs into a `RequestUnit` for the queue.
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    /// e.g. "POST", "GET" (as sent, case-sensitive per the spec)
+    pub method: String,
+    /// Path part of the request-target, e.g. "/echo_input_data"
+    pub path: String,
+    /// Raw query string after '?', without the '?', if any
+    pub query: Option<String>,
+    /// e.g. "HTTP/1.1"
+    pub version: String,
+    /// Header (name, value) pairs in the order received, values trimmed
+    pub headers: Vec<(String, String)>,
+    /// Exactly Content-Length bytes (empty if no Content-Length)
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// Returns the body as a String, replacing invalid UTF-8 sequences
+    pub fn body_as_string(&self) -> String {
+        String::from_utf8_lossy(&self.body).to_string()
+    }
+
+    /// Returns the value of the first header matching `name` (case-insensitive)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Reasons reading or parsing a request can fail
+#[derive(Debug)]
+pub enum HttpRequestError {
+    /// The client closed the connection before sending a full request head
+    ConnectionClosed,
+    /// The client closed the connection before sending the full body
+    IncompleteBody,
+    /// Reading from the stream failed (includes read timeouts)
+    Io(std::io::Error),
+    /// The request line was not METHOD SP target SP version
+    MalformedRequestLine,
+    /// A header line had no ':' or an invalid name
+    MalformedHeader,
+    /// Request line plus headers exceeded `max_header_bytes`
+    HeadersTooLarge,
+    /// Content-Length exceeded `max_body_bytes`
+    BodyTooLarge,
+    /// The request was not complete within `max_read_time`
+    ReadTimedOut,
+    /// Content-Length was not a number, or appeared with conflicting values
+    InvalidContentLength,
+    /// Transfer-Encoding (e.g. chunked) bodies are not supported
+    UnsupportedTransferEncoding,
+}
+
+impl HttpRequestError {
+    /// HTTP status code to answer with, if the connection is still usable
+    ///
+    /// Returns None when there is no one to answer (client went away, io error).
+    pub fn response_status(&self) -> Option<u16> {
+        match self {
+            HttpRequestError::ConnectionClosed
+            | HttpRequestError::IncompleteBody
+            | HttpRequestError::Io(_) => None,
+            HttpRequestError::MalformedRequestLine
+            | HttpRequestError::MalformedHeader
+            | HttpRequestError::InvalidContentLength => Some(400),
+            HttpRequestError::ReadTimedOut => Some(408),
+            HttpRequestError::BodyTooLarge => Some(413),
+            HttpRequestError::HeadersTooLarge => Some(431),
+            HttpRequestError::UnsupportedTransferEncoding => Some(501),
+        }
+    }
+}
+
+impl fmt::Display for HttpRequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpRequestError::ConnectionClosed => write!(f, "connection closed before end of headers"),
+            HttpRequestError::IncompleteBody => write!(f, "connection closed before end of body"),
+            HttpRequestError::Io(e) => write!(f, "read error: {}", e),
+            HttpRequestError::MalformedRequestLine => write!(f, "malformed request line"),
+            HttpRequestError::MalformedHeader => write!(f, "malformed header line"),
+            HttpRequestError::HeadersTooLarge => write!(f, "request headers too large"),
+            HttpRequestError::BodyTooLarge => write!(f, "request body too large"),
+            HttpRequestError::ReadTimedOut => write!(f, "request not received in time"),
+            HttpRequestError::InvalidContentLength => write!(f, "invalid Content-Length"),
+            HttpRequestError::UnsupportedTransferEncoding => write!(f, "Transfer-Encoding is not supported"),
+        }
+    }
+}
+
+/// Reads and parses one HTTP/1.x request from `stream`
+///
+/// # Arguments
+/// * `stream` - Anything readable, normally the accepted `TcpStream`
+///   (set a read timeout on it first, no longer than `max_read_time`, so a
+///   silent client cannot block a single read past the deadline)
+/// * `limits` - Header and body size caps, and the read deadline
+///
+/// # Returns
+/// * `Result<HttpRequest, HttpRequestError>` - The parsed request or why it failed
+///
+/// # Steps
+/// 1. Read until "\r\n\r\n", failing if the head exceeds `max_header_bytes`
+/// 2. Parse the request line and headers
+/// 3. Read exactly Content-Length body bytes (any bytes already buffered count)
+///
+/// Every read in steps 1 and 3 checks the same deadline, `max_read_time`
+/// after the call started; past it the request fails with 408.
+pub fn read_http_request<R: Read>(
+    stream: &mut R,
+    limits: &HttpRequestLimits,
+) -> Result<HttpRequest, HttpRequestError> {
+    let read_deadline = Instant::now() + limits.max_read_time;
+
+    // 1. Read until the end of the header block
+    let mut buffer: Vec<u8> = Vec::with_capacity(READ_CHUNK_SIZE);
+    let mut chunk = [0u8; READ_CHUNK_SIZE];
+    let head_end = loop {
+        if let Some(index) = find_header_end(&buffer) {
+            break index;
+        }
+        if buffer.len() >= limits.max_header_bytes {
+            return Err(HttpRequestError::HeadersTooLarge);
+        }
+        let bytes_read = read_before_deadline(stream, &mut chunk, read_deadline)?;
+        if bytes_read == 0 {
+            return Err(HttpRequestError::ConnectionClosed);
+        }
+        buffer.extend_from_slice(&chunk[..bytes_read]);
+    };
+    // "\r\n\r\n" itself counts toward the header budget
+    if head_end + 4 > limits.max_header_bytes {
+        return Err(HttpRequestError::HeadersTooLarge);
+    }
+
+    // 2. Parse request line and headers
+    let head = std::str::from_utf8(&buffer[..head_end])
+        .map_err(|_| HttpRequestError::MalformedHeader)?;
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().ok_or(HttpRequestError::MalformedRequestLine)?;
+    let (method, path, query, version) = parse_request_line(request_line)?;
+
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in lines {
+        headers.push(parse_header_line(line)?);
+    }
+
+    if headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("Transfer-Encoding")) {
+        return Err(HttpRequestError::UnsupportedTransferEncoding);
+    }
+    let content_length = content_length_from_headers(&headers)?;
+    if content_length > limits.max_body_bytes {
+        return Err(HttpRequestError::BodyTooLarge);
+    }
+
+    // 3. Body: bytes already read past the head, then the rest from the stream
+    let mut body: Vec<u8> = buffer[head_end + 4..].to_vec();
+    body.truncate(content_length);
+    while body.len() < content_length {
+        let wanted = (content_length - body.len()).min(READ_CHUNK_SIZE);
+        let bytes_read = read_before_deadline(stream, &mut chunk[..wanted], read_deadline)?;
+        if bytes_read == 0 {
+            return Err(HttpRequestError::IncompleteBody);
+        }
+        body.extend_from_slice(&chunk[..bytes_read]);
+    }
+
+    Ok(HttpRequest {
+        method,
+        path,
+        query,
+        version,
+        headers,
+        body,
+    })
+}
+
+/// One read from the stream, unless the read deadline has passed
+///
+/// A socket read timeout that fires past the deadline is a 408 too; one
+/// before it stays an Io error (the client went quiet, no one to answer).
+fn read_before_deadline<R: Read>(stream: &mut R, chunk: &mut [u8], read_deadline: Instant) -> Result<usize, HttpRequestError> {
+    if Instant::now() >= read_deadline {
+        return Err(HttpRequestError::ReadTimedOut);
+    }
+    match stream.read(chunk) {
+        Ok(bytes_read) => Ok(bytes_read),
+        Err(e)
+            if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut)
+                && Instant::now() >= read_deadline =>
+        {
+            Err(HttpRequestError::ReadTimedOut)
+        }
+        Err(e) => Err(HttpRequestError::Io(e)),
+    }
+}
+
+/// Returns the index of the "\r\n\r\n" that ends the header block, if present
+fn find_header_end(buffer: &[u8]) -> Option<usize> {
+    buffer.windows(4).position(|window| window == b"\r\n\r\n")
+}
+
+/// Splits "METHOD /path?query HTTP/1.1" into its parts
+fn parse_request_line(
+    request_line: &str,
+) -> Result<(String, String, Option<String>, String), HttpRequestError> {
+    let mut parts = request_line.split(' ');
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version), None) => (method, target, version),
+        _ => return Err(HttpRequestError::MalformedRequestLine),
+    };
+
+    if method.is_empty() || !method.bytes().all(is_token_byte) {
+        return Err(HttpRequestError::MalformedRequestLine);
+    }
+    if !target.starts_with('/') {
+        return Err(HttpRequestError::MalformedRequestLine);
+    }
+    if !version.starts_with("HTTP/1.") {
+        return Err(HttpRequestError::MalformedRequestLine);
+    }
+
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    };
+
+    Ok((method.to_string(), path, query, version.to_string()))
+}
+
+/// Splits "Name: value" into (name, trimmed value)
+fn parse_header_line(line: &str) -> Result<(String, String), HttpRequestError> {
+    let (name, value) = line.split_once(':').ok_or(HttpRequestError::MalformedHeader)?;
+    if name.is_empty() || !name.bytes().all(is_token_byte) {
+        return Err(HttpRequestError::MalformedHeader);
+    }
+    Ok((name.to_string(), value.trim().to_string()))
+}
+
+/// Reads Content-Length, rejecting non-numeric or conflicting values
+///
+/// No Content-Length means no body (0).
+fn content_length_from_headers(headers: &[(String, String)]) -> Result<usize, HttpRequestError> {
+    let mut content_length: Option<usize> = None;
+    for (name, value) in headers {
+        if !name.eq_ignore_ascii_case("Content-Length") {
+            continue;
+        }
+        if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(HttpRequestError::InvalidContentLength);
+        }
+        let parsed: usize = value.parse().map_err(|_| HttpRequestError::InvalidContentLength)?;
+        match content_length {
+            Some(existing) if existing != parsed => return Err(HttpRequestError::InvalidContentLength),
+            _ => content_length = Some(parsed),
+        }
+    }
+    Ok(content_length.unwrap_or(0))
+}
+
+/// True for bytes allowed in an HTTP token (method names, header names)
+fn is_token_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+/// Decodes %XX escapes (and '+' as space, as in HTML form queries)
+///
+/// Lenient: a '%' not followed by two hex digits is kept as written, and
+/// bytes that do not form valid UTF-8 are replaced, so decoding never fails.
+///
+/// # Arguments
+/// * `encoded` - e.g. "hello%20world+again"
+///
+/// # Returns
+/// * `String` - e.g. "hello world again"
+pub fn url_decode(encoded: &str) -> String {
+    let encoded_bytes = encoded.as_bytes();
+    let mut decoded_bytes: Vec<u8> = Vec::with_capacity(encoded_bytes.len());
+    let mut index = 0;
+    while index < encoded_bytes.len() {
+        match encoded_bytes[index] {
+            b'+' => decoded_bytes.push(b' '),
+            b'%' => {
+                let high = encoded_bytes.get(index + 1).copied().and_then(hex_value);
+                let low = encoded_bytes.get(index + 2).copied().and_then(hex_value);
+                if let (Some(high), Some(low)) = (high, low) {
+                    decoded_bytes.push(high * 16 + low);
+                    index += 3;
+                    continue;
+                }
+                decoded_bytes.push(b'%');
+            }
+            byte => decoded_bytes.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded_bytes).into_owned()
+}
+
+/// Value of one hex digit
+fn hex_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Splits a raw query string into URL-decoded (name, value) pairs, in order
+///
+/// "a=1&b=two%20words&flag" -> [("a","1"), ("b","two words"), ("flag","")].
+/// Empty pieces ("a=1&&b=2") are skipped; repeated names are all kept.
+pub fn parse_query_string(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((name, value)) => (url_decode(name), url_decode(value)),
+            None => (url_decode(pair), String::new()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// In-memory stream that hands out at most `read_size` bytes per read, like a slow socket
+    struct ChunkedReader {
+        data: Vec<u8>,
+        position: usize,
+        read_size: usize,
+    }
+
+    impl ChunkedReader {
+        fn new(data: &[u8], read_size: usize) -> Self {
+            ChunkedReader { data: data.to_vec(), position: 0, read_size }
+        }
+    }
+
+    impl Read for ChunkedReader {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            let remaining = &self.data[self.position..];
+            let bytes_read = remaining.len().min(buffer.len()).min(self.read_size);
+            buffer[..bytes_read].copy_from_slice(&remaining[..bytes_read]);
+            self.position += bytes_read;
+            Ok(bytes_read)
+        }
+    }
+
+    /// Stream that hands out one byte per read, pausing before each, like a client trickling bytes
+    struct TricklingReader {
+        data: Vec<u8>,
+        position: usize,
+        pause: Duration,
+    }
+
+    impl Read for TricklingReader {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            std::thread::sleep(self.pause);
+            if self.position >= self.data.len() || buffer.is_empty() {
+                return Ok(0);
+            }
+            buffer[0] = self.data[self.position];
+            self.position += 1;
+            Ok(1)
+        }
+    }
+
+    fn read_request(raw_request: &[u8], limits: &HttpRequestLimits) -> Result<HttpRequest, HttpRequestError> {
+        read_http_request(&mut ChunkedReader::new(raw_request, 100), limits)
+    }
+
+    #[test]
+    fn reads_a_large_body_over_several_reads() {
+        let body = "x".repeat(3000);
+        let raw_request = format!(
+            "POST /echo_input_data?a=1 HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let http_request = read_request(raw_request.as_bytes(), &HttpRequestLimits::default()).unwrap();
+        assert_eq!(http_request.method, "POST");
+        assert_eq!(http_request.path, "/echo_input_data");
+        assert_eq!(http_request.query.as_deref(), Some("a=1"));
+        assert_eq!(http_request.version, "HTTP/1.1");
+        assert_eq!(http_request.headers[0], ("Host".to_string(), "localhost".to_string()));
+        assert_eq!(http_request.body_as_string(), body);
+    }
+
+    #[test]
+    fn header_and_body_caps_give_431_and_413() {
+        let limits = HttpRequestLimits { max_header_bytes: 64, max_body_bytes: 10, ..HttpRequestLimits::default() };
+
+        let long_header = format!("GET / HTTP/1.1\r\nX-Padding: {}\r\n\r\n", "p".repeat(100));
+        let error = read_request(long_header.as_bytes(), &limits).unwrap_err();
+        assert!(matches!(error, HttpRequestError::HeadersTooLarge));
+        assert_eq!(error.response_status(), Some(431));
+
+        let error = read_request(b"POST / HTTP/1.1\r\nContent-Length: 11\r\n\r\nhello world", &limits).unwrap_err();
+        assert!(matches!(error, HttpRequestError::BodyTooLarge));
+        assert_eq!(error.response_status(), Some(413));
+    }
+
+    #[test]
+    fn trickled_head_or_body_past_the_read_deadline_gives_408() {
+        let limits = HttpRequestLimits { max_read_time: Duration::from_millis(100), ..HttpRequestLimits::default() };
+        let trickle = |raw_request: &[u8]| TricklingReader { data: raw_request.to_vec(), position: 0, pause: Duration::from_millis(10) };
+
+        // Every byte arrives well inside any per-read timeout; the whole head does not
+        let started = Instant::now();
+        let error = read_http_request(&mut trickle(b"GET /a-long-path-sent-one-byte-at-a-time HTTP/1.1\r\n\r\n"), &limits).unwrap_err();
+        assert!(matches!(error, HttpRequestError::ReadTimedOut), "{:?}", error);
+        assert_eq!(error.response_status(), Some(408));
+        assert!(started.elapsed() < Duration::from_millis(500), "{:?}", started.elapsed());
+
+        // The same deadline covers the body: a quick head does not reset it
+        let head = b"POST / HTTP/1.1\r\nContent-Length: 20\r\n\r\n";
+        let mut reader = ChunkedReader::new(head, head.len()).chain(trickle(&[b'x'; 20]));
+        let error = read_http_request(&mut reader, &limits).unwrap_err();
+        assert!(matches!(error, HttpRequestError::ReadTimedOut), "{:?}", error);
+
+        // Within the deadline the trickle is fine
+        let limits = HttpRequestLimits { max_read_time: Duration::from_secs(5), ..limits };
+        let http_request = read_http_request(&mut trickle(b"GET /ok HTTP/1.1\r\n\r\n"), &limits).unwrap();
+        assert_eq!(http_request.path, "/ok");
+    }
+
+    #[test]
+    fn content_length_must_be_a_single_number() {
+        let limits = HttpRequestLimits::default();
+        for raw_request in [
+            &b"POST / HTTP/1.1\r\nContent-Length: 3\r\nContent-Length: 4\r\n\r\nabcd"[..],
+            &b"POST / HTTP/1.1\r\nContent-Length: abc\r\n\r\n"[..],
+            &b"POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n"[..],
+        ] {
+            let error = read_request(raw_request, &limits).unwrap_err();
+            assert!(matches!(error, HttpRequestError::InvalidContentLength), "{:?}", error);
+            assert_eq!(error.response_status(), Some(400));
+        }
+
+        // The same value twice is accepted
+        let http_request =
+            read_request(b"POST / HTTP/1.1\r\nContent-Length: 2\r\ncontent-length: 2\r\n\r\nok", &limits).unwrap();
+        assert_eq!(http_request.body, b"ok");
+    }
+
+    #[test]
+    fn transfer_encoding_gives_501() {
+        let error = read_request(
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+            &HttpRequestLimits::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(error, HttpRequestError::UnsupportedTransferEncoding));
+        assert_eq!(error.response_status(), Some(501));
+    }
+
+    #[test]
+    fn truncated_requests_have_no_one_to_answer() {
+        let limits = HttpRequestLimits::default();
+        let error = read_request(b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort", &limits).unwrap_err();
+        assert!(matches!(error, HttpRequestError::IncompleteBody));
+        assert_eq!(error.response_status(), None);
+
+        let error = read_request(b"POST / HTTP/1.1\r\nHost: loc", &limits).unwrap_err();
+        assert!(matches!(error, HttpRequestError::ConnectionClosed));
+    }
+
+    #[test]
+    fn malformed_request_lines_give_400() {
+        for request_line in [
+            "GET",
+            "GET /",
+            "GET  / HTTP/1.1",
+            "GET / HTTP/1.1 extra",
+            "GET no-slash HTTP/1.1",
+            "GET / HTTP/2.0",
+            "G(T / HTTP/1.1",
+        ] {
+            let raw_request = format!("{}\r\n\r\n", request_line);
+            let error = read_request(raw_request.as_bytes(), &HttpRequestLimits::default()).unwrap_err();
+            assert!(matches!(error, HttpRequestError::MalformedRequestLine), "{}: {:?}", request_line, error);
+            assert_eq!(error.response_status(), Some(400));
+        }
+    }
+
+    #[test]
+    fn url_decode_handles_escapes_plus_and_bad_sequences() {
+        assert_eq!(url_decode("hello%20world+again"), "hello world again");
+        assert_eq!(url_decode("%2b%2B%3d%26"), "++=&");
+        assert_eq!(url_decode("w%C3%B6rld"), "wörld");
+        // '%' without two hex digits is kept as written
+        assert_eq!(url_decode("100%"), "100%");
+        assert_eq!(url_decode("%zz%4"), "%zz%4");
+        assert_eq!(url_decode("%%41"), "%A");
+        // Bytes that are not UTF-8 are replaced rather than failing
+        assert_eq!(url_decode("%FF"), "\u{FFFD}");
+    }
+
+    #[test]
+    fn query_string_keeps_order_repeats_and_empty_values() {
+        let pairs = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+        };
+        assert_eq!(
+            parse_query_string("a=1&b=two%20words&flag&a=2"),
+            pairs(&[("a", "1"), ("b", "two words"), ("flag", ""), ("a", "2")])
+        );
+        assert_eq!(parse_query_string("a=&&=x&b=c=d"), pairs(&[("a", ""), ("", "x"), ("b", "c=d")]));
+        assert_eq!(parse_query_string("q=a%26b%3Dc"), pairs(&[("q", "a&b=c")]));
+        assert!(parse_query_string("").is_empty());
+    }
+}
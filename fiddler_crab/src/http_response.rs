@@ -0,0 +1,198 @@
+/*
+HTTP/1.1 response writing
+
+One place that turns (status, headers, body) into response bytes:
+- status line with the proper reason phrase
+- every header the endpoint module set
+- Content-Length computed from the body
+- Connection: close (each stream carries exactly one request/response)
+
+4xx/5xx answers produced by the server itself all use one JSON body shape:
+{"error":{"status":404,"reason":"Not Found","message":"..."}}
+*/
+use std::io::Write;
+
+use crate::json::JsonValue;
+
+/// Headers the builder always sets itself; module-provided copies are ignored
+const SERVER_MANAGED_HEADERS: &[&str] = &["Content-Length", "Connection", "Transfer-Encoding"];
+
+/// Returns the standard reason phrase for a status code
+///
+/// Unknown codes get a generic phrase for their class.
+pub fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        100 => "Continue",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        411 => "Length Required",
+        413 => "Content Too Large",
+        414 => "URI Too Long",
+        415 => "Unsupported Media Type",
+        422 => "Unprocessable Content",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        505 => "HTTP Version Not Supported",
+        _ => match status / 100 {
+            1 => "Informational",
+            2 => "Success",
+            3 => "Redirection",
+            4 => "Client Error",
+            _ => "Server Error",
+        },
+    }
+}
+
+/// Builds the full response text
+///
+/// # Arguments
+/// * `status` - HTTP status code
+/// * `headers` - Headers set by the module; Content-Type defaults to
+///   "text/plain; charset=utf-8" if absent. Content-Length, Connection and
+///   Transfer-Encoding are always set here and module copies are skipped.
+/// * `body` - Response body
+///
+/// # Returns
+/// * `String` - Status line, headers, blank line and body
+pub fn build_http_response(status: u16, headers: &[(String, String)], body: &str) -> String {
+    let mut response = format!("HTTP/1.1 {} {}\r\n", status, reason_phrase(status));
+
+    if !headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("Content-Type")) {
+        response.push_str("Content-Type: text/plain; charset=utf-8\r\n");
+    }
+    for (name, value) in headers {
+        if SERVER_MANAGED_HEADERS.iter().any(|managed| name.eq_ignore_ascii_case(managed)) {
+            continue;
+        }
+        response.push_str(name);
+        response.push_str(": ");
+        response.push_str(value);
+        response.push_str("\r\n");
+    }
+    response.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    // The stream-loop reads one request per connection, so keep-alive is never offered
+    response.push_str("Connection: close\r\n\r\n");
+    response.push_str(body);
+    response
+}
+
+/// Builds a 4xx/5xx response with the server's standard JSON error body
+///
+/// # Arguments
+/// * `status` - HTTP status code (4xx or 5xx)
+/// * `message` - Human-readable detail
+/// * `extra_headers` - Any additional headers (e.g. Allow for 405)
+///
+/// # Returns
+/// * `String` - The full response text
+pub fn build_error_response(status: u16, message: &str, extra_headers: &[(String, String)]) -> String {
+    let body = build_error_body(status, message);
+    let mut headers = vec![("Content-Type".to_string(), "application/json".to_string())];
+    headers.extend_from_slice(extra_headers);
+    build_http_response(status, &headers, &body)
+}
+
+/// Builds the standard JSON error body: {"error":{"status":..,"reason":..,"message":..}}
+///
+/// Also used by endpoint modules that answer with a 4xx themselves,
+/// so every error the server sends has the same shape.
+pub fn build_error_body(status: u16, message: &str) -> String {
+    JsonValue::object(vec![(
+        "error",
+        JsonValue::object(vec![
+            ("status", JsonValue::Number(status as f64)),
+            ("reason", JsonValue::String(reason_phrase(status).to_string())),
+            ("message", JsonValue::String(message.to_string())),
+        ]),
+    )])
+    .to_json_string()
+}
+
+/// Writes response text to a stream and flushes
+///
+/// # Returns
+/// * `std::io::Result<usize>` - Bytes written, or the write error
+pub fn write_http_response<W: Write>(stream: &mut W, response: &str) -> std::io::Result<usize> {
+    stream.write_all(response.as_bytes())?;
+    stream.flush()?;
+    Ok(response.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn response_has_status_line_length_and_connection_close() {
+        let module_headers = vec![
+            ("Content-Type".to_string(), "application/json".to_string()),
+            ("X-Request-Id".to_string(), "7".to_string()),
+            ("content-length".to_string(), "999".to_string()),
+            ("Connection".to_string(), "keep-alive".to_string()),
+        ];
+        let response = build_http_response(201, &module_headers, "{\"é\":1}");
+        assert_eq!(
+            response,
+            "HTTP/1.1 201 Created\r\n\
+             Content-Type: application/json\r\n\
+             X-Request-Id: 7\r\n\
+             Content-Length: 8\r\n\
+             Connection: close\r\n\
+             \r\n\
+             {\"é\":1}"
+        );
+
+        let plain_response = build_http_response(200, &[], "");
+        assert!(plain_response.starts_with("HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\n"));
+        assert!(plain_response.ends_with("Content-Length: 0\r\nConnection: close\r\n\r\n"));
+    }
+
+    #[test]
+    fn reason_phrases_cover_known_codes_and_classes() {
+        for (status, expected_reason) in [
+            (200, "OK"),
+            (404, "Not Found"),
+            (405, "Method Not Allowed"),
+            (413, "Content Too Large"),
+            (431, "Request Header Fields Too Large"),
+            (500, "Internal Server Error"),
+            (501, "Not Implemented"),
+            (503, "Service Unavailable"),
+            (504, "Gateway Timeout"),
+            (299, "Success"),
+            (418, "Client Error"),
+            (599, "Server Error"),
+        ] {
+            assert_eq!(reason_phrase(status), expected_reason, "{}", status);
+        }
+    }
+
+    #[test]
+    fn error_body_escapes_its_message() {
+        assert_eq!(
+            build_error_body(400, "bad \"input\"\nline \\ two"),
+            "{\"error\":{\"status\":400,\"reason\":\"Bad Request\",\"message\":\"bad \\\"input\\\"\\nline \\\\ two\"}}"
+        );
+
+        let allow_header = ("Allow".to_string(), "GET, POST".to_string());
+        let response = build_error_response(405, "method not allowed", &[allow_header]);
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\nContent-Type: application/json\r\nAllow: GET, POST\r\n"));
+    }
+}
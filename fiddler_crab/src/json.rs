@@ -0,0 +1,504 @@
+/*
+Minimal in-house JSON (no serde)
+
+- JsonValue: the value type
+- parse_json: strict RFC 8259 parser with size and nesting-depth limits
+- JsonValue::to_json_string: serializer with correct string escaping
+
+Numbers are held as f64. Object member order is kept as written.
+*/
+use std::fmt::Write as _;
+
+/// Default cap on JSON input size, in bytes
+pub const DEFAULT_JSON_MAX_INPUT_BYTES: usize = 1024 * 1024;
+
+/// Default cap on nesting of arrays/objects
+pub const DEFAULT_JSON_MAX_DEPTH: usize = 32;
+
+/// Limits applied by parse_json
+#[derive(Debug, Clone, Copy)]
+pub struct JsonLimits {
+    /// Max input length in bytes
+    pub max_input_bytes: usize,
+    /// Max nesting depth of arrays and objects (a scalar is depth 0)
+    pub max_depth: usize,
+}
+
+impl Default for JsonLimits {
+    fn default() -> Self {
+        JsonLimits {
+            max_input_bytes: DEFAULT_JSON_MAX_INPUT_BYTES,
+            max_depth: DEFAULT_JSON_MAX_DEPTH,
+        }
+    }
+}
+
+/// A JSON value
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    /// (key, value) members in the order they were written
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Builds an Object from (key, value) pairs
+    pub fn object(members: Vec<(&str, JsonValue)>) -> JsonValue {
+        JsonValue::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// Returns the value of the first member named `key`, if this is an Object
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members
+                .iter()
+                .find(|(member_key, _)| member_key == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Returns the string if this is a String
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the number if this is a Number
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Serializes to compact JSON text
+    ///
+    /// Non-finite numbers (NaN, infinity) have no JSON form and are written as null.
+    pub fn to_json_string(&self) -> String {
+        let mut output = String::new();
+        write_json_value(self, &mut output);
+        output
+    }
+}
+
+fn write_json_value(value: &JsonValue, output: &mut String) {
+    match value {
+        JsonValue::Null => output.push_str("null"),
+        JsonValue::Bool(true) => output.push_str("true"),
+        JsonValue::Bool(false) => output.push_str("false"),
+        JsonValue::Number(number) => write_json_number(*number, output),
+        JsonValue::String(s) => write_json_string(s, output),
+        JsonValue::Array(items) => {
+            output.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    output.push(',');
+                }
+                write_json_value(item, output);
+            }
+            output.push(']');
+        }
+        JsonValue::Object(members) => {
+            output.push('{');
+            for (index, (key, member_value)) in members.iter().enumerate() {
+                if index > 0 {
+                    output.push(',');
+                }
+                write_json_string(key, output);
+                output.push(':');
+                write_json_value(member_value, output);
+            }
+            output.push('}');
+        }
+    }
+}
+
+fn write_json_number(number: f64, output: &mut String) {
+    if !number.is_finite() {
+        output.push_str("null");
+    } else if number.fract() == 0.0 && number.abs() < 1e15 {
+        // whole numbers without a trailing ".0"
+        let _ = write!(output, "{}", number as i64);
+    } else {
+        let _ = write!(output, "{}", number);
+    }
+}
+
+/// Writes `s` as a quoted JSON string, escaping quotes, backslashes and control characters
+fn write_json_string(s: &str, output: &mut String) {
+    output.push('"');
+    for character in s.chars() {
+        match character {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            '\u{08}' => output.push_str("\\b"),
+            '\u{0C}' => output.push_str("\\f"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(output, "\\u{:04x}", c as u32);
+            }
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+}
+
+/// Parses JSON text into a JsonValue
+///
+/// Strict: exactly one value, optional surrounding whitespace, no trailing
+/// commas, no comments, no leading zeros, no raw control characters in strings.
+///
+/// # Arguments
+/// * `text` - The JSON text
+/// * `limits` - Input size and nesting depth caps
+///
+/// # Returns
+/// * `Result<JsonValue, String>` - The value, or an error naming the byte offset
+pub fn parse_json(text: &str, limits: &JsonLimits) -> Result<JsonValue, String> {
+    if text.len() > limits.max_input_bytes {
+        return Err(format!(
+            "JSON input too large: {} bytes (max {})",
+            text.len(),
+            limits.max_input_bytes
+        ));
+    }
+
+    let mut parser = JsonParser {
+        bytes: text.as_bytes(),
+        position: 0,
+        max_depth: limits.max_depth,
+    };
+    parser.skip_whitespace();
+    let value = parser.parse_value(0)?;
+    parser.skip_whitespace();
+    if parser.position != parser.bytes.len() {
+        return Err(parser.error("unexpected data after JSON value"));
+    }
+    Ok(value)
+}
+
+struct JsonParser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    max_depth: usize,
+}
+
+impl JsonParser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("JSON parse error at byte {}: {}", self.position, message)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn expect_literal(&mut self, literal: &str, value: JsonValue) -> Result<JsonValue, String> {
+        if self.bytes[self.position..].starts_with(literal.as_bytes()) {
+            self.position += literal.len();
+            Ok(value)
+        } else {
+            Err(self.error("invalid literal"))
+        }
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Result<JsonValue, String> {
+        match self.peek() {
+            None => Err(self.error("unexpected end of input")),
+            Some(b'n') => self.expect_literal("null", JsonValue::Null),
+            Some(b't') => self.expect_literal("true", JsonValue::Bool(true)),
+            Some(b'f') => self.expect_literal("false", JsonValue::Bool(false)),
+            Some(b'"') => self.parse_string().map(JsonValue::String),
+            Some(b'[') => self.parse_array(depth + 1),
+            Some(b'{') => self.parse_object(depth + 1),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(_) => Err(self.error("unexpected character")),
+        }
+    }
+
+    fn parse_array(&mut self, depth: usize) -> Result<JsonValue, String> {
+        if depth > self.max_depth {
+            return Err(self.error("nesting too deep"));
+        }
+        self.position += 1; // '['
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(JsonValue::Array(items));
+        }
+        loop {
+            self.skip_whitespace();
+            items.push(self.parse_value(depth)?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_object(&mut self, depth: usize) -> Result<JsonValue, String> {
+        if depth > self.max_depth {
+            return Err(self.error("nesting too deep"));
+        }
+        self.position += 1; // '{'
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(JsonValue::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected string key"));
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            if self.peek() != Some(b':') {
+                return Err(self.error("expected ':'"));
+            }
+            self.position += 1;
+            self.skip_whitespace();
+            let value = self.parse_value(depth)?;
+            members.push((key, value));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(JsonValue::Object(members));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, String> {
+        let start = self.position;
+        if self.peek() == Some(b'-') {
+            self.position += 1;
+        }
+        // int: "0" or [1-9][0-9]*
+        match self.peek() {
+            Some(b'0') => self.position += 1,
+            Some(b'1'..=b'9') => self.skip_digits(),
+            _ => return Err(self.error("invalid number")),
+        }
+        if self.peek() == Some(b'.') {
+            self.position += 1;
+            if !matches!(self.peek(), Some(b'0'..=b'9')) {
+                return Err(self.error("expected digit after '.'"));
+            }
+            self.skip_digits();
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            self.position += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.position += 1;
+            }
+            if !matches!(self.peek(), Some(b'0'..=b'9')) {
+                return Err(self.error("expected digit in exponent"));
+            }
+            self.skip_digits();
+        }
+        // The slice is ASCII by construction
+        let number_text = std::str::from_utf8(&self.bytes[start..self.position])
+            .map_err(|_| self.error("invalid number"))?;
+        let number = number_text.parse::<f64>().map_err(|_| self.error("invalid number"))?;
+        // e.g. 1e400: valid syntax, but it would come back out as null
+        if !number.is_finite() {
+            return Err(self.error("number out of range"));
+        }
+        Ok(JsonValue::Number(number))
+    }
+
+    fn skip_digits(&mut self) {
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.position += 1; // opening '"'
+        let mut output = String::new();
+        loop {
+            let run_start = self.position;
+            // copy a run of plain bytes at once (input is valid UTF-8 already)
+            while let Some(byte) = self.peek() {
+                if byte == b'"' || byte == b'\\' || byte < 0x20 {
+                    break;
+                }
+                self.position += 1;
+            }
+            output.push_str(
+                std::str::from_utf8(&self.bytes[run_start..self.position])
+                    .map_err(|_| self.error("invalid UTF-8 in string"))?,
+            );
+
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    self.position += 1;
+                    return Ok(output);
+                }
+                Some(b'\\') => {
+                    self.position += 1;
+                    self.parse_escape(&mut output)?;
+                }
+                Some(_) => return Err(self.error("control character in string")),
+            }
+        }
+    }
+
+    fn parse_escape(&mut self, output: &mut String) -> Result<(), String> {
+        let escaped = self.peek().ok_or_else(|| self.error("unterminated escape"))?;
+        self.position += 1;
+        match escaped {
+            b'"' => output.push('"'),
+            b'\\' => output.push('\\'),
+            b'/' => output.push('/'),
+            b'b' => output.push('\u{08}'),
+            b'f' => output.push('\u{0C}'),
+            b'n' => output.push('\n'),
+            b'r' => output.push('\r'),
+            b't' => output.push('\t'),
+            b'u' => {
+                let first = self.parse_hex4()?;
+                let code_point = if (0xD800..0xDC00).contains(&first) {
+                    // high surrogate: must be followed by \u low surrogate
+                    if !self.bytes[self.position..].starts_with(b"\\u") {
+                        return Err(self.error("unpaired surrogate"));
+                    }
+                    self.position += 2;
+                    let second = self.parse_hex4()?;
+                    if !(0xDC00..0xE000).contains(&second) {
+                        return Err(self.error("invalid low surrogate"));
+                    }
+                    0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00)
+                } else if (0xDC00..0xE000).contains(&first) {
+                    return Err(self.error("unpaired surrogate"));
+                } else {
+                    first
+                };
+                output.push(char::from_u32(code_point).ok_or_else(|| self.error("invalid code point"))?);
+            }
+            _ => return Err(self.error("invalid escape")),
+        }
+        Ok(())
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, String> {
+        let hex_bytes = self
+            .bytes
+            .get(self.position..self.position + 4)
+            .ok_or_else(|| self.error("truncated \\u escape"))?;
+        let hex_text = std::str::from_utf8(hex_bytes).map_err(|_| self.error("invalid \\u escape"))?;
+        if !hex_text.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(self.error("invalid \\u escape"));
+        }
+        let value = u32::from_str_radix(hex_text, 16).map_err(|_| self.error("invalid \\u escape"))?;
+        self.position += 4;
+        Ok(value)
+    }
+}
+
+/// True if a Content-Type header value names JSON (parameters like charset ignored)
+pub fn is_json_content_type(content_type: Option<&str>) -> bool {
+    content_type
+        .and_then(|value| value.split(';').next())
+        .map(|media_type| media_type.trim().eq_ignore_ascii_case("application/json"))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<JsonValue, String> {
+        parse_json(text, &JsonLimits::default())
+    }
+
+    #[test]
+    fn parses_escapes_and_surrogate_pairs() {
+        let value = parse(r#""quote \" slash \/ back \\ \b\f\n\r\t \u00e9 \ud83e\udd80""#).unwrap();
+        assert_eq!(value.as_str(), Some("quote \" slash / back \\ \u{08}\u{0C}\n\r\t é 🦀"));
+
+        for lone_surrogate in [r#""\ud83e""#, r#""\ud83e\u0041""#, r#""\udd80""#] {
+            assert!(parse(lone_surrogate).is_err(), "{}", lone_surrogate);
+        }
+        for bad_string in [r#""\x""#, r#""\u12""#, "\"raw\ncontrol\"", r#""unterminated"#] {
+            assert!(parse(bad_string).is_err(), "{}", bad_string);
+        }
+    }
+
+    #[test]
+    fn enforces_depth_and_size_limits() {
+        let limits = JsonLimits { max_input_bytes: 16, max_depth: 2 };
+        assert!(parse_json("[[1]]", &limits).is_ok());
+        assert!(parse_json("{\"a\":[1]}", &limits).is_ok());
+        assert!(parse_json("[[[1]]]", &limits).unwrap_err().contains("nesting too deep"));
+        assert!(parse_json("{\"a\":{\"b\":{}}}", &limits).unwrap_err().contains("nesting too deep"));
+        assert!(parse_json("\"0123456789abcdef\"", &limits).unwrap_err().contains("too large"));
+    }
+
+    #[test]
+    fn rejects_trailing_data_and_loose_syntax() {
+        let surrounded_by_whitespace = parse(" {\"a\": [1, 2]} \n").unwrap();
+        let expected_items = JsonValue::Array(vec![JsonValue::Number(1.0), JsonValue::Number(2.0)]);
+        assert_eq!(surrounded_by_whitespace.get("a"), Some(&expected_items));
+        for bad_json in ["{} {}", "[1] x", "1 2", "[1,]", "{\"a\":1,}", "01", "1.", "-", "tru", "", "// c\n1"] {
+            assert!(parse(bad_json).is_err(), "{}", bad_json);
+        }
+    }
+
+    #[test]
+    fn numbers_are_finite_and_whole_numbers_print_as_integers() {
+        assert!(parse("1e400").unwrap_err().contains("out of range"));
+        assert!(parse("-1e400").is_err());
+        assert_eq!(parse("-0.5e1").unwrap(), JsonValue::Number(-5.0));
+
+        assert_eq!(JsonValue::Number(3.0).to_json_string(), "3");
+        assert_eq!(JsonValue::Number(-42.0).to_json_string(), "-42");
+        assert_eq!(JsonValue::Number(0.25).to_json_string(), "0.25");
+        assert_eq!(JsonValue::Number(1e20).to_json_string(), "100000000000000000000");
+        assert_eq!(JsonValue::Number(f64::NAN).to_json_string(), "null");
+        assert_eq!(JsonValue::Number(f64::INFINITY).to_json_string(), "null");
+    }
+
+    #[test]
+    fn escaped_strings_round_trip() {
+        let original = "quote \" back \\ newline \n tab \t nul \u{0} bell \u{7} unicode é 🦀";
+        let value = JsonValue::object(vec![("text", JsonValue::String(original.to_string()))]);
+        let json_text = value.to_json_string();
+        assert_eq!(
+            json_text,
+            "{\"text\":\"quote \\\" back \\\\ newline \\n tab \\t nul \\u0000 bell \\u0007 unicode é 🦀\"}"
+        );
+        assert_eq!(parse(&json_text).unwrap(), value);
+    }
+}
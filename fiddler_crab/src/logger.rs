@@ -0,0 +1,219 @@
+/*
+Minimal in-house logger: leveled, one line per event, on stderr
+
+    log_level = "info"          # off | error | warn | info | debug
+    log_format = "kv"           # kv: ts=... level=warn msg="..." request_id=7
+                                # json: {"ts":"...","level":"warn","msg":"...","request_id":"7"}
+    log_request_bodies = false  # true: debug lines include request bodies
+
+Events about one request carry its request_id (log_request_event), so the
+lines of a request can be found with one grep. Request bodies are redacted
+to their length unless log_request_bodies is set.
+
+Each line is formatted first and written with a single write to stderr,
+so lines from different threads do not interleave. Nothing is logged on
+the drop-when-full path: the admission gate runs before any call into
+this module, and drops only show up as counters (/status, /metrics).
+*/
+use std::io::Write;
+
+use crate::config::server_config;
+use crate::json::JsonValue;
+use crate::time_format::UtcDateTime;
+
+/// Severity of a log event; events below the configured log_level are skipped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    /// log_level only: log nothing
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    /// Parses a log_level config value
+    pub fn from_config_value(value: &str) -> Result<Self, String> {
+        match value {
+            "off" => Ok(LogLevel::Off),
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            other => Err(format!("log_level must be off, error, warn, info or debug, got '{}'", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Off => "off",
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        }
+    }
+}
+
+/// Line format of log events
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    /// key=value pairs, values quoted when needed
+    KeyValue,
+    /// One JSON object per line
+    Json,
+}
+
+impl LogFormat {
+    /// Parses a log_format config value
+    pub fn from_config_value(value: &str) -> Result<Self, String> {
+        match value {
+            "kv" => Ok(LogFormat::KeyValue),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("log_format must be kv or json, got '{}'", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogFormat::KeyValue => "kv",
+            LogFormat::Json => "json",
+        }
+    }
+}
+
+/// Whether events at `level` are written (check before building costly fields)
+pub fn log_enabled(level: LogLevel) -> bool {
+    level != LogLevel::Off && level <= server_config().log_level
+}
+
+/// Quotes a kv value if it is empty or has spaces, quotes, '=' or control characters
+fn kv_value(value: &str) -> String {
+    let needs_quotes = value.is_empty()
+        || value.chars().any(|c| c == ' ' || c == '"' || c == '=' || c == '\\' || c.is_control());
+    if !needs_quotes {
+        return value.to_string();
+    }
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Formats one event as a line (without the trailing newline)
+///
+/// # Arguments
+/// * `log_format` - kv or json
+/// * `timestamp` - RFC 3339 UTC time of the event
+/// * `level` - The event's level
+/// * `message` - What happened, e.g. "request timed out"
+/// * `fields` - (key, value) pairs, in order
+///
+/// # Returns
+/// * `String` - The formatted line
+pub fn format_log_line(
+    log_format: LogFormat,
+    timestamp: &str,
+    level: LogLevel,
+    message: &str,
+    fields: &[(&str, &str)],
+) -> String {
+    match log_format {
+        LogFormat::KeyValue => {
+            let mut log_line = format!("ts={} level={} msg={}", timestamp, level.as_str(), kv_value(message));
+            for (key, value) in fields {
+                log_line.push(' ');
+                log_line.push_str(key);
+                log_line.push('=');
+                log_line.push_str(&kv_value(value));
+            }
+            log_line
+        }
+        LogFormat::Json => {
+            let mut members = vec![
+                ("ts", JsonValue::String(timestamp.to_string())),
+                ("level", JsonValue::String(level.as_str().to_string())),
+                ("msg", JsonValue::String(message.to_string())),
+            ];
+            members.extend(fields.iter().map(|(key, value)| (*key, JsonValue::String(value.to_string()))));
+            JsonValue::object(members).to_json_string()
+        }
+    }
+}
+
+/// Writes one event to stderr if its level is enabled
+///
+/// # Arguments
+/// * `level` - Error, Warn, Info or Debug
+/// * `message` - What happened (a fixed phrase; variable parts go in fields)
+/// * `fields` - (key, value) pairs, e.g. [("endpoint", "llamacpp")]
+pub fn log_event(level: LogLevel, message: &str, fields: &[(&str, &str)]) {
+    if !log_enabled(level) {
+        return;
+    }
+    let mut log_line = format_log_line(
+        server_config().log_format,
+        &UtcDateTime::now().rfc3339(),
+        level,
+        message,
+        fields,
+    );
+    log_line.push('\n');
+    // One write per line; a failed write to stderr is not worth failing a request over
+    let _ = std::io::stderr().lock().write_all(log_line.as_bytes());
+}
+
+/// Writes one event about a request, with its request_id as the first field
+pub fn log_request_event(level: LogLevel, request_id: usize, message: &str, fields: &[(&str, &str)]) {
+    if !log_enabled(level) {
+        return;
+    }
+    let request_id = request_id.to_string();
+    let mut request_fields = Vec::with_capacity(fields.len() + 1);
+    request_fields.push(("request_id", request_id.as_str()));
+    request_fields.extend_from_slice(fields);
+    log_event(level, message, &request_fields);
+}
+
+/// A request body as it may appear in a log line: its length, or the body if log_request_bodies is set
+pub fn loggable_body(body: &str) -> String {
+    if server_config().log_request_bodies {
+        body.to_string()
+    } else {
+        format!("[redacted {} bytes]", body.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_kv_and_json_lines() {
+        let fields = [("request_id", "7"), ("error", "read timed out"), ("path", "/echo")];
+        assert_eq!(
+            format_log_line(LogFormat::KeyValue, "2024-03-01T00:00:00.000Z", LogLevel::Warn, "request failed", &fields),
+            "ts=2024-03-01T00:00:00.000Z level=warn msg=\"request failed\" request_id=7 error=\"read timed out\" path=/echo"
+        );
+        assert_eq!(
+            format_log_line(LogFormat::KeyValue, "t", LogLevel::Info, "x", &[("body", "a=\"b\"\n")]),
+            "ts=t level=info msg=x body=\"a=\\\"b\\\"\\n\""
+        );
+        assert_eq!(
+            format_log_line(LogFormat::Json, "t", LogLevel::Error, "bind failed", &[("error", "in \"use\"")]),
+            "{\"ts\":\"t\",\"level\":\"error\",\"msg\":\"bind failed\",\"error\":\"in \\\"use\\\"\"}"
+        );
+    }
+}
@@ -5,25 +5,55 @@ Endpoints:
 make sure there is an endpoint_modules directory in src with main.rs
 
 */
-use std::io::prelude::*;
-use std::net::{TcpListener, TcpStream};
+mod access_log;
+mod admission_gate;
+mod cli;
+mod client_stream;
+mod config;
+mod endpoint_modules;
+mod http_request;
+mod http_response;
+mod json;
+mod logger;
+mod metrics;
+mod probes;
+mod router;
+mod subprocess;
+mod time_format;
+mod watchdog;
+#[cfg(feature = "tls")]
+mod tls;
+
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
 use std::thread;
 use std::collections::VecDeque;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::{Sender, Receiver};
 use std::collections::HashMap;
 use std::panic::AssertUnwindSafe;
 
-
-const MAX_QUEUE_SIZE: usize = 500;
-const PROCESSING_DELAY_MS: u64 = 100; // Adjust as needed
-const REQUEST_HANDLER_PAUSE: u64 = 10; // millis
+use access_log::{access_log_enabled, record_access, start_access_log_writer, AccessLogRecord};
+use admission_gate::{AdmissionDecision, AdmissionGate};
+use client_stream::{client_stream_from_tcp, prepare_client_streams, ClientStream};
+use cli::{parse_command_line, version_text, CliAction, USAGE_TEXT};
+use config::{install_server_config, load_server_config, server_config};
+use http_request::{parse_query_string, read_http_request, HttpRequest, HttpRequestLimits};
+use http_response::{build_error_body, build_error_response, build_http_response, write_http_response};
+use endpoint_modules::{endpoint_registry, install_endpoint_registry, lookup_endpoint_module};
+use metrics::{record_finished_request, ACCEPTED_COUNTER, DROPPED_COUNTER, FAILED_COUNTER, HANDLER_RESTART_COUNTER};
+use logger::{log_enabled, log_event, log_request_event, loggable_body, LogLevel};
+use probes::{mark_server_start, probe_response, PROBE_PATHS};
+use router::{route_request, RouteMatch};
+use watchdog::{run_with_watchdog, WatchdogOutcome};
+
+// Queue size, pacing, timeouts and the bind address are runtime settings: see config.rs
 
 // For states of request_hanlder
+#[derive(Debug, Clone, Copy)]
 enum HandlerState {
-    // Busy,
     Idle,
+    Busy,
     Failed,
 }
 
@@ -39,6 +69,14 @@ enum HandlerState {
 /// - `Failed`: The handler has encountered an error and is not operational.
 /// 
 /// The initial state is set to `Idle`.
+///
+/// Lifecycle:
+/// - Idle -> Busy: the stream-loop claims the handler (compare-and-swap) and hands it a queue;
+///   the handler also marks itself Busy when it receives the queue
+/// - Busy -> Idle: the handler has drained the queue
+/// - Busy -> Failed: the handler panicked; it answers the rest of its batch with 500s and exits
+/// - Failed -> Idle: the stream-loop spawns a replacement handler (the listener keeps running)
+///
 /// AtomicUsize
 ///
 /// Represents an unsigned integer (usize) that can be safely accessed and modified by multiple threads concurrently.
@@ -52,25 +90,68 @@ enum HandlerState {
 /// In the previous code, we used AtomicUsize to represent the HANDLER_STATE because:
 /// 1. Enum Representation: We defined the HandlerState enum with different states (Busy, Idle, Failed).
 /// 2. Integer Mapping: We implicitly mapped these enum variants to integer values 
-/// (e.g., Idle might be 0, Busy might be  1, and Failed might be 2). This mapping is done automatically by the compiler 
-/// when you cast an enum to an integer (e.g., HandlerState::Idle as usize).
+///    (e.g., Idle might be 0, Busy might be  1, and Failed might be 2). This mapping is done automatically by the compiler 
+///    when you cast an enum to an integer (e.g., HandlerState::Idle as usize).
 /// 3. Atomic Storage: We needed an atomic variable to store this integer representation of the state 
-/// so that multiple threads could safely access and update it. 
-/// AtomicUsize is suitable because it can store unsigned integers.
+///    so that multiple threads could safely access and update it. 
+///    AtomicUsize is suitable because it can store unsigned integers.
 static HANDLER_STATE: AtomicUsize = AtomicUsize::new(HandlerState::Idle as usize); 
 
+/// What the handler thread reports for each request: (request id, processed request or error)
+type HandlerResultMessage = (usize, Result<RequestUnit, String>);
+
+/// How the handler processes one request (process_request_with_deadline in the server)
+type ProcessRequestFn = fn(RequestUnit) -> Result<RequestUnit, String>;
+
+/// Messages to the responder thread
+///
+/// The stream-loop registers each accepted stream under its request id;
+/// the handler reports each finished request under the same id.
+enum ResponderMessage {
+    /// A stream waiting for the response to request `id`
+    RegisterStream(usize, ClientStream),
+    /// A finished request (processed RequestUnit or error message), boxed to keep messages small
+    Completed(Box<HandlerResultMessage>),
+}
+
 #[derive(Clone, Debug)]
 struct RequestUnit {
     id: usize,
     endpoint_module_name: Option<String>,  // or module-function name, whatever
+    method: String,  // from the request line, e.g. "POST"
+    path: String,  // from the request line, e.g. "/echo_input_data"
+    query: Option<String>,  // raw query string after '?', if any
+    query_params: Vec<(String, String)>,  // URL-decoded (name, value) pairs from the query
+    http_version: String,  // from the request line, e.g. "HTTP/1.1"
+    request_headers: Vec<(String, String)>,
     body: String,
-    output_for_response: Option<String>,
     stream_addr: std::net::SocketAddr, // Or a unique stream ID
+    received_at: Instant,  // when the request was read off the stream (for queue wait time)
     response_status: Option<u16>, 
     response_headers: Option<Vec<(String, String)>>,
     response_body: Option<String>,
 }
 
+impl RequestUnit {
+    /// Returns the value of the first request header matching `name` (case-insensitive)
+    fn request_header(&self, name: &str) -> Option<&str> {
+        self.request_headers
+            .iter()
+            .find(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the URL-decoded value of the first query parameter named `name`
+    ///
+    /// e.g. "/echo_input_data?input_string=hello%20world" -> Some("hello world")
+    fn query_parameter(&self, name: &str) -> Option<&str> {
+        self.query_params
+            .iter()
+            .find(|(param_name, _)| param_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
 
 
 
@@ -84,12 +165,12 @@ struct RequestUnit {
 /// Each endpoint-module:
 /// - Lives in its own directory in endpoint_modules/
 /// - Has its own input/output handling
-/// - Is referenced in the endpoint lookup table
+/// - Is referenced in the endpoint lookup table (endpoint_registry())
 /// - Processes its specific type of request
 /// 
 /// # Function Steps
 /// 1. Gets endpoint module name from request
-/// 2. Validates module exists in lookup table
+/// 2. Looks up the module's handler in the registry
 /// 3. Routes request to the module
 /// 4. Returns processed result or error
 /// 
@@ -109,31 +190,84 @@ struct RequestUnit {
 /// - No endpoint module is specified
 /// - Specified endpoint module not found in lookup table
 /// - Module processing fails
-/// fn route_request_to_endpoint_module(mut request_unit_struct: RequestUnit) -> Result<RequestUnit, String> {
-/// process_request_with_module
-fn process_request_with_module(mut request_unit_struct: RequestUnit) -> Result<RequestUnit, String> {
-
-    // Log incoming request for debugging
-    println!("Routing request to endpoint module: {:?}", request_unit_struct);
+///
+/// (formerly: route_request_to_endpoint_module)
+fn process_request_with_module(request_unit_struct: RequestUnit) -> Result<RequestUnit, String> {
+
+    // Log incoming request for debugging (the body is redacted unless log_request_bodies is set)
+    if log_enabled(LogLevel::Debug) {
+        log_request_event(
+            LogLevel::Debug,
+            request_unit_struct.id,
+            "routing request to endpoint module",
+            &[
+                ("endpoint", request_unit_struct.endpoint_module_name.as_deref().unwrap_or_default()),
+                ("method", &request_unit_struct.method),
+                ("path", &request_unit_struct.path),
+                ("client", &request_unit_struct.stream_addr.to_string()),
+                ("body", &loggable_body(&request_unit_struct.body)),
+            ],
+        );
+    }
 
     // 1. Get endpoint module name from request
     let endpoint_module_name = request_unit_struct.endpoint_module_name
         .as_ref()
         .ok_or("No endpoint module specified")?;
 
-    // 2. Validate: check if endpoint module exists in lookup table
-    // TODO: implement actual lookup table check
-    if !validate_endpoint_module_exists(endpoint_module_name) {
-        return Err(format!("Endpoint module not found among modules: {}", endpoint_module_name));
-    }
+    // 2. Look up the endpoint module's handler in the registry
+    let endpoint_handler = lookup_endpoint_module(endpoint_module_name)
+        .ok_or_else(|| format!("Endpoint module not found among modules: {}", endpoint_module_name))?;
 
-    // 3. TODO: Route to Module
-    // - Get module from lookup table
-    // - Pass request data to module
-    // - Get processed result from module
+    // 3. Route to Module: the module parses the body, processes it,
+    //    and fills in the response fields
+    endpoint_handler(request_unit_struct)
+}
 
-    // 4. For now, return error to force implementation discussion
-    Err("Need concrete implementation of module lookup and processing".to_string())
+/// Runs the endpoint module for one request under the watchdog
+///
+/// The module gets its own disposable thread and a deadline
+/// (processing_timeout_ms, or endpoint.NAME.processing_timeout_ms).
+/// Past the deadline its child process (if registered) is killed and the
+/// client gets a 504; a module that panics gets a 500. Either way the
+/// handler moves on to the next request.
+///
+/// # Arguments
+/// * `request_unit_struct` - The request to process
+///
+/// # Returns
+/// * `Result<RequestUnit, String>` - As process_request_with_module, or a 504 RequestUnit on timeout
+fn process_request_with_deadline(mut request_unit_struct: RequestUnit) -> Result<RequestUnit, String> {
+    let request_id = request_unit_struct.id;
+    let endpoint_module_name = request_unit_struct.endpoint_module_name.clone().unwrap_or_default();
+    let deadline = server_config().processing_timeout_for(&endpoint_module_name);
+
+    // Keep the request's metadata (not its body) to answer with if the module times out
+    let request_body = std::mem::take(&mut request_unit_struct.body);
+    let mut timed_out_request = request_unit_struct.clone();
+    request_unit_struct.body = request_body;
+
+    match run_with_watchdog(request_id, deadline, move || process_request_with_module(request_unit_struct)) {
+        WatchdogOutcome::Finished(result) => *result,
+        WatchdogOutcome::TimedOut => {
+            log_request_event(
+                LogLevel::Warn,
+                request_id,
+                "request timed out",
+                &[("endpoint", &endpoint_module_name), ("timeout_ms", &deadline.as_millis().to_string())],
+            );
+            timed_out_request.response_status = Some(504);
+            timed_out_request.response_headers = Some(vec![
+                ("Content-Type".to_string(), "application/json".to_string()),
+            ]);
+            timed_out_request.response_body = Some(build_error_body(
+                504,
+                &format!("endpoint module did not finish within {} ms", deadline.as_millis()),
+            ));
+            Ok(timed_out_request)
+        }
+        WatchdogOutcome::Panicked => Err("Endpoint module failed while processing this request".to_string()),
+    }
 }
 
 // /// Processes a request by routing it to the appropriate module and handling the response
@@ -205,18 +339,6 @@ fn process_request_with_module(mut request_unit_struct: RequestUnit) -> Result<R
 //     Err("Need concrete implementation of module lookup and processing".to_string())
 // }
 
-// Helper functions that need to be implemented:
-fn module_exists(module_name: &str) -> bool {
-    // TODO: implement actual module checking
-    true  // temporary
-}
-
-fn validate_endpoint_module_exists(endpoint_module_name: &str) -> bool {
-    // Check if directory exists in endpoint_modules/
-    let module_path = format!("endpoint_modules/{}", endpoint_module_name);
-    std::path::Path::new(&module_path).is_dir()
-}
-
 
 // /// Processes an individual request by routing it to the appropriate endpoint module
 // /// and handling the response.
@@ -291,11 +413,6 @@ fn validate_endpoint_module_exists(endpoint_module_name: &str) -> bool {
 //     Ok(request_unit_struct)
 // }
 
-fn convert_output_to_json_string(output: &Option<String>) -> Result<String, String> {
-    // Implementation needed
-    unimplemented!()
-}
-
 // // for processing and functions on request data
 // /// TODO Doc String Needed!!!
 // fn process_a_request(mut request_unit_struct: RequestUnit) -> Result<RequestUnit, String> {
@@ -424,61 +541,734 @@ fn convert_output_to_json_string(output: &Option<String>) -> Result<String, Stri
 //     });
 // }
 
+/// Handler thread: processes handed-off queues, one request at a time
+///
+/// # Queue Handoff
+/// The stream-loop fills a disposable_handoff_queue. When this handler is
+/// Idle, the stream-loop sets it Busy, sends it the whole queue over
+/// `handoff_receiver`, and starts a fresh empty queue for new requests.
+/// The handler owns the queue it was given: it drains it, drops it, sets
+/// itself Idle, and blocks until the next queue arrives. Only one thread
+/// touches any queue at a time, so no locks are needed.
+///
+/// # Failure
+/// A panic while processing a batch is caught: the request that was in
+/// flight and every request still in the batch are sent to the responder
+/// as errors (500s, so no client hangs), HANDLER_STATE is set to Failed,
+/// and this thread returns. The stream-loop sees Failed and spawns a
+/// replacement with spawn_request_handler.
+/// If the responder is gone, no result of the batch can reach its client:
+/// the handler logs it, sets Failed and returns the same way.
+/// (Catching needs unwinding: with `panic = "abort"`, as in the
+/// release-small profile, a panic ends the process instead.)
+///
+/// # Arguments
+/// * `handoff_receiver` - Queues handed off by the stream-loop
+/// * `sender` - Channel to the responder thread for finished requests
+/// * `process_request` - Processes one request
 fn handler_of_request_and_queue(
-    request_unit_struct: RequestUnit,
-    mut disposable_handoff_queue: VecDeque<RequestUnit>,
-    sender: Sender<(usize, Result<RequestUnit, String>)>
+    handoff_receiver: Receiver<VecDeque<RequestUnit>>,
+    sender: Sender<ResponderMessage>,
+    process_request: ProcessRequestFn,
 ) {
-    // Wrap the closure in AssertUnwindSafe
-    let closure = AssertUnwindSafe(|| {
-        // 1. Add the initial request to the queue
+    // 1. Wait for a queue (idle handlers block here, doing no work)
+    for mut disposable_handoff_queue in handoff_receiver.iter() {
+        HANDLER_STATE.store(HandlerState::Busy as usize, Ordering::Relaxed);
+
+        // Request currently being processed, so it can be answered if processing panics
+        let mut in_flight_request_id: Option<usize> = None;
+        // Set when a result could not be sent: the responder thread is gone
+        let mut responder_gone = false;
+
+        // Wrap the closure in AssertUnwindSafe
+        let closure = AssertUnwindSafe(|| {
+            // 2. Process the queue
+            while let Some(request_unit) = disposable_handoff_queue.pop_front() {
+                let request_id = request_unit.id;
+                in_flight_request_id = Some(request_id);
+                let endpoint_module_name = request_unit.endpoint_module_name.clone().unwrap_or_default();
+
+                // Process the request (under the watchdog's deadline) and handle the result
+                let processing_start = Instant::now();
+                let access_log_record = access_log_enabled().then(|| access_log_record_for(&request_unit, processing_start));
+                let result = process_request(request_unit);
+                let processing_time = processing_start.elapsed();
+                let response_status = match &result {
+                    Ok(processed_request) => processed_request.response_status.unwrap_or(200),
+                    Err(_) => 500,
+                };
+                record_finished_request(&endpoint_module_name, processing_time, response_status >= 500);
+                if let Some(mut access_log_record) = access_log_record {
+                    access_log_record.status = response_status;
+                    access_log_record.bytes = match &result {
+                        Ok(processed_request) => processed_request.response_body.as_ref().map_or(0, String::len),
+                        Err(error_message) => build_error_body(500, error_message).len(),
+                    };
+                    access_log_record.processing_time = processing_time;
+                    access_log_record.completed_at = std::time::SystemTime::now();
+                    // Never waits: dropped (and counted) if the writer is behind
+                    record_access(access_log_record);
+                }
+                // Send the processed RequestUnit (or error message) to the responder thread
+                if let Err(e) = sender.send(ResponderMessage::Completed(Box::new((request_id, result)))) {
+                    log_request_event(LogLevel::Error, request_id, "could not send result to responder", &[("error", &e.to_string())]);
+                    responder_gone = true;
+                    break;
+                }
+                in_flight_request_id = None;
+                HANDLER_BACKLOG_COUNTER.fetch_sub(1, Ordering::Relaxed);
+
+                // Intentional pacing: one request at a time, with a pause between them
+                thread::sleep(Duration::from_millis(server_config().processing_delay_ms));
+            }
+        });
+
+        // Call catch_unwind with the wrapped closure
+        if std::panic::catch_unwind(closure).is_err() {
+            log_event(LogLevel::Error, "request handler panicked", &[]);
+            // Every request of the failed batch gets a 500 rather than hanging
+            let failed_request_ids = in_flight_request_id
+                .into_iter()
+                .chain(disposable_handoff_queue.iter().map(|request_unit| request_unit.id));
+            for request_id in failed_request_ids {
+                FAILED_COUNTER.fetch_add(1, Ordering::Relaxed);
+                let _ = sender.send(ResponderMessage::Completed(Box::new((
+                    request_id,
+                    Err("Request handler failed while processing this batch".to_string()),
+                ))));
+            }
+            HANDLER_BACKLOG_COUNTER.store(0, Ordering::Relaxed);
+            HANDLER_STATE.store(HandlerState::Failed as usize, Ordering::Relaxed);
+            return;
+        }
+        if responder_gone {
+            // The rest of the batch could not be answered either: drop it and let the stream-loop restart us
+            log_event(
+                LogLevel::Error,
+                "request handler stopped: responder is gone",
+                &[("unanswered_requests", &disposable_handoff_queue.len().to_string())],
+            );
+            HANDLER_BACKLOG_COUNTER.store(0, Ordering::Relaxed);
+            HANDLER_STATE.store(HandlerState::Failed as usize, Ordering::Relaxed);
+            return;
+        }
+
+        // 3. Drained: the queue is dropped here; go Idle so the stream-loop hands off the next one
+        HANDLER_STATE.store(HandlerState::Idle as usize, Ordering::Relaxed);
+    }
+}
+
+/// Starts the access-log record of a request the handler is about to process
+///
+/// Status, bytes and processing time are filled in once the request is done.
+fn access_log_record_for(request_unit: &RequestUnit, processing_start: Instant) -> AccessLogRecord {
+    AccessLogRecord {
+        request_id: Some(request_unit.id),
+        completed_at: std::time::SystemTime::now(),
+        client: request_unit.stream_addr,
+        method: request_unit.method.clone(),
+        path: request_unit.path.clone(),
+        query: request_unit.query.clone(),
+        http_version: request_unit.http_version.clone(),
+        endpoint: request_unit.endpoint_module_name.clone(),
+        status: 0,
+        bytes: 0,
+        queue_wait: processing_start.saturating_duration_since(request_unit.received_at),
+        processing_time: Duration::ZERO,
+        referer: request_unit.request_header("Referer").map(str::to_string),
+        user_agent: request_unit.request_header("User-Agent").map(str::to_string),
+    }
+}
+
+/// Result of one handoff attempt by the stream-loop
+#[derive(Debug, PartialEq)]
+enum HandoffOutcome {
+    /// Nothing queued, or the handler is still Busy (or Failed)
+    NotHandedOff,
+    /// The handler was Idle, is now Busy and owns the queue
+    HandedOff,
+    /// The handler thread is gone; the queue was kept and the handler must be replaced
+    HandlerGone,
+}
+
+/// Hands the queue to the handler if requests are waiting and the handler is Idle
+///
+/// Claims the handler (Idle -> Busy), gives it the whole queue and leaves a
+/// fresh empty one behind (counter back to zero). If the send fails the
+/// requests are put back, so nothing is lost while the handler is replaced.
+///
+/// # Arguments
+/// * `disposable_handoff_queue` - The stream-loop's current queue
+/// * `handoff_sender` - Channel to the handler thread
+/// * `queue_capacity` - Capacity of the fresh queue
+///
+/// # Returns
+/// * `HandoffOutcome` - Whether the queue changed hands
+fn hand_off_queue_if_idle(
+    disposable_handoff_queue: &mut VecDeque<RequestUnit>,
+    handoff_sender: &Sender<VecDeque<RequestUnit>>,
+    queue_capacity: usize,
+) -> HandoffOutcome {
+    if disposable_handoff_queue.is_empty()
+        || HANDLER_STATE
+            .compare_exchange(
+                HandlerState::Idle as usize,
+                HandlerState::Busy as usize,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_err()
+    {
+        return HandoffOutcome::NotHandedOff;
+    }
+    let full_queue = std::mem::replace(disposable_handoff_queue, VecDeque::with_capacity(queue_capacity));
+    HANDLER_BACKLOG_COUNTER.store(full_queue.len(), Ordering::Relaxed);
+    QUEUE_COUNTER.store(0, Ordering::Relaxed);
+    match handoff_sender.send(full_queue) {
+        Ok(()) => HandoffOutcome::HandedOff,
+        Err(returned_queue) => {
+            *disposable_handoff_queue = returned_queue.0;
+            HANDLER_BACKLOG_COUNTER.store(0, Ordering::Relaxed);
+            QUEUE_COUNTER.store(disposable_handoff_queue.len(), Ordering::Relaxed);
+            HandoffOutcome::HandlerGone
+        }
+    }
+}
+
+/// Starts a fresh handler thread and marks the handler Idle
+///
+/// Used at startup and to replace a Failed handler.
+///
+/// # Arguments
+/// * `sender` - Channel to the responder thread (cloned for the handler)
+/// * `process_request` - Processes one request
+///
+/// # Returns
+/// * `Sender<VecDeque<RequestUnit>>` - Where the stream-loop hands off queues to the new handler
+fn spawn_request_handler(sender: &Sender<ResponderMessage>, process_request: ProcessRequestFn) -> Sender<VecDeque<RequestUnit>> {
+    let (handoff_sender, handoff_receiver): (Sender<VecDeque<RequestUnit>>, Receiver<VecDeque<RequestUnit>>) = std::sync::mpsc::channel();
+    let sender_for_thread = sender.clone();
+    HANDLER_STATE.store(HandlerState::Idle as usize, Ordering::Relaxed);
+    thread::spawn(move || {
+        handler_of_request_and_queue(handoff_receiver, sender_for_thread, process_request);
+    });
+    handoff_sender
+}
+
+/// Reads one request off an accepted stream and builds its RequestUnit
+///
+/// Runs on the connection's reader thread (see read_admitted_connection).
+/// Requests that can be answered without the queue are answered here (and
+/// access-logged) and return None: unreadable requests (4xx/5xx), probes,
+/// no route (404), wrong method (405).
+///
+/// # Arguments
+/// * `stream` - The accepted client stream
+/// * `request_limits` - Header and body size caps and the read deadline
+///
+/// # Returns
+/// * `Option<RequestUnit>` - The RequestUnit for the queue, or None if already dealt with
+fn request_unit_from_stream(stream: &mut ClientStream, request_limits: &HttpRequestLimits) -> Option<RequestUnit> {
+    // Read the whole request (request line, headers, Content-Length body).
+    // The per-read timeout stops a silent client, the read deadline in
+    // request_limits a trickling one; no single read may outlast the deadline.
+    let read_timeout = Duration::from_millis(server_config().stream_read_timeout_ms).min(request_limits.max_read_time);
+    if let Err(e) = stream.set_read_timeout(Some(read_timeout)) {
+        log_event(LogLevel::Warn, "could not set read timeout", &[("error", &e.to_string())]);
+        return None;
+    }
+    let http_request = match read_http_request(stream, request_limits) {
+        Ok(http_request) => http_request,
+        Err(e) => {
+            // Answer if the client can still hear us, then drop the stream and move on
+            if let Some(status) = e.response_status() {
+                answer_directly(stream, None, &build_error_response(status, &e.to_string(), &[]));
+            }
+            log_event(LogLevel::Info, "unreadable request", &[("error", &e.to_string())]);
+            return None;
+        }
+    };
+
+    // Probes (/healthz, /readyz, /status) are answered right here: no request id, no queue
+    if let Some(probe_response_text) = probe_response(&http_request.method, &http_request.path) {
+        answer_directly(stream, Some(&http_request), &probe_response_text);
+        return None;
+    }
+
+    // Route on the request-line method and path (not the body)
+    let endpoint_name = match route_request(&http_request.method, &http_request.path, endpoint_registry()) {
+        RouteMatch::Found(endpoint_name) => endpoint_name,
+        RouteMatch::NotFound => {
+            answer_directly(stream, Some(&http_request), &build_error_response(404, "no endpoint at this path", &[]));
+            return None;
+        }
+        RouteMatch::MethodNotAllowed(allowed_methods) => {
+            let allow_header = ("Allow".to_string(), allowed_methods.join(", "));
+            let response = build_error_response(405, "method not allowed for this path", &[allow_header]);
+            answer_directly(stream, Some(&http_request), &response);
+            return None;
+        }
+    };
+
+    // Stream Decoupling: Store stream address in RequestUnit
+    let stream_addr = match stream.peer_addr() {
+        Ok(stream_addr) => stream_addr,
+        Err(e) => {
+            log_event(LogLevel::Warn, "could not read peer address", &[("error", &e.to_string())]);
+            return None;
+        }
+    };
+
+    let request_body = http_request.body_as_string();
+
+    // Generate a unique request ID
+    let request_id = REQUEST_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    Some(RequestUnit {
+        id: request_id,
+        // for endpoint-module: the name comes from the routing table
+        endpoint_module_name: Some(endpoint_name.to_string()),
+        method: http_request.method,
+        path: http_request.path,
+        query_params: http_request.query.as_deref().map(parse_query_string).unwrap_or_default(),
+        query: http_request.query,
+        http_version: http_request.version,
+        request_headers: http_request.headers,
+        body: request_body,
+        stream_addr,
+        received_at: Instant::now(),
+        response_status: None, // Initialize response fields to None
+        response_headers: None,
+        response_body: None,
+    })
+}
+
+/// Writes a request answered without the queue, then access-logs it
+///
+/// Write errors are ignored: the client may already be gone, and the
+/// reader thread just ends. The record is still written, as the handler
+/// does for a client that left before its response.
+///
+/// # Arguments
+/// * `stream` - The client's stream
+/// * `http_request` - The request, or None if it could not be read
+/// * `response` - The full response text
+fn answer_directly(stream: &mut ClientStream, http_request: Option<&HttpRequest>, response: &str) {
+    let _ = write_http_response(stream, response);
+    if !access_log_enabled() {
+        return;
+    }
+    if let Ok(client) = stream.peer_addr() {
+        // Never waits: dropped (and counted) if the writer is behind
+        record_access(direct_answer_access_log_record(client, http_request, response));
+    }
+}
+
+/// Builds the access-log record of a request answered without the queue
+///
+/// No request id, no endpoint, and no queue wait or processing time; status
+/// and body size are read back from the response text.
+///
+/// # Arguments
+/// * `client` - The client's address
+/// * `http_request` - The request, or None if it could not be read
+/// * `response` - The full response text that was sent
+///
+/// # Returns
+/// * `AccessLogRecord` - The record for record_access
+fn direct_answer_access_log_record(client: std::net::SocketAddr, http_request: Option<&HttpRequest>, response: &str) -> AccessLogRecord {
+    // "HTTP/1.1 404 Not Found\r\n...\r\n\r\nbody"
+    let status = response.split(' ').nth(1).and_then(|status| status.parse().ok()).unwrap_or(0);
+    let bytes = response.split_once("\r\n\r\n").map_or(0, |(_, body)| body.len());
+    AccessLogRecord {
+        request_id: None,
+        completed_at: std::time::SystemTime::now(),
+        client,
+        method: http_request.map(|http_request| http_request.method.clone()).unwrap_or_default(),
+        path: http_request.map(|http_request| http_request.path.clone()).unwrap_or_default(),
+        query: http_request.and_then(|http_request| http_request.query.clone()),
+        http_version: http_request.map(|http_request| http_request.version.clone()).unwrap_or_default(),
+        endpoint: None,
+        status,
+        bytes,
+        queue_wait: Duration::ZERO,
+        processing_time: Duration::ZERO,
+        referer: http_request.and_then(|http_request| http_request.header("Referer")).map(str::to_string),
+        user_agent: http_request.and_then(|http_request| http_request.header("User-Agent")).map(str::to_string),
+    }
+}
+
+/// Writes a server-generated error response (standard JSON error body) and flushes
+///
+/// Used by the responder for module errors (500). Write errors are ignored:
+/// the client may already be gone.
+fn respond_with_error(stream: &mut ClientStream, status: u16, message: &str) {
+    let response = build_error_response(status, message, &[]);
+    let _ = write_http_response(stream, &response);
+}
+
+/// Writes a processed RequestUnit back to its client
+///
+/// Sends the status the module set (default 200), every header the module
+/// set in response_headers, and the body; see http_response::build_http_response
+/// for Content-Length, Content-Type default and Connection handling.
+///
+/// # Arguments
+/// * `stream` - The client's stream, taken from stream_map
+/// * `processed_request` - The RequestUnit returned by the endpoint module
+///
+/// # Returns
+/// * `std::io::Result<usize>` - Bytes written, or Err if writing to the client failed
+fn write_processed_response(stream: &mut ClientStream, processed_request: RequestUnit) -> std::io::Result<usize> {
+    let status = processed_request.response_status.unwrap_or(200);
+    let headers = processed_request.response_headers.unwrap_or_default();
+    let body = processed_request.response_body.unwrap_or_default();
+
+    let response = build_http_response(status, &headers, &body);
+    write_http_response(stream, &response)
+}
+
+/// What the stream-loop did with one accepted connection
+#[derive(Debug, PartialEq)]
+enum AcceptOutcome {
+    /// Turned away by the admission gate: closed unread, no ID, nothing registered
+    Dropped,
+    /// Admitted and handed to a reader thread, which reports back on the reader channel
+    Reading,
+    /// Admitted, but the stream or the reader thread could not be set up
+    NotRead,
+}
+
+/// What a reader thread reports to the stream-loop, once per admitted connection
+enum ReaderMessage {
+    /// Read and routed: the stream-loop registers the stream and queues the request
+    Read(Box<(RequestUnit, ClientStream)>),
+    /// Already answered (probe, 404, 405, unreadable) or gone: nothing to queue
+    Finished,
+}
+
+/// Whether the stream-loop could queue the requests its readers reported
+#[derive(Debug, PartialEq)]
+enum QueueOutcome {
+    /// Every reported request was registered with the responder and queued
+    Queued,
+    /// The responder channel is closed; the stream-loop must restart
+    ResponderGone,
+}
+
+/// Admits (or drops) one accepted connection and starts a reader thread for it
+///
+/// The admission gate runs first, before any read, so a dropped connection
+/// costs nothing: no parsing, no request ID, no stream_map entry, no log line.
+/// An admitted connection is read on its own thread, so a client that sends
+/// its request slowly never holds up accept() (or probes, or handoffs).
+///
+/// # Arguments
+/// * `tcp_stream` - The connection just returned by `listener.accept()`
+/// * `admission_gate` - The stream-loop's gate (queue-full check and cool-off)
+/// * `waiting_request_count` - Requests queued plus connections still being read
+/// * `request_limits` - Header and body size caps and the read deadline
+/// * `reader_sender` - Where the reader thread reports the request
+///
+/// # Returns
+/// * `AcceptOutcome` - What happened to the connection
+fn accept_connection(
+    tcp_stream: TcpStream,
+    admission_gate: &mut AdmissionGate,
+    waiting_request_count: usize,
+    request_limits: &HttpRequestLimits,
+    reader_sender: &Sender<ReaderMessage>,
+) -> AcceptOutcome {
+    // if queue is full (or cooling off after it was): drop unread (the stream closes when dropped)
+    if admission_gate.check(waiting_request_count) == AdmissionDecision::Drop {
+        return AcceptOutcome::Dropped;
+    }
+    // Admitted, so this connection gets work anyway: publish the drops counted so far
+    ACCEPTED_COUNTER.fetch_add(1, Ordering::Relaxed);
+    flush_dropped_count(admission_gate);
+
+    // Accepted streams should block (with timeouts), unlike the listener
+    if tcp_stream.set_nonblocking(false).is_err() {
+        return AcceptOutcome::NotRead;
+    }
+
+    let request_limits = *request_limits;
+    let reader_sender = reader_sender.clone();
+    let spawn_result = thread::Builder::new()
+        .name("request-reader".to_string())
+        .spawn(move || read_admitted_connection(tcp_stream, &request_limits, &reader_sender));
+    match spawn_result {
+        Ok(_) => AcceptOutcome::Reading,
+        Err(e) => {
+            log_event(LogLevel::Warn, "could not start request reader", &[("error", &e.to_string())]);
+            AcceptOutcome::NotRead
+        }
+    }
+}
+
+/// Reader thread: reads one admitted connection and reports it to the stream-loop
+///
+/// Requests answered without the queue are answered here; everything else
+/// goes back to the stream-loop, which owns the queue. Exactly one message
+/// is sent, so the stream-loop can count connections still being read.
+///
+/// # Arguments
+/// * `tcp_stream` - The admitted connection (already blocking)
+/// * `request_limits` - Header and body size caps and the read deadline
+/// * `reader_sender` - The stream-loop's reader channel
+fn read_admitted_connection(tcp_stream: TcpStream, request_limits: &HttpRequestLimits, reader_sender: &Sender<ReaderMessage>) {
+    // Plain, or TLS when HTTPS is configured (the handshake runs on the first read)
+    let mut stream = match client_stream_from_tcp(tcp_stream) {
+        Ok(stream) => stream,
+        Err(e) => {
+            log_event(LogLevel::Warn, "could not set up client stream", &[("error", &e.to_string())]);
+            let _ = reader_sender.send(ReaderMessage::Finished);
+            return;
+        }
+    };
+
+    // The request ID is assigned in here, so only admitted requests use one
+    let reader_message = match request_unit_from_stream(&mut stream, request_limits) {
+        Some(request_unit_struct) => ReaderMessage::Read(Box::new((request_unit_struct, stream))),
+        None => ReaderMessage::Finished,
+    };
+    // A closed channel means the stream-loop restarted; the stream is dropped (closed) here
+    let _ = reader_sender.send(reader_message);
+}
+
+/// Registers and queues the requests the reader threads have finished reading
+///
+/// Never blocks: takes only the messages already waiting.
+///
+/// # Arguments
+/// * `reader_receiver` - The stream-loop's end of the reader channel
+/// * `reads_in_flight` - Connections still being read; one less per message
+/// * `disposable_handoff_queue` - The current queue; read requests are pushed here
+/// * `sender` - Responder channel the streams are registered on
+///
+/// # Returns
+/// * `QueueOutcome` - Queued, or ResponderGone if the responder channel is closed
+fn queue_read_requests(
+    reader_receiver: &Receiver<ReaderMessage>,
+    reads_in_flight: &mut usize,
+    disposable_handoff_queue: &mut VecDeque<RequestUnit>,
+    sender: &Sender<ResponderMessage>,
+) -> QueueOutcome {
+    while let Ok(reader_message) = reader_receiver.try_recv() {
+        *reads_in_flight = reads_in_flight.saturating_sub(1);
+        let ReaderMessage::Read(read_request) = reader_message else {
+            continue;
+        };
+        let (request_unit_struct, stream) = *read_request;
+
+        // Hand the stream to the responder, which keeps it in stream_map until the result arrives.
+        // (Registered before the request is queued, so it always arrives ahead of the result.)
+        if let Err(e) = stream.set_write_timeout(Some(Duration::from_millis(server_config().stream_write_timeout_ms))) {
+            log_request_event(LogLevel::Warn, request_unit_struct.id, "could not set write timeout", &[("error", &e.to_string())]);
+        }
+        if sender.send(ResponderMessage::RegisterStream(request_unit_struct.id, stream)).is_err() {
+            return QueueOutcome::ResponderGone;
+        }
+
+        // add request to queue!
         disposable_handoff_queue.push_back(request_unit_struct);
+        QUEUE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    }
+    QueueOutcome::Queued
+}
 
-        // 2. Process the queue
-        loop {
-            if let Some(request_unit) = disposable_handoff_queue.pop_front() {
-                // Process the request and handle the result
-                match process_request_with_module(request_unit.clone()) {
+/// Moves the admission gate's drop count to DROPPED_COUNTER (for /status and /metrics)
+///
+/// Called off the drop path only (idle polls, admitted connections), so a
+/// dropped connection itself never touches shared state.
+fn flush_dropped_count(admission_gate: &mut AdmissionGate) {
+    let dropped_count = admission_gate.take_dropped_count();
+    if dropped_count > 0 {
+        DROPPED_COUNTER.fetch_add(dropped_count, Ordering::Relaxed);
+    }
+}
+
+/// Responder thread: writes finished requests back to their streams
+///
+/// Owns stream_map (request id -> ClientStream). The stream-loop registers each
+/// read stream here and goes straight back to `listener.accept()`; the handler
+/// reports each finished request here. Neither of them ever waits on a
+/// client write.
+///
+/// Runs until every sender (stream-loop and handlers) is dropped.
+///
+/// # Arguments
+/// * `receiver` - Receiving end of the responder channel
+fn responder_of_completed_requests(receiver: Receiver<ResponderMessage>) {
+    // Create a mapping to store streams by request ID
+    let mut stream_map: HashMap<usize, ClientStream> = HashMap::new();
+
+    for message in receiver {
+        match message {
+            ResponderMessage::RegisterStream(request_id, stream) => {
+                stream_map.insert(request_id, stream);
+            }
+            ResponderMessage::Completed(completed) => {
+                let (request_id, result) = *completed;
+                // Find the corresponding stream using the request ID
+                let Some(mut stream) = stream_map.remove(&request_id) else {
+                    log_request_event(LogLevel::Warn, request_id, "stream not found for finished request", &[]);
+                    continue;
+                };
+                // Handle the result from the handler
+                match result {
                     Ok(processed_request) => {
-                        // Send the processed RequestUnit back to the main thread
-                        if let Err(e) = sender.send((processed_request.id, Ok(processed_request))) {
-                            eprintln!("Error sending processed request to main thread: {}", e);
-                            // TODO: Handle the error appropriately (e.g., log, retry, or exit)
+                        // Send the module's output with its status and headers
+                        if let Err(e) = write_processed_response(&mut stream, processed_request) {
+                            log_request_event(LogLevel::Info, request_id, "could not write response", &[("error", &e.to_string())]);
                         }
                     }
                     Err(error_message) => {
-                        // Send the error message back to the main thread
-                        if let Err(e) = sender.send((request_unit.id, Err(error_message))) {
-                            eprintln!("Error sending error message to main thread: {}", e);
-                            // TODO: Handle the error appropriately (e.g., log, retry, or exit)
-                        }
+                        // Send error response
+                        respond_with_error(&mut stream, 500, &error_message);
                     }
                 }
-            } else {
-                // Queue is empty, wait a bit before checking again
-                thread::sleep(Duration::from_millis(REQUEST_HANDLER_PAUSE));
+                // stream is dropped (closed) here
             }
         }
-    });
-
-    // Call catch_unwind with the wrapped closure
-    std::panic::catch_unwind(closure).unwrap_or_else(|_| {
-        HANDLER_STATE.store(HandlerState::Failed as usize, Ordering::Relaxed);
-        println!("Handler thread panicked!");
-        // You'll need to return a RequestUnit here as well
-    });
+    }
 }
 
-
-// TODO Add explanation here, in detail
-/*
-text
-*/
+/// Number of requests in the stream-loop's current disposable_handoff_queue
+///
+/// Incremented once per queued request, reset to 0 when the queue is handed
+/// off to the handler. Dropped connections never touch it; the admission
+/// gate itself reads the stream-loop's own queue length.
 static QUEUE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Requests handed off to the handler and not finished yet
+///
+/// Set to the batch size at handoff, decremented by the handler after each
+/// request, zeroed if the batch fails. /readyz adds it to QUEUE_COUNTER, so
+/// a handler saturated by one big batch is not reported ready.
+static HANDLER_BACKLOG_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Serializes tests that set HANDLER_STATE and the queue counters (they are process-wide)
+#[cfg(test)]
+static HANDLER_STATE_TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Source of unique request IDs (also the running total of requests given an ID)
 static REQUEST_ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
+/// Prints what is routable in this build: "METHODS path -> endpoint name"
+fn print_registered_endpoints() {
+    println!("Registered endpoints:");
+    for registered_endpoint in endpoint_registry() {
+        println!("  {} {} -> {}", registered_endpoint.methods.join(","), registered_endpoint.path, registered_endpoint.name);
+    }
+    for probe_path in PROBE_PATHS {
+        println!("  GET {} -> (built-in)", probe_path);
+    }
+}
+
+/// Logs what is routable, one info line per endpoint (the --list-endpoints output, as log events)
+fn log_registered_endpoints() {
+    for registered_endpoint in endpoint_registry() {
+        log_event(
+            LogLevel::Info,
+            "endpoint registered",
+            &[
+                ("endpoint", registered_endpoint.name),
+                ("methods", &registered_endpoint.methods.join(",")),
+                ("path", registered_endpoint.path),
+            ],
+        );
+    }
+}
 
 fn main() {
+
+    // Command line: setting flags, or an action that prints and exits
+    let command_line_args: Vec<String> = std::env::args().skip(1).collect();
+    let command_line = match parse_command_line(&command_line_args) {
+        Ok(command_line) => command_line,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    match command_line.action {
+        CliAction::PrintVersion => {
+            println!("{}", version_text());
+            return;
+        }
+        CliAction::PrintHelp => {
+            print!("{}", USAGE_TEXT);
+            return;
+        }
+        CliAction::RunServer | CliAction::ListEndpoints | CliAction::CheckConfig => {}
+    }
+
+    // Defaults < config file < FIDDLER_CRAB_* environment < command line flags
+    let loaded_config = match load_server_config(
+        command_line.config_path.as_deref(),
+        &command_line.settings,
+        std::env::vars(),
+    ) {
+        Ok(loaded_config) => loaded_config,
+        Err(e) => {
+            eprintln!("Configuration error: {}", e);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = install_server_config(loaded_config) {
+        eprintln!("Configuration error: {}", e);
+        std::process::exit(2);
+    }
+    let server_config = server_config();
+
+    // HTTPS: load certificate and key now, so a bad path stops startup (and --check-config)
+    if let Err(e) = prepare_client_streams(server_config) {
+        eprintln!("Configuration error: {}", e);
+        std::process::exit(2);
+    }
+    if let Err(e) = install_endpoint_registry(server_config) {
+        eprintln!("Configuration error: {}", e);
+        std::process::exit(2);
+    }
+
+    match command_line.action {
+        CliAction::ListEndpoints => {
+            print_registered_endpoints();
+            return;
+        }
+        CliAction::CheckConfig => {
+            // Loading already validated every key; also make sure the address resolves
+            if let Err(e) = server_config.bind_address.to_socket_addrs() {
+                eprintln!("Configuration error: bind_address {}: {}", server_config.bind_address, e);
+                std::process::exit(2);
+            }
+            print!("{}", server_config.to_config_text());
+            println!("# config OK");
+            return;
+        }
+        _ => {}
+    }
+
+    // Access log: open the file now, so a bad path stops startup
+    if let Err(e) = start_access_log_writer(server_config) {
+        eprintln!("Configuration error: {}", e);
+        std::process::exit(2);
+    }
+
+    log_event(
+        LogLevel::Info,
+        "server starting",
+        &[
+            ("version", &version_text()),
+            ("bind_address", &server_config.bind_address),
+            ("scheme", if server_config.tls_cert_path.is_some() { "https" } else { "http" }),
+            ("max_queue_size", &server_config.max_queue_size.to_string()),
+            ("pacing_ms", &server_config.processing_delay_ms.to_string()),
+        ],
+    );
+    log_registered_endpoints();
+    mark_server_start();
     
     // Main loop for crash resistance, 'Let it fail, and try again.'
     // Main Loop:
@@ -493,19 +1283,56 @@ fn main() {
     // queue when the server starts. It might also handle the creation of a new queue if the handler thread 
     // encounters an error, but this logic might also be delegated to the stream-loop.
     loop {
-        let listener = TcpListener::bind("127.0.0.1:8080").unwrap();
-        // TODO error handling, when fails: signal_to_restart = True, restart loop
+        let listener = match TcpListener::bind(&server_config.bind_address) {
+            Ok(listener) => listener,
+            Err(e) => {
+                log_event(
+                    LogLevel::Error,
+                    "could not bind listener, retrying",
+                    &[("bind_address", &server_config.bind_address), ("error", &e.to_string())],
+                );
+                thread::sleep(Duration::from_millis(1000));
+                continue;
+            }
+        };
+        // Non-blocking accept: between connections the stream-loop also checks
+        // whether the handler is Idle and ready for the next queue
+        if let Err(e) = listener.set_nonblocking(true) {
+            log_event(LogLevel::Error, "could not set listener non-blocking, retrying", &[("error", &e.to_string())]);
+            continue;
+        }
+
+        // Create a channel to the responder thread: the stream-loop registers streams on it,
+        // handler threads report finished requests on it
+        let (sender, receiver): (Sender<ResponderMessage>, Receiver<ResponderMessage>) = std::sync::mpsc::channel();
 
-        // Create a channel for communication between the main thread and the handler thread
-        let (sender, receiver): (Sender<(usize, Result<RequestUnit, String>)>, Receiver<(usize, Result<RequestUnit, String>)>) = std::sync::mpsc::channel();
+        // The responder owns stream_map and writes responses, so the stream-loop
+        // goes straight back to accepting connections
+        thread::spawn(move || {
+            responder_of_completed_requests(receiver);
+        });
 
-        // Create a mapping to store streams by request ID
-        let mut stream_map: HashMap<usize, TcpStream> = HashMap::new();
+        // Start the (single) handler thread; queues are handed to it over this channel
+        let mut handoff_sender = spawn_request_handler(&sender, process_request_with_deadline);
 
-        // Clone the sender before entering the loop
-        let sender_clone = sender.clone();
+        // Header and body size caps and the read deadline for reading requests off the socket
+        let request_limits = server_config.http_request_limits();
+
+        // Admitted connections are read on their own threads, which report back here;
+        // they count against max_queue_size while they are being read
+        let (reader_sender, reader_receiver): (Sender<ReaderMessage>, Receiver<ReaderMessage>) = std::sync::mpsc::channel();
+        let mut reads_in_flight: usize = 0;
+
+        // Initial creation (in the main loop)
+        let mut disposable_handoff_queue: VecDeque<RequestUnit> = VecDeque::with_capacity(server_config.max_queue_size);
+        QUEUE_COUNTER.store(0, Ordering::Relaxed);
+
+        // Decides, before anything is read, whether a new connection gets any work at all
+        let mut admission_gate = AdmissionGate::new(
+            server_config.max_queue_size,
+            Duration::from_millis(server_config.queue_full_cool_off_ms),
+        );
 
-        
         // Purpose: The stream-loop is responsible for listening for incoming requests, 
         // handling the request queue, and passing requests to the handler.
         // Execution: The stream-loop runs continuously within the main loop, 
@@ -515,345 +1342,61 @@ fn main() {
         // off the previous queue to the handler. It also manages adding requests 
         // to the current queue and checking if the queue is full.
         // Additionally, the stream-loop can signal a restart of the main loop in case of bad failures.
-        for stream in listener.incoming() {
-            match stream {
-                Ok(mut stream) => {
-                    
-                    // Initial creation (in the main loop)
-                    let mut disposable_handoff_queue: Option<VecDeque<RequestUnit>> = Some(VecDeque::with_capacity(MAX_QUEUE_SIZE));
-                
-                    let mut buffer = [0; 1024];
-                    stream.read(&mut buffer).unwrap();
-                    // TODO when fails, error restart_signal = True / or drop and continue stream
-
-                    let request_string = String::from_utf8_lossy(&buffer[..]);
-                    // TODO when fails, error restart_signal = True / or drop and continue stream
-
-                    // Very basic parsing of the request (assuming POST)
-                    if request_string.starts_with("POST") {
-                        // let body_start = request_string.find("\r\n\r\n").unwrap_or(0) + 4;
-                        // let request_body = request_string[body_start..].to_string();
-                        let body_start = match request_string.find("\r\n\r\n") {
-                            Some(index) => index + 4,
-                            None => {
-                                eprintln!("Error: Invalid request format. Could not find end of headers.");
-                                // Handle the error appropriately (e.g., return an error response to the client)
-                                // For now, let's just return 0 to avoid a crash, but you should replace this 
-                                // with more robust error handling
-                                0 
-                            }
-                        };
-                        
-                        let request_body = request_string[body_start..].to_string();
-                        // TODO when fails, error restart_signal = True / or drop and continue stream
-
-                        // Generate a unique request ID
-                        let request_id = REQUEST_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
-
-                        // for endpoint-module: After parsing the request path
-                        // let endpoint_name = &request_path[1..request_path.find('/').unwrap_or(request_path.len())]; // Remove leading '/'
-                        let endpoint_name = &request_body[1..request_body.find('/').unwrap_or(request_body.len())]; // Remove leading '/'
-                        
-                        // for endpoint-module
-                        let endpoint_module_name = format!("endpoint_modules/{}/module.rs", endpoint_name);
-
-                        // Stream Decoupling: Store stream address in RequestUnit
-                        let stream_addr = stream.peer_addr().unwrap(); 
-                        let request_unit_struct = RequestUnit {
-                            id: request_id,
-                            endpoint_module_name: Some(endpoint_module_name),
-                            body: request_body,
-                            output_for_response: None,
-                            stream_addr: stream_addr,
-                            response_status: None, // Initialize response fields to None
-                            response_headers: None,
-                            response_body: None,
-                        };
-                        
-                        // Insert the stream into the map
-                        stream_map.insert(request_id, stream);
-
-                        // if Idle
-                        // Checks if the request handler is currently in the `Idle` state.
-                        //
-                        // This function loads the current state of the handler from the `HANDLER_STATE` atomic variable
-                        // and compares it with the integer representation of the `Idle` state. 
-                        // It returns `true` if the handler is `Idle` and `false` otherwise (if it's `Busy` or `Failed`).
-                        //
-                        // Note: This check only explicitly distinguishes between `Idle` and non-`Idle` states.
-                        // It does not differentiate between `Busy` and `Failed` within this specific check. 
-                        // However, the `else` block that follows this check handles both `Busy` and `Failed` states 
-                        // by attempting to add the incoming request to the queue.
-                        if HANDLER_STATE.load(Ordering::Relaxed) == HandlerState::Idle as usize {
-                            /*
-                            handler can be: 1 busy, 2. not_busy 3. failed
-                            
-                            A. look for quit-signal_to_restart (optional, if needed later)
-                            B. if handler is not busy, give request+queue to handler & reset counter to 0
-                            C. if handler is busy, check counter
-                            E. if counter > MAX: drop request
-                            F. if counter < MAX: check if there is an existing queue
-                            G. if there is an existing queue: add request to quque
-                            H: if there is no queue: make a queue and add request to queue
-                            loop back 
-                            */
-                            // Request processing oc
-                            // when this fails (everything will fail at some point)
-                            // this should output a signal to set a 'restart' flag 
-                            // Spawn the handler thread and pass the sender channel
-                            // In main
-                            
-                            // Clone sender_clone inside the loop
-                            let sender_for_thread = sender_clone.clone(); 
-                            
-                            thread::spawn(move || {
-                                handler_of_request_and_queue(
-                                    request_unit_struct,
-                                    disposable_handoff_queue.take().unwrap(),
-                                    sender_for_thread, // Pass a clone of sender_clone
-                                );
-                            });
-
-                            // 1. handler_of_request_and_queue(request, quque)
-
-                            
-                            // Double Tap: make sure queue is removed
-                            // When the handler finishes or fails (in the handler thread or stream-loop):
-                            // let disposable_handoff_queue: Option<VecDeque<String>> = None; // Indicate that a new queue needs to be created 
-                            
-                            // 2. counter = zero
-                            // Reset the queue counter
-                            QUEUE_COUNTER.store(0, Ordering::Relaxed);
-
-                            // 3. make a new empty disposable_handoff_queue
-                            // let mut disposable_handoff_queue: Option<VecDeque<String>> = Some(VecDeque::with_capacity(MAX_QUEUE_SIZE));
-                            
-                            // if faile:
-                            // exit/continue/break stream-loop/quit/reboot
-                            
-                            
-                        } else {  // if NOT Idle: elif busy, elif failed
-                            
-                            // Handle busy/failed state (e.g., add to queue)
-                            // Check if a queue exists and add requests to it
-                            if let Some(queue) = &mut disposable_handoff_queue {
-                                // ... (add requests to the queue) ...
-                                // if queue is not full: add request to queue
-                                if QUEUE_COUNTER.load(Ordering::Relaxed) < MAX_QUEUE_SIZE {
-
-                                    // add request to queue!
-                                    queue.push_back(request_unit_struct); // Call push_back on the VecDeque inside the Option
-                     
-                                    QUEUE_COUNTER.fetch_add(1, Ordering::Relaxed);
-                            } else {
-                                // No queue available, create a new one 
-                                // let mut disposable_handoff_queue: Option<VecDeque<String>> = Some(VecDeque::with_capacity(MAX_QUEUE_SIZE));
-                                // ... (potentially add the current request to the new queue) ...
-                            }
-                            
-
-                            } else {
-                                // Ignore the request (queue is full)
-                            }
-                            
-                            // increment counter
-                            QUEUE_COUNTER.fetch_add(1, Ordering::Relaxed);
-                        }
-                        
-                        
-                        if HANDLER_STATE.load(Ordering::Relaxed) == HandlerState::Failed as usize {
-                            println!("Handler thread failed. Restarting..."); // Log the failure
-                            break; // Exit the stream-loop to signal a restart 
-                        }
-                    }
-                    // } else if request_string.starts_with("GET") {
-                    //     /*
-                    //     TODO: this "works" but it returns a document containing
-                    //     the post-request format server return...
-                    //     which is something...but should be...optimized...
-                    //     */
-                        
-                    //     // let body_start = request_string.find("\r\n\r\n").unwrap_or(0) + 4;
-                    //     // let request_body = request_string[body_start..].to_string();
-                    //     let request_body = if let Some(index) = request_string.find('X') {
-                    //         &request_string[index + 1..] 
-                    //     } else {
-                    //         "" // No query string found
-                    //     };
-                        
-                    //     // // Parse query_string into parameters (e.g., using a library or manual splitting)
-                    //     // let parameters: HashMap<String, String> = /* ... parsing logic ... */;
-                        
-                    //     // let request_body = query_string;
-                    
-                    //     // Generate a unique request ID
-                    //     let request_id = REQUEST_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
-                    
-                    //     // Stream Decoupling: Store stream address in RequestUnit
-                    //     let stream_addr = stream.peer_addr().unwrap(); 
-                    //     let mut request_unit_struct = RequestUnit {
-                    //         id: request_id,
-                    //         endpoint_module_name: "".to_string(),
-                    //         body: request_body.to_string(),
-                    //         output_for_response: None,
-                    //         stream_addr: stream_addr,
-                    //         response_status: None, // Initialize response fields to None
-                    //         response_headers: None,
-                    //         response_body: None,
-                    //     };
-                        
-                    //     // Insert the stream into the map
-                    //     stream_map.insert(request_id, stream);
-
-                    //     // if Idle
-                    //     // Checks if the request handler is currently in the `Idle` state.
-                    //     //
-                    //     // This function loads the current state of the handler from the `HANDLER_STATE` atomic variable
-                    //     // and compares it with the integer representation of the `Idle` state. 
-                    //     // It returns `true` if the handler is `Idle` and `false` otherwise (if it's `Busy` or `Failed`).
-                    //     //
-                    //     // Note: This check only explicitly distinguishes between `Idle` and non-`Idle` states.
-                    //     // It does not differentiate between `Busy` and `Failed` within this specific check. 
-                    //     // However, the `else` block that follows this check handles both `Busy` and `Failed` states 
-                    //     // by attempting to add the incoming request to the queue.
-                    //     if HANDLER_STATE.load(Ordering::Relaxed) == HandlerState::Idle as usize {
-                    //         /*
-                    //         handler can be: 1 busy, 2. not_busy 3. failed
-                            
-                    //         A. look for quit-signal_to_restart (optional, if needed later)
-                    //         B. if handler is not busy, give request+queue to handler & reset counter to 0
-                    //         C. if handler is busy, check counter
-                    //         E. if counter > MAX: drop request
-                    //         F. if counter < MAX: check if there is an existing queue
-                    //         G. if there is an existing queue: add request to quque
-                    //         H: if there is no queue: make a queue and add request to queue
-                    //         loop back 
-                    //         */
-                    //         // Request processing oc
-                    //         // when this fails (everything will fail at some point)
-                    //         // this should output a signal to set a 'restart' flag 
-                    //         // Spawn the handler thread and pass the sender channel
-                    //         // In main
-                            
-                    //         // Clone sender_clone inside the loop
-                    //         let sender_for_thread = sender_clone.clone(); 
-                            
-                    //         thread::spawn(move || {
-                    //             handler_of_request_and_queue(
-                    //                 request_unit_struct,
-                    //                 disposable_handoff_queue.take().unwrap(),
-                    //                 sender_for_thread, // Pass a clone of sender_clone
-                    //             );
-                    //         });
-
-                    //         // 1. handler_of_request_and_queue(request, quque)
-
-                            
-                    //         // Double Tap: make sure queue is removed
-                    //         // When the handler finishes or fails (in the handler thread or stream-loop):
-                    //         // let disposable_handoff_queue: Option<VecDeque<String>> = None; // Indicate that a new queue needs to be created 
-                            
-                    //         // 2. counter = zero
-                    //         // Reset the queue counter
-                    //         QUEUE_COUNTER.store(0, Ordering::Relaxed);
-
-                    //         // 3. make a new empty disposable_handoff_queue
-                    //         // let mut disposable_handoff_queue: Option<VecDeque<String>> = Some(VecDeque::with_capacity(MAX_QUEUE_SIZE));
-                            
-                    //         // if faile:
-                    //         // exit/continue/break stream-loop/quit/reboot
-                            
-                            
-                    //     } else {  // if NOT Idle: elif busy, elif failed
-                            
-                    //         // Handle busy/failed state (e.g., add to queue)
-                    //         // Check if a queue exists and add requests to it
-                    //         if let Some(queue) = &mut disposable_handoff_queue {
-                    //             // ... (add requests to the queue) ...
-                    //             // if queue is not full: add request to queue
-                    //             if QUEUE_COUNTER.load(Ordering::Relaxed) < MAX_QUEUE_SIZE {
-
-                    //                 // add request to queue!
-                    //                 queue.push_back(request_unit_struct); // Call push_back on the VecDeque inside the Option
-                     
-                    //                 QUEUE_COUNTER.fetch_add(1, Ordering::Relaxed);
-                    //         } else {
-                    //             // No queue available, create a new one 
-                    //             // let mut disposable_handoff_queue: Option<VecDeque<String>> = Some(VecDeque::with_capacity(MAX_QUEUE_SIZE));
-                    //             // ... (potentially add the current request to the new queue) ...
-                    //         }
-                            
-
-                    //         } else {
-                    //             // Ignore the request (queue is full)
-                    //         }
-                            
-                    //         // increment counter
-                    //         QUEUE_COUNTER.fetch_add(1, Ordering::Relaxed);
-                    //     }
-                        
-                        
-                    //     if HANDLER_STATE.load(Ordering::Relaxed) == HandlerState::Failed as usize {
-                    //         println!("Handler thread failed. Restarting..."); // Log the failure
-                    //         break; // Exit the stream-loop to signal a restart 
-                    //     }
-                    // }
-
-                    // (maybe too old of a text comment block)4. Respond
-                    /*
-                    Check for Valid Response Data: We first check if the response_status, response_headers, 
-                    and response_body fields in the RequestUnit have been populated.
-                
-                    Establish Connection: We use TcpStream::connect to establish a new connection 
-                    to the client using the stream_addr stored in the RequestUnit.
-                
-                    Send Status Line: We construct the HTTP status line (e.g., "HTTP/1.1 200 OK\r\n") 
-                    and send it through the stream.
-                
-                    Send Headers: We iterate through the response_headers and send each 
-                    header line (e.g., "Content-Type: text/plain\r\n").
-                
-                    Send Empty Line: We send an empty line ("\r\n") to indicate the end of the headers.
-                
-                    Send Response Body: Finally, we send the response_body through the stream.
-                    */
-                    // 4?
-                    // Receive the processed RequestUnit or error from the channel
-                    if let Ok((request_id, result)) = receiver.recv() {
-                        // Find the corresponding stream using the request ID
-                        if let Some(mut stream) = stream_map.remove(&request_id) {
-                            // Handle the result from the handler
-                            match result {
-                                Ok(processed_request) => {
-                                    // Send successful response
-                                    let response = format!(
-                                        "HTTP/1.1 {} OK\r\nContent-Type: text/plain\r\n\r\n{}",
-                                        processed_request.response_status.unwrap_or(200), // Get status or default to 200
-                                        processed_request.response_body.unwrap_or_default() // Get body or default to empty
-                                    );
-                                    stream.write_all(response.as_bytes()).unwrap();
-                                    stream.flush().unwrap(); // Flush the stream to ensure data is sent
-                                }
-                                Err(error_message) => {
-                                    // Send error response
-                                    let response = format!("HTTP/1.1 500 Internal Server Error\r\n\r\n{}", error_message);
-                                    stream.write_all(response.as_bytes()).unwrap();
-                                    stream.flush().unwrap(); // Flush the stream
-                                }
-                            }
-                        } else {
-                            eprintln!("Stream not found for request ID: {}", request_id);
-                            // Handle the case where the stream is not found
-                        }
-                    } else {
-                        eprintln!("Error receiving data from handler thread.");
-                        // Handle the error appropriately
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let accept_outcome = accept_connection(
+                        stream,
+                        &mut admission_gate,
+                        disposable_handoff_queue.len() + reads_in_flight,
+                        &request_limits,
+                        &reader_sender,
+                    );
+                    if accept_outcome == AcceptOutcome::Reading {
+                        reads_in_flight += 1;
                     }
                 }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    // No connection waiting: publish the drop count, pause briefly before polling again
+                    flush_dropped_count(&mut admission_gate);
+                    thread::sleep(Duration::from_millis(server_config.request_handler_pause_ms));
+                }
                 Err(e) => {
-                    eprintln!("Error accepting connection: {}", e);
+                    log_event(LogLevel::Warn, "could not accept connection", &[("error", &e.to_string())]);
                 }
-                // look for restart-flag from failure and signal larger restart exit
+            }
+
+            // Requests the reader threads have finished reading join the queue
+            let queue_outcome = queue_read_requests(
+                &reader_receiver,
+                &mut reads_in_flight,
+                &mut disposable_handoff_queue,
+                &sender,
+            );
+            if queue_outcome == QueueOutcome::ResponderGone {
+                log_event(LogLevel::Error, "responder thread is gone, restarting", &[]);
+                break; // Exit the stream-loop to signal a restart
+            }
+
+            // Handler failed: replace it. The listener and the current queue are kept;
+            // the failed batch was already answered with 500s by the old handler.
+            if HANDLER_STATE.load(Ordering::Relaxed) == HandlerState::Failed as usize {
+                log_event(LogLevel::Error, "handler thread failed, spawning a new handler", &[]);
+                HANDLER_RESTART_COUNTER.fetch_add(1, Ordering::Relaxed);
+                handoff_sender = spawn_request_handler(&sender, process_request_with_deadline);
+            }
+
+            // Queue Handoff: the handler sets itself Idle again when it has drained the queue
+            let handoff_outcome = hand_off_queue_if_idle(
+                &mut disposable_handoff_queue,
+                &handoff_sender,
+                server_config.max_queue_size,
+            );
+            if handoff_outcome == HandoffOutcome::HandlerGone {
+                // Handler thread is gone without marking Failed: the requests were kept, replace it
+                log_event(LogLevel::Error, "handler thread is gone, spawning a new handler", &[]);
+                HANDLER_RESTART_COUNTER.fetch_add(1, Ordering::Relaxed);
+                handoff_sender = spawn_request_handler(&sender, process_request_with_deadline);
             }
         }
 
@@ -861,3 +1404,284 @@ fn main() {
         // The outer loop will restart, creating a fresh disposable_handoff_queue and listener
     } 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::mpsc::TryRecvError;
+
+    /// Connects a client that sends a full valid request, returns the server side
+    fn accepted_stream_with_request() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client
+            .write_all(b"POST /echo_input_data HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello")
+            .unwrap();
+        let (server_stream, _) = listener.accept().unwrap();
+        (server_stream, client)
+    }
+
+    /// Waits until the reader thread of one admitted connection reports, then queues what it read
+    fn queue_after_read(
+        reader_receiver: &Receiver<ReaderMessage>,
+        disposable_handoff_queue: &mut VecDeque<RequestUnit>,
+        sender: &Sender<ResponderMessage>,
+    ) {
+        // queue_read_requests counts into QUEUE_COUNTER, which the probe tests set and read
+        let _handler_state_guard = HANDLER_STATE_TEST_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut reads_in_flight = 1;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while reads_in_flight > 0 {
+            assert!(Instant::now() < deadline, "reader thread never reported");
+            let queue_outcome = queue_read_requests(reader_receiver, &mut reads_in_flight, disposable_handoff_queue, sender);
+            assert_eq!(queue_outcome, QueueOutcome::Queued);
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn queued_request_unit() -> RequestUnit {
+        RequestUnit {
+            id: usize::MAX,
+            endpoint_module_name: Some("echo_input_data".to_string()),
+            method: "POST".to_string(),
+            path: "/echo_input_data".to_string(),
+            query: None,
+            query_params: Vec::new(),
+            http_version: "HTTP/1.1".to_string(),
+            request_headers: Vec::new(),
+            body: String::new(),
+            stream_addr: "127.0.0.1:1".parse().unwrap(),
+            received_at: Instant::now(),
+            response_status: None,
+            response_headers: None,
+            response_body: None,
+        }
+    }
+
+    #[test]
+    fn dropped_connection_does_not_grow_queue_or_stream_map() {
+        let (_sender, receiver) = std::sync::mpsc::channel::<ResponderMessage>();
+        let (reader_sender, reader_receiver) = std::sync::mpsc::channel::<ReaderMessage>();
+        let mut admission_gate = AdmissionGate::new(1, Duration::ZERO);
+        let mut disposable_handoff_queue = VecDeque::from(vec![queued_request_unit()]);
+
+        let (server_stream, _client) = accepted_stream_with_request();
+        let accept_outcome = accept_connection(
+            server_stream,
+            &mut admission_gate,
+            disposable_handoff_queue.len(),
+            &HttpRequestLimits::default(),
+            &reader_sender,
+        );
+
+        assert_eq!(accept_outcome, AcceptOutcome::Dropped);
+        assert_eq!(disposable_handoff_queue.len(), 1);
+        assert!(matches!(reader_receiver.try_recv(), Err(TryRecvError::Empty)));
+        assert!(matches!(receiver.try_recv(), Err(TryRecvError::Empty)));
+
+        // A connection still being read takes a queue slot too
+        disposable_handoff_queue.clear();
+        let (server_stream, _client) = accepted_stream_with_request();
+        let accept_outcome = accept_connection(
+            server_stream, &mut admission_gate, 1, &HttpRequestLimits::default(), &reader_sender,
+        );
+        assert_eq!(accept_outcome, AcceptOutcome::Dropped);
+    }
+
+    #[test]
+    fn cool_off_drops_with_empty_queue_then_admits() {
+        let (sender, receiver) = std::sync::mpsc::channel::<ResponderMessage>();
+        let (reader_sender, reader_receiver) = std::sync::mpsc::channel::<ReaderMessage>();
+        let mut admission_gate = AdmissionGate::new(1, Duration::from_millis(100));
+        let mut disposable_handoff_queue = VecDeque::from(vec![queued_request_unit()]);
+        let request_limits = HttpRequestLimits::default();
+
+        // full queue starts the cool-off
+        let (server_stream, _client) = accepted_stream_with_request();
+        let accept_outcome = accept_connection(
+            server_stream, &mut admission_gate, disposable_handoff_queue.len(), &request_limits, &reader_sender,
+        );
+        assert_eq!(accept_outcome, AcceptOutcome::Dropped);
+
+        // queue handed off, but still cooling off: dropped unread
+        disposable_handoff_queue.clear();
+        let (server_stream, _client) = accepted_stream_with_request();
+        let accept_outcome = accept_connection(
+            server_stream, &mut admission_gate, disposable_handoff_queue.len(), &request_limits, &reader_sender,
+        );
+        assert_eq!(accept_outcome, AcceptOutcome::Dropped);
+        assert!(disposable_handoff_queue.is_empty());
+        assert!(matches!(reader_receiver.try_recv(), Err(TryRecvError::Empty)));
+        assert!(matches!(receiver.try_recv(), Err(TryRecvError::Empty)));
+
+        // window over: admitted, registered once, queued once
+        thread::sleep(Duration::from_millis(120));
+        let (server_stream, _client) = accepted_stream_with_request();
+        let accept_outcome = accept_connection(
+            server_stream, &mut admission_gate, disposable_handoff_queue.len(), &request_limits, &reader_sender,
+        );
+        assert_eq!(accept_outcome, AcceptOutcome::Reading);
+        queue_after_read(&reader_receiver, &mut disposable_handoff_queue, &sender);
+        assert_eq!(disposable_handoff_queue.len(), 1);
+        let Ok(ResponderMessage::RegisterStream(request_id, _)) = receiver.try_recv() else {
+            panic!("stream was not registered");
+        };
+        assert_eq!(request_id, disposable_handoff_queue[0].id);
+        assert!(matches!(receiver.try_recv(), Err(TryRecvError::Empty)));
+    }
+
+    /// Test module: panics on the body "panic", answers 200 otherwise
+    fn panic_on_request(mut request_unit: RequestUnit) -> Result<RequestUnit, String> {
+        if request_unit.body == "panic" {
+            panic!("test module panic");
+        }
+        request_unit.response_status = Some(200);
+        Ok(request_unit)
+    }
+
+    fn request_unit_with(id: usize, body: &str) -> RequestUnit {
+        RequestUnit { id, body: body.to_string(), ..queued_request_unit() }
+    }
+
+    fn wait_for_handler_state(handler_state: HandlerState) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while HANDLER_STATE.load(Ordering::Relaxed) != handler_state as usize {
+            assert!(Instant::now() < deadline, "handler never reached {:?}", handler_state);
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn next_completed(receiver: &Receiver<ResponderMessage>) -> HandlerResultMessage {
+        match receiver.recv_timeout(Duration::from_secs(5)) {
+            Ok(ResponderMessage::Completed(handler_result_message)) => *handler_result_message,
+            Ok(ResponderMessage::RegisterStream(request_id, _)) => panic!("unexpected stream registration {}", request_id),
+            Err(e) => panic!("no result from the handler: {}", e),
+        }
+    }
+
+    // HANDLER_STATE is process-wide, so the whole handoff/failure cycle is one test
+    #[test]
+    fn handler_panic_fails_batch_then_replacement_serves_next_request() {
+        let _handler_state_guard = HANDLER_STATE_TEST_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (sender, receiver) = std::sync::mpsc::channel::<ResponderMessage>();
+        let mut handoff_sender = spawn_request_handler(&sender, panic_on_request);
+
+        // Busy handler: the queue stays with the stream-loop
+        let mut disposable_handoff_queue = VecDeque::from(vec![request_unit_with(1, "panic"), request_unit_with(2, "fine")]);
+        HANDLER_STATE.store(HandlerState::Busy as usize, Ordering::Relaxed);
+        assert_eq!(hand_off_queue_if_idle(&mut disposable_handoff_queue, &handoff_sender, 4), HandoffOutcome::NotHandedOff);
+        assert_eq!(disposable_handoff_queue.len(), 2);
+
+        // Idle handler: claimed (Idle -> Busy) and given the whole queue
+        HANDLER_STATE.store(HandlerState::Idle as usize, Ordering::Relaxed);
+        assert_eq!(hand_off_queue_if_idle(&mut disposable_handoff_queue, &handoff_sender, 4), HandoffOutcome::HandedOff);
+        assert!(disposable_handoff_queue.is_empty());
+
+        // The panic fails the in-flight request and the rest of the batch, then the handler is Failed
+        for expected_request_id in [1, 2] {
+            let (request_id, result) = next_completed(&receiver);
+            assert_eq!(request_id, expected_request_id);
+            assert!(result.is_err());
+        }
+        wait_for_handler_state(HandlerState::Failed);
+        assert_eq!(HANDLER_BACKLOG_COUNTER.load(Ordering::Relaxed), 0);
+
+        // Failed handler: nothing is handed off
+        disposable_handoff_queue.push_back(request_unit_with(3, "fine"));
+        assert_eq!(hand_off_queue_if_idle(&mut disposable_handoff_queue, &handoff_sender, 4), HandoffOutcome::NotHandedOff);
+
+        // A handler thread that is gone: the queue is kept for the replacement
+        HANDLER_STATE.store(HandlerState::Idle as usize, Ordering::Relaxed);
+        assert_eq!(hand_off_queue_if_idle(&mut disposable_handoff_queue, &handoff_sender, 4), HandoffOutcome::HandlerGone);
+        assert_eq!(disposable_handoff_queue.len(), 1);
+
+        // The replacement serves the next request
+        handoff_sender = spawn_request_handler(&sender, panic_on_request);
+        assert_eq!(hand_off_queue_if_idle(&mut disposable_handoff_queue, &handoff_sender, 4), HandoffOutcome::HandedOff);
+        let (request_id, result) = next_completed(&receiver);
+        assert_eq!(request_id, 3);
+        assert_eq!(result.unwrap().response_status, Some(200));
+        wait_for_handler_state(HandlerState::Idle);
+        assert_eq!(HANDLER_BACKLOG_COUNTER.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn handler_without_responder_stops_as_failed() {
+        let _handler_state_guard = HANDLER_STATE_TEST_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (sender, receiver) = std::sync::mpsc::channel::<ResponderMessage>();
+        let handoff_sender = spawn_request_handler(&sender, panic_on_request);
+        drop(receiver);
+
+        let mut disposable_handoff_queue = VecDeque::from(vec![request_unit_with(1, "fine"), request_unit_with(2, "fine")]);
+        HANDLER_STATE.store(HandlerState::Idle as usize, Ordering::Relaxed);
+        assert_eq!(hand_off_queue_if_idle(&mut disposable_handoff_queue, &handoff_sender, 4), HandoffOutcome::HandedOff);
+
+        // The first result cannot be sent: the rest of the batch is dropped and the handler is Failed
+        wait_for_handler_state(HandlerState::Failed);
+        assert_eq!(HANDLER_BACKLOG_COUNTER.load(Ordering::Relaxed), 0);
+        HANDLER_STATE.store(HandlerState::Idle as usize, Ordering::Relaxed);
+        disposable_handoff_queue.push_back(request_unit_with(3, "fine"));
+        assert_eq!(hand_off_queue_if_idle(&mut disposable_handoff_queue, &handoff_sender, 4), HandoffOutcome::HandlerGone);
+    }
+
+    #[test]
+    fn get_echo_returns_the_decoded_query_parameter() {
+        let (sender, receiver) = std::sync::mpsc::channel::<ResponderMessage>();
+        let (reader_sender, reader_receiver) = std::sync::mpsc::channel::<ReaderMessage>();
+        let mut admission_gate = AdmissionGate::new(4, Duration::ZERO);
+        let mut disposable_handoff_queue = VecDeque::new();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client
+            .write_all(b"GET /echo_input_data?other=1&input_string=hello+w%C3%B6rld%20%26%20more&input_string=second HTTP/1.1\r\n\r\n")
+            .unwrap();
+        let (server_stream, _) = listener.accept().unwrap();
+        let accept_outcome = accept_connection(
+            server_stream, &mut admission_gate, disposable_handoff_queue.len(), &HttpRequestLimits::default(), &reader_sender,
+        );
+        assert_eq!(accept_outcome, AcceptOutcome::Reading);
+        queue_after_read(&reader_receiver, &mut disposable_handoff_queue, &sender);
+        assert!(matches!(receiver.try_recv(), Ok(ResponderMessage::RegisterStream(_, _))));
+
+        let request_unit = disposable_handoff_queue.pop_front().unwrap();
+        assert_eq!(request_unit.query_parameter("input_string"), Some("hello wörld & more"));
+        let processed_request = process_request_with_module(request_unit).unwrap();
+        assert_eq!(processed_request.response_status, Some(200));
+        let response_body = processed_request.response_body.unwrap();
+        assert!(response_body.contains("\"hello wörld & more\""), "{}", response_body);
+    }
+
+    #[test]
+    fn direct_answers_get_access_log_records_from_the_response() {
+        let client: std::net::SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let http_request = HttpRequest {
+            method: "GET".to_string(),
+            path: "/nowhere".to_string(),
+            query: Some("a=1".to_string()),
+            version: "HTTP/1.1".to_string(),
+            headers: vec![("user-agent".to_string(), "curl/8.0".to_string())],
+            body: Vec::new(),
+        };
+        let response = build_error_response(404, "no endpoint at this path", &[]);
+        let access_log_record = direct_answer_access_log_record(client, Some(&http_request), &response);
+        assert_eq!(access_log_record.request_id, None);
+        assert_eq!(access_log_record.endpoint, None);
+        assert_eq!(access_log_record.status, 404);
+        assert_eq!(access_log_record.bytes, build_error_body(404, "no endpoint at this path").len());
+        assert_eq!((access_log_record.path.as_str(), access_log_record.query.as_deref()), ("/nowhere", Some("a=1")));
+        assert_eq!(access_log_record.user_agent.as_deref(), Some("curl/8.0"));
+
+        // Unreadable request: only the client and the response are known
+        let response = build_error_response(431, "headers too large", &[]);
+        let access_log_record = direct_answer_access_log_record(client, None, &response);
+        assert_eq!((access_log_record.status, access_log_record.method.as_str()), (431, ""));
+
+        // Probes go through the same path
+        let response = probe_response("GET", "/healthz").unwrap();
+        let access_log_record = direct_answer_access_log_record(client, Some(&http_request), &response);
+        assert_eq!(access_log_record.status, 200);
+        assert_eq!(access_log_record.bytes, response.split_once("\r\n\r\n").unwrap().1.len());
+    }
+}
@@ -0,0 +1,291 @@
+/*
+Counters and latency histograms, exposed at GET /metrics (Prometheus text format)
+
+    fiddler_crab_connections_accepted_total   admitted by the admission gate
+    fiddler_crab_connections_dropped_total    dropped unread for overload
+    fiddler_crab_requests_queued_total        given a request id and queued
+    fiddler_crab_requests_completed_total     answered by their module (status below 500)
+    fiddler_crab_requests_failed_total        module error, panic or timeout (500/504)
+    fiddler_crab_handler_restarts_total       Failed or vanished handlers replaced
+    fiddler_crab_access_log_lines_dropped_total  access-log records dropped (writer behind)
+    fiddler_crab_queue_depth                  requests waiting in the current queue
+    fiddler_crab_handler_backlog              handed-off requests the handler has not finished
+    fiddler_crab_request_duration_seconds     per-endpoint processing time (histogram)
+
+Everything is a plain atomic (no locks, no allocation to count), and every
+update happens off the drop path: drops are counted in the admission gate
+and flushed here while the stream-loop is idle, so shedding load costs no
+more than it did before metrics existed.
+
+Histograms have fixed buckets and one set of atomics per registered
+endpoint, built from the endpoint registry on first use. The duration is
+the module's processing time under the watchdog, not time spent queued.
+*/
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::endpoint_modules::endpoint_registry;
+use crate::{HANDLER_BACKLOG_COUNTER, QUEUE_COUNTER};
+
+/// Connections admitted by the admission gate (read, then queued or answered directly)
+pub static ACCEPTED_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Connections dropped by the admission gate, flushed from the gate's own count while the stream-loop is idle
+pub static DROPPED_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Requests the handler finished with a status below 500
+pub static COMPLETED_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Requests that ended in a module error, a panic or a timeout
+pub static FAILED_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Handlers replaced after failing (or disappearing)
+pub static HANDLER_RESTART_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Access-log records dropped because the writer thread was behind
+pub static ACCESS_LOG_DROPPED_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Upper bounds of the latency buckets, in milliseconds (+Inf is implied)
+const LATENCY_BUCKET_BOUNDS_MS: [u64; 14] = [
+    5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000, 30000, 60000, 300000,
+];
+
+/// Fixed-bucket processing-time histogram for one endpoint
+struct LatencyHistogram {
+    endpoint_name: &'static str,
+    /// Per-bucket (not cumulative) counts; the last slot is +Inf
+    bucket_counts: [AtomicUsize; LATENCY_BUCKET_BOUNDS_MS.len() + 1],
+    /// Sum of all observations, in microseconds
+    sum_micros: AtomicU64,
+    count: AtomicUsize,
+}
+
+impl LatencyHistogram {
+    fn new(endpoint_name: &'static str) -> Self {
+        LatencyHistogram {
+            endpoint_name,
+            bucket_counts: std::array::from_fn(|_| AtomicUsize::new(0)),
+            sum_micros: AtomicU64::new(0),
+            count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Counts `elapsed` in the first bucket whose bound it does not exceed
+    ///
+    /// Compared in microseconds: whole milliseconds would truncate, so e.g.
+    /// 5.9 ms would land in the 5 ms bucket.
+    fn observe(&self, elapsed: Duration) {
+        let elapsed_micros = elapsed.as_micros();
+        let bucket_index = LATENCY_BUCKET_BOUNDS_MS
+            .iter()
+            .position(|&bound_ms| elapsed_micros <= bound_ms as u128 * 1000)
+            .unwrap_or(LATENCY_BUCKET_BOUNDS_MS.len());
+        self.bucket_counts[bucket_index].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(elapsed_micros as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Appends the _bucket (cumulative), _sum and _count lines
+    fn write_exposition(&self, metric_name: &str, metrics_text: &mut String) {
+        let mut cumulative_count = 0;
+        for (bucket_index, bucket_count) in self.bucket_counts.iter().enumerate() {
+            cumulative_count += bucket_count.load(Ordering::Relaxed);
+            let upper_bound = match LATENCY_BUCKET_BOUNDS_MS.get(bucket_index) {
+                Some(&bound_ms) => (bound_ms as f64 / 1000.0).to_string(),
+                None => "+Inf".to_string(),
+            };
+            let _ = writeln!(
+                metrics_text,
+                "{}_bucket{{endpoint=\"{}\",le=\"{}\"}} {}",
+                metric_name, self.endpoint_name, upper_bound, cumulative_count
+            );
+        }
+        let sum_seconds = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(metrics_text, "{}_sum{{endpoint=\"{}\"}} {}", metric_name, self.endpoint_name, sum_seconds);
+        let _ = writeln!(
+            metrics_text,
+            "{}_count{{endpoint=\"{}\"}} {}",
+            metric_name,
+            self.endpoint_name,
+            self.count.load(Ordering::Relaxed)
+        );
+    }
+}
+
+/// One histogram per registered endpoint, in registry order
+static LATENCY_HISTOGRAMS: OnceLock<Vec<LatencyHistogram>> = OnceLock::new();
+
+fn latency_histograms() -> &'static [LatencyHistogram] {
+    LATENCY_HISTOGRAMS.get_or_init(|| {
+        endpoint_registry()
+            .iter()
+            .map(|registered_endpoint| LatencyHistogram::new(registered_endpoint.name))
+            .collect()
+    })
+}
+
+/// Records one request the handler has finished
+///
+/// # Arguments
+/// * `endpoint_name` - The endpoint that processed it
+/// * `elapsed` - Processing time (watchdog included, queue wait excluded)
+/// * `failed` - Module error, panic or timeout, i.e. answered with a 5xx
+pub fn record_finished_request(endpoint_name: &str, elapsed: Duration, failed: bool) {
+    if failed {
+        FAILED_COUNTER.fetch_add(1, Ordering::Relaxed);
+    } else {
+        COMPLETED_COUNTER.fetch_add(1, Ordering::Relaxed);
+    }
+    if let Some(latency_histogram) = latency_histograms()
+        .iter()
+        .find(|latency_histogram| latency_histogram.endpoint_name == endpoint_name)
+    {
+        latency_histogram.observe(elapsed);
+    }
+}
+
+/// Appends one counter or gauge with its HELP and TYPE lines
+fn write_metric(metrics_text: &mut String, metric_name: &str, metric_type: &str, help: &str, value: usize) {
+    let _ = writeln!(metrics_text, "# HELP {} {}", metric_name, help);
+    let _ = writeln!(metrics_text, "# TYPE {} {}", metric_name, metric_type);
+    let _ = writeln!(metrics_text, "{} {}", metric_name, value);
+}
+
+/// Renders every metric in Prometheus text exposition format (version 0.0.4)
+///
+/// # Returns
+/// * `String` - The /metrics response body
+pub fn metrics_text() -> String {
+    let mut metrics_text = String::new();
+    let counters = [
+        ("fiddler_crab_connections_accepted_total", "Connections admitted by the admission gate.", &ACCEPTED_COUNTER),
+        ("fiddler_crab_connections_dropped_total", "Connections dropped unread because the queue was full.", &DROPPED_COUNTER),
+        ("fiddler_crab_requests_queued_total", "Requests given an id and queued for the handler.", &crate::REQUEST_ID_COUNTER),
+        ("fiddler_crab_requests_completed_total", "Requests answered by their endpoint module with a status below 500.", &COMPLETED_COUNTER),
+        ("fiddler_crab_requests_failed_total", "Requests that ended in a module error, panic or timeout.", &FAILED_COUNTER),
+        ("fiddler_crab_handler_restarts_total", "Request handlers replaced after failing.", &HANDLER_RESTART_COUNTER),
+        ("fiddler_crab_access_log_lines_dropped_total", "Access-log records dropped because the writer was behind.", &ACCESS_LOG_DROPPED_COUNTER),
+    ];
+    for (metric_name, help, counter) in counters {
+        write_metric(&mut metrics_text, metric_name, "counter", help, counter.load(Ordering::Relaxed));
+    }
+    write_metric(
+        &mut metrics_text,
+        "fiddler_crab_queue_depth",
+        "gauge",
+        "Requests waiting in the current queue.",
+        QUEUE_COUNTER.load(Ordering::Relaxed),
+    );
+    write_metric(
+        &mut metrics_text,
+        "fiddler_crab_handler_backlog",
+        "gauge",
+        "Handed-off requests the handler has not finished.",
+        HANDLER_BACKLOG_COUNTER.load(Ordering::Relaxed),
+    );
+
+    let metric_name = "fiddler_crab_request_duration_seconds";
+    let _ = writeln!(metrics_text, "# HELP {} Endpoint module processing time.", metric_name);
+    let _ = writeln!(metrics_text, "# TYPE {} histogram", metric_name);
+    for latency_histogram in latency_histograms() {
+        latency_histogram.write_exposition(metric_name, &mut metrics_text);
+    }
+    metrics_text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let latency_histogram = LatencyHistogram::new("echo_input_data");
+        latency_histogram.observe(Duration::from_millis(3));
+        latency_histogram.observe(Duration::from_millis(40));
+        latency_histogram.observe(Duration::from_secs(600));
+
+        let mut metrics_text = String::new();
+        latency_histogram.write_exposition("latency", &mut metrics_text);
+
+        assert!(metrics_text.contains("latency_bucket{endpoint=\"echo_input_data\",le=\"0.005\"} 1\n"));
+        assert!(metrics_text.contains("latency_bucket{endpoint=\"echo_input_data\",le=\"0.05\"} 2\n"));
+        assert!(metrics_text.contains("latency_bucket{endpoint=\"echo_input_data\",le=\"300\"} 2\n"));
+        assert!(metrics_text.contains("latency_bucket{endpoint=\"echo_input_data\",le=\"+Inf\"} 3\n"));
+        assert!(metrics_text.contains("latency_sum{endpoint=\"echo_input_data\"} 600.043\n"));
+        assert!(metrics_text.contains("latency_count{endpoint=\"echo_input_data\"} 3\n"));
+    }
+
+    #[test]
+    fn observations_just_above_a_bound_go_to_the_next_bucket() {
+        let latency_histogram = LatencyHistogram::new("echo_input_data");
+        latency_histogram.observe(Duration::from_millis(5));
+        latency_histogram.observe(Duration::from_micros(5_900));
+        latency_histogram.observe(Duration::from_micros(10_001));
+
+        let mut metrics_text = String::new();
+        latency_histogram.write_exposition("latency", &mut metrics_text);
+
+        assert!(metrics_text.contains("latency_bucket{endpoint=\"echo_input_data\",le=\"0.005\"} 1\n"));
+        assert!(metrics_text.contains("latency_bucket{endpoint=\"echo_input_data\",le=\"0.01\"} 2\n"));
+        assert!(metrics_text.contains("latency_bucket{endpoint=\"echo_input_data\",le=\"0.025\"} 3\n"));
+        assert!(metrics_text.contains("latency_sum{endpoint=\"echo_input_data\"} 0.020901\n"));
+    }
+
+    #[test]
+    fn metrics_text_is_prometheus_exposition() {
+        // Handler tests record requests too: keep the histograms still while reading them
+        let _handler_state_guard = crate::HANDLER_STATE_TEST_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        record_finished_request("echo_input_data", Duration::from_micros(5_900), false);
+        record_finished_request("echo_input_data", Duration::from_secs(2), true);
+        let metrics_text = metrics_text();
+
+        // Every sample is preceded by the HELP and TYPE lines of its metric
+        let mut described_metric = "";
+        let mut lines = metrics_text.lines();
+        while let Some(line) = lines.next() {
+            if let Some(help) = line.strip_prefix("# HELP ") {
+                described_metric = help.split(' ').next().unwrap();
+                let type_line = lines.next().unwrap();
+                assert!(type_line.starts_with(&format!("# TYPE {} ", described_metric)), "{}", type_line);
+                let metric_type = type_line.rsplit(' ').next().unwrap();
+                assert!(["counter", "gauge", "histogram"].contains(&metric_type), "{}", type_line);
+                continue;
+            }
+            let sample_name = line.split(['{', ' ']).next().unwrap();
+            assert!(
+                sample_name == described_metric || sample_name.strip_prefix(described_metric).is_some_and(|suffix| ["_bucket", "_sum", "_count"].contains(&suffix)),
+                "sample {} under metric {}",
+                sample_name,
+                described_metric
+            );
+            assert!(line.rsplit(' ').next().unwrap().parse::<f64>().is_ok(), "{}", line);
+        }
+        assert!(metrics_text.contains("# TYPE fiddler_crab_requests_failed_total counter\n"));
+        assert!(metrics_text.contains("# TYPE fiddler_crab_queue_depth gauge\n"));
+        assert!(metrics_text.contains("# TYPE fiddler_crab_request_duration_seconds histogram\n"));
+
+        // Per endpoint: cumulative buckets in bound order, ending in +Inf equal to _count
+        for registered_endpoint in endpoint_registry() {
+            let bucket_prefix = format!("fiddler_crab_request_duration_seconds_bucket{{endpoint=\"{}\",le=\"", registered_endpoint.name);
+            let buckets: Vec<(&str, usize)> = metrics_text
+                .lines()
+                .filter_map(|line| line.strip_prefix(&bucket_prefix))
+                .map(|rest| {
+                    let (upper_bound, count) = rest.split_once("\"} ").unwrap();
+                    (upper_bound, count.parse().unwrap())
+                })
+                .collect();
+            assert_eq!(buckets.len(), LATENCY_BUCKET_BOUNDS_MS.len() + 1);
+            assert!(buckets.windows(2).all(|pair| pair[0].1 <= pair[1].1), "{:?}", buckets);
+            let (last_bound, inf_count) = buckets[buckets.len() - 1];
+            assert_eq!(last_bound, "+Inf");
+            let count_line = format!("fiddler_crab_request_duration_seconds_count{{endpoint=\"{}\"}} {}\n", registered_endpoint.name, inf_count);
+            assert!(metrics_text.contains(&count_line), "no {}", count_line);
+        }
+        let echo_inf_line = "fiddler_crab_request_duration_seconds_bucket{endpoint=\"echo_input_data\",le=\"+Inf\"} ";
+        let echo_inf_count: usize = metrics_text.lines().find_map(|line| line.strip_prefix(echo_inf_line)).unwrap().parse().unwrap();
+        assert!(echo_inf_count >= 2);
+    }
+}
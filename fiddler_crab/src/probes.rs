@@ -0,0 +1,209 @@
+/*
+Built-in probe and metrics endpoints, answered on the reader thread (no queue)
+
+    GET /healthz  -> 200 while the process is up (liveness)
+    GET /readyz   -> 200 if the handler is not Failed and the waiting requests
+                     (the queue plus what is left of the batch the handler
+                     is working through) are below readiness_queue_threshold,
+                     else 503 (readiness)
+    GET /status   -> 200 with counters: handler state, queued requests,
+                     handler backlog, total requests, dropped connections, uptime
+    GET /metrics  -> 200 with counters and latency histograms in
+                     Prometheus text format (see metrics.rs)
+
+These never enter the queue, never takes a request id and never waits
+for the handler, so it answers even while a long module call is running.
+Probes still pass the admission gate first: while the queue is full a
+probe is dropped unread like any other connection. For /readyz that is
+the right answer; give a liveness probe a failure threshold longer than
+queue_full_cool_off_ms.
+*/
+use std::sync::atomic::Ordering;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use crate::cli::version_text;
+use crate::config::server_config;
+use crate::http_response::{build_error_response, build_http_response};
+use crate::json::JsonValue;
+use crate::metrics::{metrics_text, DROPPED_COUNTER};
+use crate::{HandlerState, HANDLER_BACKLOG_COUNTER, HANDLER_STATE, QUEUE_COUNTER, REQUEST_ID_COUNTER};
+
+/// Paths the server answers itself (no endpoint module may use them)
+pub const PROBE_PATHS: &[&str] = &["/healthz", "/readyz", "/status", "/metrics"];
+
+/// When the server started, for uptime
+static SERVER_START: OnceLock<Instant> = OnceLock::new();
+
+/// Records the start time; call once at startup
+pub fn mark_server_start() {
+    SERVER_START.get_or_init(Instant::now);
+}
+
+/// Builds the response for a probe path, or None if `path` is not a probe
+///
+/// # Arguments
+/// * `method` - Request method (probes answer GET; anything else is a 405)
+/// * `path` - Request path, query already removed
+///
+/// # Returns
+/// * `Option<String>` - The full response text to write, or None for a normal request
+pub fn probe_response(method: &str, path: &str) -> Option<String> {
+    let path = match path.trim_end_matches('/') {
+        "" => "/",
+        trimmed => trimmed,
+    };
+    if !PROBE_PATHS.contains(&path) {
+        return None;
+    }
+    if method != "GET" {
+        let allow_header = ("Allow".to_string(), "GET".to_string());
+        return Some(build_error_response(405, "probe endpoints only answer GET", &[allow_header]));
+    }
+    if path == "/metrics" {
+        let headers = [("Content-Type".to_string(), "text/plain; version=0.0.4; charset=utf-8".to_string())];
+        return Some(build_http_response(200, &headers, &metrics_text()));
+    }
+
+    let (status, body) = match path {
+        "/healthz" => (200, JsonValue::object(vec![("status", JsonValue::String("ok".to_string()))])),
+        "/readyz" => readiness(),
+        _ => (200, status_body()),
+    };
+    let headers = [("Content-Type".to_string(), "application/json".to_string())];
+    Some(build_http_response(status, &headers, &body.to_json_string()))
+}
+
+/// 200 if new requests can be taken on, 503 with the reason otherwise
+///
+/// The queue counter resets to zero at every handoff, so a Busy handler's
+/// unfinished batch is counted too: a handler saturated by one big batch
+/// is not ready just because the fresh queue is still empty.
+fn readiness() -> (u16, JsonValue) {
+    let queued_requests = QUEUE_COUNTER.load(Ordering::Relaxed);
+    let handler_backlog = HANDLER_BACKLOG_COUNTER.load(Ordering::Relaxed);
+    let waiting_requests = queued_requests + handler_backlog;
+    let not_ready_reason = if HANDLER_STATE.load(Ordering::Relaxed) == HandlerState::Failed as usize {
+        Some("request handler failed".to_string())
+    } else if waiting_requests >= server_config().readiness_queue_limit() {
+        Some(format!(
+            "{} requests waiting ({} queued, {} in the handler's batch)",
+            waiting_requests, queued_requests, handler_backlog
+        ))
+    } else {
+        None
+    };
+    match not_ready_reason {
+        None => (200, JsonValue::object(vec![("status", JsonValue::String("ready".to_string()))])),
+        Some(reason) => (
+            503,
+            JsonValue::object(vec![
+                ("status", JsonValue::String("not ready".to_string())),
+                ("reason", JsonValue::String(reason)),
+            ]),
+        ),
+    }
+}
+
+/// The /status counters
+fn status_body() -> JsonValue {
+    let handler_state = match HANDLER_STATE.load(Ordering::Relaxed) {
+        state if state == HandlerState::Idle as usize => "idle",
+        state if state == HandlerState::Busy as usize => "busy",
+        _ => "failed",
+    };
+    let uptime_seconds = SERVER_START.get().map_or(0, |start| start.elapsed().as_secs());
+    JsonValue::object(vec![
+        ("version", JsonValue::String(version_text())),
+        ("handler_state", JsonValue::String(handler_state.to_string())),
+        ("queued_requests", JsonValue::Number(QUEUE_COUNTER.load(Ordering::Relaxed) as f64)),
+        ("handler_backlog", JsonValue::Number(HANDLER_BACKLOG_COUNTER.load(Ordering::Relaxed) as f64)),
+        ("max_queue_size", JsonValue::Number(server_config().max_queue_size as f64)),
+        ("total_requests", JsonValue::Number(REQUEST_ID_COUNTER.load(Ordering::Relaxed) as f64)),
+        ("dropped_connections", JsonValue::Number(DROPPED_COUNTER.load(Ordering::Relaxed) as f64)),
+        ("uptime_seconds", JsonValue::Number(uptime_seconds as f64)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::{parse_json, JsonLimits};
+    use crate::HANDLER_STATE_TEST_LOCK;
+
+    /// Splits a response into (status line, body)
+    fn status_line_and_body(response: &str) -> (&str, &str) {
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap(), body)
+    }
+
+    /// Sets the handler state and counters, returns the /readyz (status line, body)
+    fn readyz_with(handler_state: HandlerState, queued_requests: usize, handler_backlog: usize) -> (String, String) {
+        HANDLER_STATE.store(handler_state as usize, Ordering::Relaxed);
+        QUEUE_COUNTER.store(queued_requests, Ordering::Relaxed);
+        HANDLER_BACKLOG_COUNTER.store(handler_backlog, Ordering::Relaxed);
+        let response = probe_response("GET", "/readyz").unwrap();
+        let (status_line, body) = status_line_and_body(&response);
+        (status_line.to_string(), body.to_string())
+    }
+
+    #[test]
+    fn healthz_is_ok_and_other_paths_are_not_probes() {
+        let response = probe_response("GET", "/healthz/").unwrap();
+        assert_eq!(status_line_and_body(&response), ("HTTP/1.1 200 OK", "{\"status\":\"ok\"}"));
+
+        let response = probe_response("POST", "/healthz").unwrap();
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        assert!(response.contains("Allow: GET\r\n"));
+
+        assert!(probe_response("GET", "/healthzz").is_none());
+        assert!(probe_response("GET", "/echo_input_data").is_none());
+    }
+
+    #[test]
+    fn readyz_counts_the_queue_and_the_handler_backlog() {
+        let _handler_state_guard = HANDLER_STATE_TEST_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let readiness_queue_limit = server_config().readiness_queue_limit();
+
+        let (status_line, body) = readyz_with(HandlerState::Busy, readiness_queue_limit - 1, 0);
+        assert_eq!((status_line.as_str(), body.as_str()), ("HTTP/1.1 200 OK", "{\"status\":\"ready\"}"));
+
+        // Fresh queue is empty, but the handler still has a full batch to get through
+        let (status_line, body) = readyz_with(HandlerState::Busy, 0, readiness_queue_limit);
+        assert_eq!(status_line, "HTTP/1.1 503 Service Unavailable");
+        assert!(body.contains(&format!("{} requests waiting (0 queued, {} in the handler's batch)", readiness_queue_limit, readiness_queue_limit)), "{}", body);
+
+        let (status_line, _) = readyz_with(HandlerState::Busy, 2, readiness_queue_limit - 2);
+        assert_eq!(status_line, "HTTP/1.1 503 Service Unavailable");
+
+        let (status_line, body) = readyz_with(HandlerState::Failed, 0, 0);
+        assert_eq!(status_line, "HTTP/1.1 503 Service Unavailable");
+        assert_eq!(body, "{\"status\":\"not ready\",\"reason\":\"request handler failed\"}");
+
+        readyz_with(HandlerState::Idle, 0, 0);
+    }
+
+    #[test]
+    fn status_reports_state_and_counters() {
+        let _handler_state_guard = HANDLER_STATE_TEST_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        HANDLER_STATE.store(HandlerState::Busy as usize, Ordering::Relaxed);
+        QUEUE_COUNTER.store(3, Ordering::Relaxed);
+        HANDLER_BACKLOG_COUNTER.store(2, Ordering::Relaxed);
+        let response = probe_response("GET", "/status").unwrap();
+        HANDLER_STATE.store(HandlerState::Idle as usize, Ordering::Relaxed);
+        QUEUE_COUNTER.store(0, Ordering::Relaxed);
+        HANDLER_BACKLOG_COUNTER.store(0, Ordering::Relaxed);
+
+        let (status_line, body) = status_line_and_body(&response);
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        assert!(response.contains("Content-Type: application/json\r\n"));
+        let status = parse_json(body, &JsonLimits::default()).unwrap();
+        assert_eq!(status.get("version").and_then(JsonValue::as_str), Some(version_text().as_str()));
+        assert_eq!(status.get("handler_state").and_then(JsonValue::as_str), Some("busy"));
+        assert!(matches!(status.get("queued_requests"), Some(JsonValue::Number(queued)) if *queued == 3.0));
+        assert!(matches!(status.get("handler_backlog"), Some(JsonValue::Number(backlog)) if *backlog == 2.0));
+        for counter_name in ["max_queue_size", "total_requests", "dropped_connections", "uptime_seconds"] {
+            assert!(matches!(status.get(counter_name), Some(JsonValue::Number(_))), "{}", counter_name);
+        }
+    }
+}
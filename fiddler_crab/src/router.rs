@@ -0,0 +1,117 @@
+/*
+Path-based routing: request-line method + path -> endpoint module name
+
+Routes come from the endpoint registry (endpoint_modules::endpoint_registry:
+the compiled modules plus script endpoints from config): each entry
+declares its path and methods. A path is compared segment by segment after
+normalizing ("//" collapsed, trailing "/" ignored), so nested paths such as
+"/v1/echo_input_data" are just routes with more segments.
+
+No match at all -> 404
+Path matches but not the method -> 405 (with the methods that would match)
+*/
+use crate::endpoint_modules::RegisteredEndpoint;
+
+/// Result of looking up a request in the routing table
+#[derive(Debug, PartialEq)]
+pub enum RouteMatch {
+    /// Name of the endpoint module to run
+    Found(&'static str),
+    /// No route has this path
+    NotFound,
+    /// The path exists, but not for this method; holds the allowed methods
+    MethodNotAllowed(Vec<&'static str>),
+}
+
+/// Finds the endpoint module for a request method and path
+///
+/// # Arguments
+/// * `method` - Method from the request line, e.g. "POST"
+/// * `path` - Path from the request line (query already removed)
+/// * `registry` - The table to search, normally `endpoint_registry()`
+///
+/// # Returns
+/// * `RouteMatch` - Found(endpoint name), NotFound, or MethodNotAllowed(allowed methods)
+pub fn route_request(method: &str, path: &str, registry: &[RegisteredEndpoint]) -> RouteMatch {
+    let mut allowed_methods: Vec<&'static str> = Vec::new();
+
+    for registered_endpoint in registry {
+        if !paths_match(registered_endpoint.path, path) {
+            continue;
+        }
+        if registered_endpoint.methods.contains(&method) {
+            return RouteMatch::Found(registered_endpoint.name);
+        }
+        for allowed_method in registered_endpoint.methods {
+            if !allowed_methods.contains(allowed_method) {
+                allowed_methods.push(allowed_method);
+            }
+        }
+    }
+
+    if allowed_methods.is_empty() {
+        RouteMatch::NotFound
+    } else {
+        RouteMatch::MethodNotAllowed(allowed_methods)
+    }
+}
+
+/// Compares two paths segment by segment, ignoring empty segments
+///
+/// "/a/b", "/a/b/" and "//a//b" all match each other; "/a" does not match "/a/b".
+fn paths_match(route_path: &str, request_path: &str) -> bool {
+    let mut route_segments = route_path.split('/').filter(|segment| !segment.is_empty());
+    let mut request_segments = request_path.split('/').filter(|segment| !segment.is_empty());
+    loop {
+        match (route_segments.next(), request_segments.next()) {
+            (None, None) => return true,
+            (Some(route_segment), Some(request_segment)) if route_segment == request_segment => continue,
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RequestUnit;
+
+    fn unused_handler(request_unit: RequestUnit) -> Result<RequestUnit, String> {
+        Ok(request_unit)
+    }
+
+    const TEST_REGISTRY: &[RegisteredEndpoint] = &[
+        RegisteredEndpoint { name: "echo", path: "/echo", methods: &["GET", "POST"], handler: unused_handler, settings: &[] },
+        RegisteredEndpoint { name: "nested", path: "/v1/echo", methods: &["POST"], handler: unused_handler, settings: &[] },
+        RegisteredEndpoint { name: "nested_delete", path: "/v1/echo/", methods: &["DELETE", "POST"], handler: unused_handler, settings: &[] },
+    ];
+
+    #[test]
+    fn exact_and_trailing_slash_paths_match() {
+        assert_eq!(route_request("POST", "/echo", TEST_REGISTRY), RouteMatch::Found("echo"));
+        assert_eq!(route_request("GET", "/echo/", TEST_REGISTRY), RouteMatch::Found("echo"));
+        assert_eq!(route_request("GET", "//echo", TEST_REGISTRY), RouteMatch::Found("echo"));
+        assert_eq!(route_request("POST", "/v1/echo", TEST_REGISTRY), RouteMatch::Found("nested"));
+        assert_eq!(route_request("DELETE", "/v1/echo", TEST_REGISTRY), RouteMatch::Found("nested_delete"));
+    }
+
+    #[test]
+    fn unknown_paths_are_not_found() {
+        assert_eq!(route_request("POST", "/", TEST_REGISTRY), RouteMatch::NotFound);
+        assert_eq!(route_request("POST", "/echo/more", TEST_REGISTRY), RouteMatch::NotFound);
+        assert_eq!(route_request("POST", "/v1", TEST_REGISTRY), RouteMatch::NotFound);
+        assert_eq!(route_request("POST", "/Echo", TEST_REGISTRY), RouteMatch::NotFound);
+    }
+
+    #[test]
+    fn wrong_method_lists_the_allowed_methods_once() {
+        assert_eq!(
+            route_request("PUT", "/echo", TEST_REGISTRY),
+            RouteMatch::MethodNotAllowed(vec!["GET", "POST"])
+        );
+        assert_eq!(
+            route_request("GET", "/v1/echo", TEST_REGISTRY),
+            RouteMatch::MethodNotAllowed(vec!["POST", "DELETE"])
+        );
+    }
+}
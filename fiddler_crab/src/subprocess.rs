@@ -0,0 +1,522 @@
+/*
+Subprocess runner for endpoint modules that call external programs
+
+One place that runs a child process safely, so a missing binary, a
+flood of output or a hung program is a module error, never a panic:
+- program and argument list (typically from config)
+- optional bytes piped to stdin
+- stdout and stderr captured with size caps
+- optional wall-clock timeout
+- killed, with its whole process group, if the runner returns early or the
+  module thread unwinds (kill-on-drop); once the child exits, whatever it
+  left running in its group is killed too, so a background process holding
+  the output pipes cannot keep the runner waiting
+- registered with the watchdog, so a request timeout kills it too
+- non-zero exit or death by signal mapped to a SubprocessError
+- optional CPU-time and memory limits via setrlimit (Linux only)
+
+Per-endpoint settings read by SubprocessSpec::apply_endpoint_settings:
+
+    [endpoint.NAME]
+    args = "--ctx-size 2048 --threads 4"   # extra arguments, split on whitespace
+    timeout_ms = 60000
+    max_output_bytes = 1048576
+    cpu_time_limit_seconds = 120
+    memory_limit_bytes = 8589934592
+*/
+use std::fmt;
+use std::io::{Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::server_config;
+use crate::endpoint_modules::{EndpointSetting, SettingValue};
+use crate::watchdog::{kill_process, kill_process_group, unwatch_child_process, watch_child_process};
+
+/// Default cap on captured stdout, in bytes
+pub const DEFAULT_MAX_STDOUT_BYTES: usize = 4 * 1024 * 1024;
+
+/// Default cap on captured stderr, in bytes (the rest is discarded)
+pub const DEFAULT_MAX_STDERR_BYTES: usize = 64 * 1024;
+
+/// Keys apply_endpoint_settings reads, accepted by every endpoint that runs a subprocess
+pub const SUBPROCESS_ENDPOINT_SETTINGS: &[EndpointSetting] = &[
+    EndpointSetting { key: "args", value: SettingValue::Text },
+    EndpointSetting { key: "timeout_ms", value: SettingValue::NonZeroNumber },
+    EndpointSetting { key: "max_output_bytes", value: SettingValue::Number },
+    EndpointSetting { key: "cpu_time_limit_seconds", value: SettingValue::Number },
+    EndpointSetting { key: "memory_limit_bytes", value: SettingValue::Number },
+];
+
+/// How often the runner checks whether the child has exited
+const EXIT_POLL_INTERVAL_MS: u64 = 10;
+
+/// Size of each read from the child's pipes
+const PIPE_READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Most stderr quoted in an exit-failure error message
+const STDERR_EXCERPT_BYTES: usize = 512;
+
+/// What to run and under which limits
+#[derive(Debug, Clone)]
+pub struct SubprocessSpec {
+    /// Program path (or name looked up on PATH)
+    pub program: String,
+    /// Arguments, passed as-is (no shell)
+    pub args: Vec<String>,
+    /// Bytes written to the child's stdin, which is then closed (None = no stdin)
+    pub stdin_bytes: Option<Vec<u8>>,
+    /// Extra environment variables for the child (on top of the server's own)
+    pub env_vars: Vec<(String, String)>,
+    /// More stdout than this is an error (the child is killed)
+    pub max_stdout_bytes: usize,
+    /// Stderr beyond this is discarded
+    pub max_stderr_bytes: usize,
+    /// Wall-clock limit (None = rely on the request watchdog)
+    pub timeout: Option<Duration>,
+    /// RLIMIT_CPU in seconds (Linux only)
+    pub cpu_time_limit_seconds: Option<u64>,
+    /// RLIMIT_AS in bytes (Linux only)
+    pub memory_limit_bytes: Option<u64>,
+}
+
+impl SubprocessSpec {
+    /// A spec with default caps and no timeout or resource limits
+    pub fn new(program: &str, args: Vec<String>) -> Self {
+        SubprocessSpec {
+            program: program.to_string(),
+            args,
+            stdin_bytes: None,
+            env_vars: Vec::new(),
+            max_stdout_bytes: DEFAULT_MAX_STDOUT_BYTES,
+            max_stderr_bytes: DEFAULT_MAX_STDERR_BYTES,
+            timeout: None,
+            cpu_time_limit_seconds: None,
+            memory_limit_bytes: None,
+        }
+    }
+
+    /// Applies an endpoint's settings from config: extra args, timeout, output cap, rlimits
+    ///
+    /// # Arguments
+    /// * `endpoint_name` - Section name, e.g. "llamacpp" for [endpoint.llamacpp]
+    ///
+    /// # Returns
+    /// * `Result<Self, String>` - The updated spec, or Err naming a setting that is not a number
+    pub fn apply_endpoint_settings(mut self, endpoint_name: &str) -> Result<Self, String> {
+        let config = server_config();
+        if let Some(extra_args) = config.endpoint_setting(endpoint_name, "args") {
+            self.args.extend(extra_args.split_whitespace().map(str::to_string));
+        }
+        if let Some(timeout_ms) = endpoint_number_setting(endpoint_name, "timeout_ms")? {
+            self.timeout = Some(Duration::from_millis(timeout_ms));
+        }
+        if let Some(max_output_bytes) = endpoint_number_setting(endpoint_name, "max_output_bytes")? {
+            self.max_stdout_bytes = max_output_bytes as usize;
+        }
+        if let Some(cpu_time_limit_seconds) = endpoint_number_setting(endpoint_name, "cpu_time_limit_seconds")? {
+            self.cpu_time_limit_seconds = Some(cpu_time_limit_seconds);
+        }
+        if let Some(memory_limit_bytes) = endpoint_number_setting(endpoint_name, "memory_limit_bytes")? {
+            self.memory_limit_bytes = Some(memory_limit_bytes);
+        }
+        Ok(self)
+    }
+}
+
+/// What a successful run produced
+#[derive(Debug)]
+pub struct SubprocessOutput {
+    /// Everything the child wrote to stdout
+    pub stdout: Vec<u8>,
+    /// The first max_stderr_bytes of stderr
+    #[allow(dead_code)] // kept for modules that want diagnostics from a successful run
+    pub stderr: Vec<u8>,
+}
+
+impl SubprocessOutput {
+    /// Stdout as a String, replacing invalid UTF-8 sequences
+    pub fn stdout_as_string(&self) -> String {
+        String::from_utf8_lossy(&self.stdout).to_string()
+    }
+}
+
+/// Ways running a child process can fail
+#[derive(Debug)]
+pub enum SubprocessError {
+    /// The program could not be started (missing, not executable, ...)
+    SpawnFailed(String, std::io::Error),
+    /// Waiting on or talking to the child failed
+    Io(std::io::Error),
+    /// The wall-clock timeout passed; the child was killed
+    TimedOut(Duration),
+    /// Stdout exceeded max_stdout_bytes; the child was killed
+    OutputTooLarge(usize),
+    /// The request was abandoned by the watchdog before the child started
+    Abandoned,
+    /// Non-zero exit code, with the start of stderr
+    ExitCode(i32, String),
+    /// Killed by a signal (e.g. a resource limit), with the start of stderr
+    Signal(i32, String),
+}
+
+impl fmt::Display for SubprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubprocessError::SpawnFailed(program, e) => write!(f, "failed to start {}: {}", program, e),
+            SubprocessError::Io(e) => write!(f, "error talking to child process: {}", e),
+            SubprocessError::TimedOut(timeout) => write!(f, "child process timed out after {} ms", timeout.as_millis()),
+            SubprocessError::OutputTooLarge(max_bytes) => write!(f, "child process wrote more than {} bytes", max_bytes),
+            SubprocessError::Abandoned => write!(f, "request timed out before the child process started"),
+            SubprocessError::ExitCode(code, stderr) => write!(f, "child process exited with code {}: {}", code, stderr),
+            SubprocessError::Signal(signal, stderr) => write!(f, "child process killed by signal {}: {}", signal, stderr),
+        }
+    }
+}
+
+/// Kills and reaps the child unless it was already waited on
+struct ChildGuard {
+    child: Child,
+    reaped: bool,
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if !self.reaped {
+            // The whole process group, so grandchildren do not outlive the request
+            kill_process(self.child.id());
+            let _ = self.child.wait();
+        }
+        unwatch_child_process(self.child.id());
+    }
+}
+
+/// Runs a child process to completion under the spec's limits
+///
+/// # Arguments
+/// * `spec` - Program, arguments, stdin and limits
+///
+/// # Returns
+/// * `Result<SubprocessOutput, SubprocessError>` - Captured output on exit code 0, otherwise why not
+pub fn run_subprocess(spec: &SubprocessSpec) -> Result<SubprocessOutput, SubprocessError> {
+    let mut command = Command::new(&spec.program);
+    command
+        .args(&spec.args)
+        .envs(spec.env_vars.iter().map(|(name, value)| (name, value)))
+        .stdin(if spec.stdin_bytes.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    apply_resource_limits(&mut command, spec.cpu_time_limit_seconds, spec.memory_limit_bytes);
+    // Own process group, so a kill reaches everything the child started
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+    let child = command
+        .spawn()
+        .map_err(|e| SubprocessError::SpawnFailed(spec.program.clone(), e))?;
+    let mut child_guard = ChildGuard { child, reaped: false };
+    if !watch_child_process(child_guard.child.id()) {
+        return Err(SubprocessError::Abandoned);
+    }
+
+    // Feed stdin and drain both pipes on their own threads, so a full pipe never deadlocks the child
+    let stdin_writer = match (child_guard.child.stdin.take(), spec.stdin_bytes.clone()) {
+        (Some(mut child_stdin), Some(stdin_bytes)) => Some(thread::spawn(move || {
+            // A child that exits without reading all of stdin is not an error here
+            let _ = child_stdin.write_all(&stdin_bytes);
+        })),
+        _ => None,
+    };
+    let mut stdout_reader = child_guard
+        .child
+        .stdout
+        .take()
+        .map(|child_stdout| spawn_capped_reader(child_stdout, spec.max_stdout_bytes));
+    let stderr_reader = child_guard
+        .child
+        .stderr
+        .take()
+        .map(|child_stderr| spawn_capped_reader(child_stderr, spec.max_stderr_bytes));
+
+    // Wait for exit, up to the timeout; stop early if stdout overflows
+    let started = Instant::now();
+    let mut early_stdout: Option<Vec<u8>> = None;
+    let exit_status: ExitStatus = loop {
+        if stdout_reader.as_ref().is_some_and(|reader| reader.is_finished()) {
+            let (stdout, stdout_overflowed) = join_capped_reader(stdout_reader.take());
+            if stdout_overflowed {
+                // child_guard kills the child on return
+                return Err(SubprocessError::OutputTooLarge(spec.max_stdout_bytes));
+            }
+            early_stdout = Some(stdout);
+        }
+        match child_guard.child.try_wait() {
+            Ok(Some(exit_status)) => {
+                // Reaped: the pid may be reused now, so the watchdog must not kill it any more
+                child_guard.reaped = true;
+                unwatch_child_process(child_guard.child.id());
+                // Leftovers in the group (e.g. `sleep 600 &`) would hold the pipes open
+                // and keep the readers below from finishing
+                kill_process_group(child_guard.child.id());
+                break exit_status;
+            }
+            Ok(None) => {}
+            Err(e) => return Err(SubprocessError::Io(e)),
+        }
+        if let Some(timeout) = spec.timeout {
+            if started.elapsed() >= timeout {
+                return Err(SubprocessError::TimedOut(timeout));
+            }
+        }
+        thread::sleep(Duration::from_millis(EXIT_POLL_INTERVAL_MS));
+    };
+
+    // A process that left the group (setsid) can still hold the pipes: the readers get what is left of the timeout
+    let reader_deadline = spec.timeout.map(|timeout| started + timeout);
+    let (stdout, stdout_overflowed) = match early_stdout {
+        Some(stdout) => (stdout, false),
+        None => join_capped_reader_until(stdout_reader, reader_deadline, spec.timeout)?,
+    };
+    let (stderr, _) = join_capped_reader_until(stderr_reader, reader_deadline, spec.timeout)?;
+    if let Some(stdin_writer) = stdin_writer {
+        let _ = stdin_writer.join();
+    }
+
+    if stdout_overflowed {
+        return Err(SubprocessError::OutputTooLarge(spec.max_stdout_bytes));
+    }
+    if exit_status.success() {
+        return Ok(SubprocessOutput { stdout, stderr });
+    }
+    let stderr_excerpt = String::from_utf8_lossy(&stderr[..stderr.len().min(STDERR_EXCERPT_BYTES)])
+        .trim()
+        .to_string();
+    match exit_status.code() {
+        Some(code) => Err(SubprocessError::ExitCode(code, stderr_excerpt)),
+        None => Err(SubprocessError::Signal(exit_signal(&exit_status), stderr_excerpt)),
+    }
+}
+
+/// Reads a pipe to its end, keeping at most `max_bytes`
+///
+/// Returns (kept bytes, whether more than max_bytes arrived). On overflow
+/// it stops reading and closes the pipe, so a child still writing gets EPIPE.
+fn spawn_capped_reader<R: Read + Send + 'static>(
+    mut pipe: R,
+    max_bytes: usize,
+) -> thread::JoinHandle<(Vec<u8>, bool)> {
+    thread::spawn(move || {
+        let mut captured: Vec<u8> = Vec::new();
+        let mut chunk = [0u8; PIPE_READ_CHUNK_SIZE];
+        loop {
+            match pipe.read(&mut chunk) {
+                Ok(0) | Err(_) => return (captured, false),
+                Ok(bytes_read) => {
+                    if captured.len() + bytes_read > max_bytes {
+                        let room = max_bytes - captured.len();
+                        captured.extend_from_slice(&chunk[..room]);
+                        return (captured, true);
+                    }
+                    captured.extend_from_slice(&chunk[..bytes_read]);
+                }
+            }
+        }
+    })
+}
+
+/// Collects a capped reader's result (empty if there was no pipe or the thread failed)
+fn join_capped_reader(reader: Option<thread::JoinHandle<(Vec<u8>, bool)>>) -> (Vec<u8>, bool) {
+    reader
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default()
+}
+
+/// Collects a capped reader's result, waiting no later than `deadline` (None = no limit)
+///
+/// # Returns
+/// * `Result<(Vec<u8>, bool), SubprocessError>` - As join_capped_reader, or TimedOut
+///   if the pipe was still open at the deadline (the reader thread ends when it closes)
+fn join_capped_reader_until(
+    reader: Option<thread::JoinHandle<(Vec<u8>, bool)>>,
+    deadline: Option<Instant>,
+    timeout: Option<Duration>,
+) -> Result<(Vec<u8>, bool), SubprocessError> {
+    if let (Some(reader), Some(deadline), Some(timeout)) = (&reader, deadline, timeout) {
+        while !reader.is_finished() {
+            if Instant::now() >= deadline {
+                return Err(SubprocessError::TimedOut(timeout));
+            }
+            thread::sleep(Duration::from_millis(EXIT_POLL_INTERVAL_MS));
+        }
+    }
+    Ok(join_capped_reader(reader))
+}
+
+/// Reads a numeric endpoint setting
+fn endpoint_number_setting(endpoint_name: &str, key: &str) -> Result<Option<u64>, String> {
+    match server_config().endpoint_setting(endpoint_name, key) {
+        None => Ok(None),
+        Some(value) => value.parse::<u64>().map(Some).map_err(|_| {
+            format!("endpoint.{}.{} must be a whole number, got '{}'", endpoint_name, key, value)
+        }),
+    }
+}
+
+#[cfg(unix)]
+fn exit_signal(exit_status: &ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+    exit_status.signal().unwrap_or(0)
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_exit_status: &ExitStatus) -> i32 {
+    0
+}
+
+/// Sets RLIMIT_CPU / RLIMIT_AS in the child between fork and exec (Linux only)
+#[cfg(target_os = "linux")]
+fn apply_resource_limits(command: &mut Command, cpu_time_limit_seconds: Option<u64>, memory_limit_bytes: Option<u64>) {
+    use std::os::unix::process::CommandExt;
+
+    #[repr(C)]
+    struct ResourceLimit {
+        current: u64,
+        maximum: u64,
+    }
+    extern "C" {
+        fn setrlimit(resource: i32, limit: *const ResourceLimit) -> i32;
+    }
+    const RLIMIT_CPU: i32 = 0;
+    const RLIMIT_AS: i32 = 9;
+
+    if cpu_time_limit_seconds.is_none() && memory_limit_bytes.is_none() {
+        return;
+    }
+    let limits: Vec<(i32, u64)> = [(RLIMIT_CPU, cpu_time_limit_seconds), (RLIMIT_AS, memory_limit_bytes)]
+        .into_iter()
+        .filter_map(|(resource, value)| value.map(|value| (resource, value)))
+        .collect();
+    // SAFETY: the closure only calls setrlimit(2), which is async-signal-safe,
+    // on data prepared before fork; it allocates nothing.
+    unsafe {
+        command.pre_exec(move || {
+            for (resource, value) in &limits {
+                let limit = ResourceLimit { current: *value, maximum: *value };
+                if setrlimit(*resource, &limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn apply_resource_limits(_command: &mut Command, _cpu_time_limit_seconds: Option<u64>, _memory_limit_bytes: Option<u64>) {}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn shell_spec(script: &str) -> SubprocessSpec {
+        SubprocessSpec::new("sh", vec!["-c".to_string(), script.to_string()])
+    }
+
+    #[test]
+    fn exit_status_maps_to_output_or_error() {
+        let output = run_subprocess(&shell_spec("printf 'out'; printf 'note' >&2")).unwrap();
+        assert_eq!(output.stdout_as_string(), "out");
+        assert_eq!(output.stderr, b"note");
+
+        match run_subprocess(&shell_spec("echo 'bad input' >&2; exit 3")) {
+            Err(SubprocessError::ExitCode(3, stderr_excerpt)) => assert_eq!(stderr_excerpt, "bad input"),
+            other => panic!("expected exit code 3, got {:?}", other),
+        }
+        match run_subprocess(&shell_spec("kill -9 $$")) {
+            Err(SubprocessError::Signal(9, _)) => {}
+            other => panic!("expected signal 9, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_program_is_spawn_failed() {
+        let spec = SubprocessSpec::new("/nonexistent/fiddler_crab_tool", Vec::new());
+        match run_subprocess(&spec) {
+            Err(SubprocessError::SpawnFailed(program, _)) => assert_eq!(program, "/nonexistent/fiddler_crab_tool"),
+            other => panic!("expected SpawnFailed, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn stdin_is_piped_to_the_child() {
+        let mut spec = SubprocessSpec::new("cat", Vec::new());
+        spec.stdin_bytes = Some(b"hello".to_vec());
+        assert_eq!(run_subprocess(&spec).unwrap().stdout, b"hello");
+
+        // More than a pipe buffer each way: stdin and stdout are served concurrently
+        let large_input = vec![b'x'; 1024 * 1024];
+        spec.stdin_bytes = Some(large_input.clone());
+        assert_eq!(run_subprocess(&spec).unwrap().stdout, large_input);
+
+        // Without stdin_bytes the child reads an empty stdin instead of blocking
+        let spec = SubprocessSpec::new("cat", Vec::new());
+        assert!(run_subprocess(&spec).unwrap().stdout.is_empty());
+    }
+
+    #[test]
+    fn stdout_overflow_kills_the_child_and_stderr_is_truncated() {
+        let mut spec = shell_spec("yes");
+        spec.max_stdout_bytes = 1000;
+        let started = Instant::now();
+        match run_subprocess(&spec) {
+            Err(SubprocessError::OutputTooLarge(1000)) => {}
+            other => panic!("expected OutputTooLarge, got {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let mut spec = shell_spec("head -c 5000 /dev/zero >&2; printf done");
+        spec.max_stderr_bytes = 100;
+        let output = run_subprocess(&spec).unwrap();
+        assert_eq!(output.stdout_as_string(), "done");
+        assert_eq!(output.stderr.len(), 100);
+    }
+
+    #[test]
+    fn background_process_left_by_the_child_does_not_hold_the_runner() {
+        // The backgrounded sleep inherits stdout and stderr; only killing it closes them
+        let mut spec = shell_spec("sleep 30 & echo hi");
+        spec.timeout = Some(Duration::from_secs(2));
+        let started = Instant::now();
+        let output = run_subprocess(&spec).unwrap();
+        assert_eq!(output.stdout_as_string(), "hi\n");
+        assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+
+        // Out of the group's reach (setsid): the readers stop waiting at the timeout
+        let mut spec = shell_spec("setsid sleep 3 & echo hi");
+        spec.timeout = Some(Duration::from_millis(300));
+        let started = Instant::now();
+        match run_subprocess(&spec) {
+            Err(SubprocessError::TimedOut(_)) => {}
+            other => panic!("expected TimedOut, got {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn timeout_kills_the_child_and_its_process_group() {
+        let marker_path = std::env::temp_dir().join(format!("fiddler_crab_subprocess_test_{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker_path);
+        // The grandchild would write the marker if the kill did not reach the whole group
+        let mut spec = shell_spec(&format!("(sleep 1; touch '{}') & sleep 30", marker_path.display()));
+        spec.timeout = Some(Duration::from_millis(100));
+        let started = Instant::now();
+        match run_subprocess(&spec) {
+            Err(SubprocessError::TimedOut(timeout)) => assert_eq!(timeout, Duration::from_millis(100)),
+            other => panic!("expected TimedOut, got {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(1500));
+        assert!(!marker_path.exists());
+    }
+}
@@ -0,0 +1,103 @@
+/*
+Date and time formatting without a date library
+
+SystemTime is turned into a UTC calendar date with the days-to-civil
+conversion (proleptic Gregorian calendar), which is all the log and
+access-log lines need: no time zones, no parsing.
+*/
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A point in time broken down into UTC calendar fields
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UtcDateTime {
+    pub year: i64,
+    /// 1..=12
+    pub month: u32,
+    /// 1..=31
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub millisecond: u32,
+}
+
+/// Converts days since 1970-01-01 to (year, month, day)
+fn civil_from_days(days_since_epoch: i64) -> (i64, u32, u32) {
+    // Shift the epoch to 0000-03-01 so leap days fall at the end of each 400-year era
+    let days = days_since_epoch + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153; // 0 = March
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+impl UtcDateTime {
+    /// Breaks a SystemTime down into UTC fields (times before 1970 are clamped to the epoch)
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let total_seconds = since_epoch.as_secs() as i64;
+        let (year, month, day) = civil_from_days(total_seconds.div_euclid(86_400));
+        let second_of_day = total_seconds.rem_euclid(86_400) as u32;
+        UtcDateTime {
+            year,
+            month,
+            day,
+            hour: second_of_day / 3600,
+            minute: second_of_day % 3600 / 60,
+            second: second_of_day % 60,
+            millisecond: since_epoch.subsec_millis(),
+        }
+    }
+
+    /// The current time
+    pub fn now() -> Self {
+        Self::from_system_time(SystemTime::now())
+    }
+
+    /// Common Log Format date, e.g. "09/Mar/2024:14:05:07 +0000"
+    pub fn clf(&self) -> String {
+        const MONTH_NAMES: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+        format!(
+            "{:02}/{}/{:04}:{:02}:{:02}:{:02} +0000",
+            self.day,
+            MONTH_NAMES[self.month as usize - 1],
+            self.year,
+            self.hour,
+            self.minute,
+            self.second
+        )
+    }
+
+    /// RFC 3339 with milliseconds, e.g. "2024-03-09T14:05:07.250Z"
+    pub fn rfc3339(&self) -> String {
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, self.millisecond
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn formats_known_instants() {
+        let epoch = UtcDateTime::from_system_time(UNIX_EPOCH);
+        assert_eq!(epoch.rfc3339(), "1970-01-01T00:00:00.000Z");
+
+        // 2024-02-29 (a leap day) 23:59:59.999
+        let leap_day = UtcDateTime::from_system_time(UNIX_EPOCH + Duration::from_millis(1_709_251_199_999));
+        assert_eq!(leap_day.rfc3339(), "2024-02-29T23:59:59.999Z");
+
+        let next_day = UtcDateTime::from_system_time(UNIX_EPOCH + Duration::from_secs(1_709_251_200));
+        assert_eq!(next_day.rfc3339(), "2024-03-01T00:00:00.000Z");
+        assert_eq!(next_day.clf(), "01/Mar/2024:00:00:00 +0000");
+    }
+}
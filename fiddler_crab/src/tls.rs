@@ -0,0 +1,82 @@
+/*
+HTTPS termination (only built with the `tls` cargo feature)
+
+    cargo build --release --features tls
+
+Certificate chain and private key are PEM files named in the config
+(tls_cert_path, tls_key_path). They are loaded once at startup; each
+accepted connection then gets its own rustls ServerConnection.
+
+rustls takes its config as an Arc; it is created once and only ever
+read, so this is not shared mutable state.
+*/
+use std::net::TcpStream;
+use std::sync::{Arc, OnceLock};
+
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+
+/// A TLS session over an accepted TCP stream
+pub type TlsStream = StreamOwned<ServerConnection, TcpStream>;
+
+/// Set once at startup when HTTPS is configured
+static TLS_SERVER_CONFIG: OnceLock<Arc<ServerConfig>> = OnceLock::new();
+
+/// Loads the certificate chain and key and stores the TLS config
+///
+/// # Arguments
+/// * `cert_path` - PEM file with the certificate chain (leaf first)
+/// * `key_path` - PEM file with the private key (PKCS#8, PKCS#1 or SEC1)
+///
+/// # Returns
+/// * `Result<(), String>` - Err if a file cannot be read or the key does not fit the certificate
+pub fn install_tls_server_config(cert_path: &str, key_path: &str) -> Result<(), String> {
+    let cert_chain: Vec<CertificateDer<'static>> = CertificateDer::pem_file_iter(cert_path)
+        .map_err(|e| format!("cannot read tls_cert_path {}: {}", cert_path, e))?
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("invalid certificate in {}: {}", cert_path, e))?;
+    if cert_chain.is_empty() {
+        return Err(format!("no certificate found in {}", cert_path));
+    }
+    let private_key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| format!("cannot read private key from tls_key_path {}: {}", key_path, e))?;
+
+    let server_config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("TLS setup failed: {}", e))?
+        .with_no_client_auth()
+        .with_single_cert(cert_chain, private_key)
+        .map_err(|e| format!("certificate and key do not match: {}", e))?;
+
+    // A second call (e.g. --check-config then serving) keeps the first, identical config
+    let _ = TLS_SERVER_CONFIG.set(Arc::new(server_config));
+    Ok(())
+}
+
+/// True once install_tls_server_config has succeeded
+pub fn tls_is_enabled() -> bool {
+    TLS_SERVER_CONFIG.get().is_some()
+}
+
+/// Starts a server-side TLS session on an accepted stream (no I/O yet)
+pub fn accept_tls_stream(tcp_stream: TcpStream) -> Result<TlsStream, String> {
+    let server_config = TLS_SERVER_CONFIG
+        .get()
+        .ok_or_else(|| "TLS is not configured".to_string())?;
+    let server_connection = ServerConnection::new(Arc::clone(server_config))
+        .map_err(|e| format!("TLS session setup failed: {}", e))?;
+    Ok(StreamOwned::new(server_connection, tcp_stream))
+}
+
+/// Sends close_notify so the client sees a clean end of the response
+///
+/// Best effort and write-only: never waits on the client.
+pub fn close_tls_stream(tls_stream: &mut TlsStream) {
+    tls_stream.conn.send_close_notify();
+    while tls_stream.conn.wants_write() {
+        if tls_stream.conn.write_tls(&mut tls_stream.sock).is_err() {
+            break;
+        }
+    }
+}
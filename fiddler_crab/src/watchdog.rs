@@ -0,0 +1,230 @@
+/*
+Watchdog: a deadline for each endpoint module invocation
+
+The handler runs each module call in a disposable thread and waits on a
+channel with recv_timeout. If the deadline passes:
+- the child process the module registered (if any) is killed
+- the request is answered with 504 Gateway Timeout
+- the handler moves on to the next queued RequestUnit
+
+A module that hangs inside Rust code (not in a child process) cannot be
+stopped from outside; its thread is abandoned and ends whenever it ends.
+A module that panics only takes its own thread down.
+
+Child registration uses atomics only: the handler is serial, so at most
+one request is being watched at a time. The watched request and its child
+share one atomic slot, so a late child from an abandoned (timed-out)
+request can never overwrite or clear the registration of the request
+that is watched now.
+*/
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::time::Duration;
+
+use crate::RequestUnit;
+
+/// Marker for "no request is being watched"
+const NO_REQUEST: usize = usize::MAX;
+
+/// Slot value between requests (no request watched; u32::MAX is never a pid)
+const NOT_WATCHING: u64 = u64::MAX;
+
+/// Watched request and its child process, packed by watch_slot (NOT_WATCHING between requests)
+static WATCHED_CHILD: AtomicU64 = AtomicU64::new(NOT_WATCHING);
+
+/// Packs (request, child pid) into one slot value; pid 0 = no child registered
+///
+/// Only the low 32 bits of the request id are kept: an abandoned module
+/// thread would have to outlive four billion requests to be mistaken for
+/// the current one.
+fn watch_slot(request_id: usize, pid: u32) -> u64 {
+    ((request_id as u32 as u64) << 32) | pid as u64
+}
+
+thread_local! {
+    /// Request this module thread works for (NO_REQUEST outside module threads)
+    static CURRENT_REQUEST_ID: Cell<usize> = const { Cell::new(NO_REQUEST) };
+}
+
+/// How a watched module invocation ended
+pub enum WatchdogOutcome {
+    /// The module returned in time (with its own Ok or Err), boxed to keep the enum small
+    Finished(Box<Result<RequestUnit, String>>),
+    /// The deadline passed; any registered child process was killed
+    TimedOut,
+    /// The module thread ended without a result (it panicked)
+    Panicked,
+}
+
+/// Runs `work` in a disposable thread and waits at most `deadline` for it
+///
+/// # Arguments
+/// * `request_id` - The request being processed (for child process registration)
+/// * `deadline` - How long to wait for the module
+/// * `work` - The module invocation
+///
+/// # Returns
+/// * `WatchdogOutcome` - Finished, TimedOut or Panicked
+pub fn run_with_watchdog<F>(request_id: usize, deadline: Duration, work: F) -> WatchdogOutcome
+where
+    F: FnOnce() -> Result<RequestUnit, String> + Send + 'static,
+{
+    WATCHED_CHILD.store(watch_slot(request_id, 0), Ordering::SeqCst);
+
+    let (result_sender, result_receiver) = std::sync::mpsc::channel();
+    let spawn_result = thread::Builder::new()
+        .name(format!("module-request-{}", request_id))
+        .spawn(move || {
+            CURRENT_REQUEST_ID.with(|current_request_id| current_request_id.set(request_id));
+            // If the watchdog already gave up, nobody is listening: ignore the send error
+            let _ = result_sender.send(work());
+        });
+    if let Err(e) = spawn_result {
+        WATCHED_CHILD.store(NOT_WATCHING, Ordering::SeqCst);
+        return WatchdogOutcome::Finished(Box::new(Err(format!("Could not start module thread: {}", e))));
+    }
+
+    let outcome = match result_receiver.recv_timeout(deadline) {
+        Ok(result) => WatchdogOutcome::Finished(Box::new(result)),
+        Err(RecvTimeoutError::Timeout) => {
+            kill_watched_child_process(request_id);
+            WatchdogOutcome::TimedOut
+        }
+        Err(RecvTimeoutError::Disconnected) => WatchdogOutcome::Panicked,
+    };
+    WATCHED_CHILD.store(NOT_WATCHING, Ordering::SeqCst);
+    outcome
+}
+
+/// Registers a child process the current module started, so a timeout can kill it
+///
+/// Call right after spawning, and `unwatch_child_process` after waiting on it
+/// (one child per request at a time). The slot is only claimed if it still
+/// belongs to this request; if the request was already abandoned by the
+/// watchdog, the child is killed at once.
+///
+/// # Returns
+/// * `bool` - false if the child was killed because its request already timed out
+pub fn watch_child_process(pid: u32) -> bool {
+    let request_id = CURRENT_REQUEST_ID.with(|current_request_id| current_request_id.get());
+    if request_id == NO_REQUEST {
+        // Not running under the watchdog: nothing to register with
+        return true;
+    }
+    let registered = WATCHED_CHILD
+        .compare_exchange(watch_slot(request_id, 0), watch_slot(request_id, pid), Ordering::SeqCst, Ordering::SeqCst)
+        .is_ok();
+    if !registered {
+        kill_process(pid);
+    }
+    registered
+}
+
+/// Clears the registration once the child has been waited on
+pub fn unwatch_child_process(pid: u32) {
+    let request_id = CURRENT_REQUEST_ID.with(|current_request_id| current_request_id.get());
+    if request_id == NO_REQUEST {
+        return;
+    }
+    let _ = WATCHED_CHILD.compare_exchange(watch_slot(request_id, pid), watch_slot(request_id, 0), Ordering::SeqCst, Ordering::SeqCst);
+}
+
+/// Kills the child registered for a request that timed out, if there is one
+fn kill_watched_child_process(request_id: usize) {
+    let watched_child = WATCHED_CHILD.swap(NOT_WATCHING, Ordering::SeqCst);
+    let pid = watched_child as u32;
+    if watched_child != NOT_WATCHING && pid != 0 && watched_child == watch_slot(request_id, pid) {
+        kill_process(pid);
+    }
+}
+
+/// Sends SIGKILL to a process and to its process group (std can only kill a Child it owns)
+///
+/// Children started by the subprocess runner lead their own process group,
+/// so anything they spawned (e.g. a shell script's commands) dies with them.
+#[cfg(unix)]
+pub fn kill_process(pid: u32) {
+    extern "C" {
+        fn kill(pid: i32, signal: i32) -> i32;
+    }
+    const SIGKILL: i32 = 9;
+    // SAFETY: kill(2) takes plain integers and has no memory effects on this process.
+    // A negative pid addresses the group; it fails harmlessly if there is no such group.
+    unsafe {
+        kill(-(pid as i32), SIGKILL);
+        kill(pid as i32, SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn kill_process(_pid: u32) {}
+
+/// Sends SIGKILL to what is left of a process group whose leader was already reaped
+///
+/// Only the group is signalled, never the (possibly reused) pid itself; the
+/// group id cannot be reused while any member is alive.
+#[cfg(unix)]
+pub fn kill_process_group(pid: u32) {
+    extern "C" {
+        fn kill(pid: i32, signal: i32) -> i32;
+    }
+    const SIGKILL: i32 = 9;
+    // SAFETY: kill(2) takes plain integers; it fails harmlessly if the group is gone.
+    unsafe {
+        kill(-(pid as i32), SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn kill_process_group(_pid: u32) {}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use std::sync::mpsc::Sender;
+    use std::time::Instant;
+
+    /// Module work that starts `sleep 30`, registers it, and reports whether
+    /// it was registered and when it exited
+    fn start_and_wait_on_child(start_delay: Duration, report_sender: Sender<(bool, Instant)>) -> Result<RequestUnit, String> {
+        thread::sleep(start_delay);
+        let mut child = Command::new("sleep").arg("30").spawn().map_err(|e| e.to_string())?;
+        let registered = watch_child_process(child.id());
+        let _ = child.wait();
+        unwatch_child_process(child.id());
+        let _ = report_sender.send((registered, Instant::now()));
+        Err("child exited".to_string())
+    }
+
+    #[test]
+    fn late_child_of_abandoned_request_cannot_take_over_the_next_request() {
+        let (first_report_sender, first_report_receiver) = std::sync::mpsc::channel();
+        let (second_report_sender, second_report_receiver) = std::sync::mpsc::channel();
+
+        // Request 1 times out before its module starts a child
+        let outcome = run_with_watchdog(1, Duration::from_millis(50), move || {
+            start_and_wait_on_child(Duration::from_millis(300), first_report_sender)
+        });
+        assert!(matches!(outcome, WatchdogOutcome::TimedOut));
+
+        // Request 2 registers its child first; request 1's late child arrives while request 2 is watched
+        let second_request_start = Instant::now();
+        let outcome = run_with_watchdog(2, Duration::from_millis(800), move || {
+            start_and_wait_on_child(Duration::ZERO, second_report_sender)
+        });
+        assert!(matches!(outcome, WatchdogOutcome::TimedOut));
+
+        // The late child was refused and killed at once
+        let (first_registered, first_exited_at) = first_report_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(!first_registered);
+        assert!(first_exited_at < second_request_start + Duration::from_millis(800));
+
+        // Request 2's child was still registered, so its timeout killed it
+        let (second_registered, _) = second_report_receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(second_registered);
+        assert_eq!(WATCHED_CHILD.load(Ordering::SeqCst), NOT_WATCHING);
+    }
+}
@@ -0,0 +1,50 @@
+/*
+Helpers shared by the integration tests: start the real binary, wait for
+it to listen, and kill it when the test ends.
+*/
+#![allow(dead_code)] // each test crate uses a different subset
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::Child;
+use std::time::{Duration, Instant};
+
+/// Kills the server when the test ends, pass or fail
+pub struct ServerProcess(pub Child);
+
+impl Drop for ServerProcess {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// A port that was free a moment ago
+pub fn free_local_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+/// Waits until the server accepts connections
+pub fn wait_for_listener(port: u16) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while TcpStream::connect(("127.0.0.1", port)).is_err() {
+        assert!(Instant::now() < deadline, "server did not start listening");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Sends one plain HTTP POST with a JSON body and returns the whole response as text
+pub fn post_json(port: u16, path: &str, body: &str) -> String {
+    let mut tcp_stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    tcp_stream.set_read_timeout(Some(Duration::from_secs(20))).unwrap();
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        path,
+        body.len(),
+        body
+    );
+    tcp_stream.write_all(request.as_bytes()).unwrap();
+    let mut response = Vec::new();
+    tcp_stream.read_to_end(&mut response).unwrap();
+    String::from_utf8_lossy(&response).into_owned()
+}
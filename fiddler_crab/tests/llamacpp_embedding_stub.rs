@@ -0,0 +1,81 @@
+/*
+llamacpp_embedding against a stub llama-embedding (Unix: the stub is a shell script)
+
+The stub prints llama-embedding's `--embd-output-format json` shape with one
+deterministic vector per text: [text length, index, 0.25]. So the test
+checks argument passing, splitting on the separator, and vector order,
+without a model.
+*/
+#![cfg(unix)]
+
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use common::{free_local_port, post_json, wait_for_listener, ServerProcess};
+
+const STUB_LLAMA_EMBEDDING_SCRIPT: &str = r#"#!/bin/sh
+while [ $# -gt 0 ]; do
+  case "$1" in
+    -p) prompt="$2"; shift 2 ;;
+    --embd-separator) separator="$2"; shift 2 ;;
+    --embd-output-format) format="$2"; shift 2 ;;
+    *) shift ;;
+  esac
+done
+[ "$format" = json ] || { echo "expected --embd-output-format json" >&2; exit 1; }
+printf '{"object":"list","data":['
+index=0
+rest="$prompt"
+while :; do
+  case "$rest" in
+    *"$separator"*) text="${rest%%"$separator"*}"; rest="${rest#*"$separator"}" ;;
+    *) text="$rest"; last=1 ;;
+  esac
+  [ $index -gt 0 ] && printf ','
+  printf '{"object":"embedding","index":%d,"embedding":[%d,%d,0.25]}' $index ${#text} $index
+  index=$((index + 1))
+  [ -n "$last" ] && break
+done
+printf ']}\n'
+"#;
+
+#[test]
+fn embeds_each_text_in_order_with_stub_executable() {
+    let directory: PathBuf = std::env::temp_dir().join(format!("fiddler_crab_embedding_test_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let stub_path = directory.join("llama-embedding");
+    std::fs::write(&stub_path, STUB_LLAMA_EMBEDDING_SCRIPT).unwrap();
+    std::fs::set_permissions(&stub_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let port = free_local_port();
+    let _server = ServerProcess(
+        Command::new(env!("CARGO_BIN_EXE_fiddler_crab"))
+            .arg("--bind")
+            .arg(format!("127.0.0.1:{}", port))
+            .arg("--pace-ms")
+            .arg("0")
+            .arg(format!("--endpoint.llamacpp_embedding.binary_path={}", stub_path.display()))
+            .arg("--endpoint.llamacpp_embedding.model_path=/models/stub.gguf")
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+    wait_for_listener(port);
+
+    // The second text has a newline: it must stay one text, not become two
+    let response = post_json(port, "/llamacpp_embedding", r#"{"input":["hello","two\nlines"]}"#);
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "response: {}", response);
+    assert!(
+        response.contains(r#""output":{"dimensions":3,"embeddings":[[5,0,0.25],[9,1,0.25]]}"#),
+        "response: {}",
+        response
+    );
+
+    let response = post_json(port, "/llamacpp_embedding", r#"{"input":[]}"#);
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"), "response: {}", response);
+
+    let _ = std::fs::remove_dir_all(&directory);
+}
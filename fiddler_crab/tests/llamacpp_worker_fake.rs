@@ -0,0 +1,180 @@
+/*
+llamacpp server mode against a fake llama-server (Unix, needs python3)
+
+The fake worker is a small python HTTP server that speaks the two routes
+the module uses: GET /health (503 while "loading", then 200) and
+POST /completion, whose "content" names the worker's pid. The prompt
+"fail" gets a 500 from the fake and the prompt "die" makes it exit without
+answering. So the tests check lazy start, reuse across requests, error
+replies, and restart after the worker dies, without a model. Skipped
+(passes with a note) if python3 is missing.
+*/
+#![cfg(unix)]
+
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use common::{free_local_port, post_json, wait_for_listener, ServerProcess};
+
+const FAKE_LLAMA_SERVER_SCRIPT: &str = r#"#!/usr/bin/env python3
+import json, os, sys, time
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+port = int(sys.argv[sys.argv.index("--port") + 1])
+started = time.time()
+
+class FakeLlamaServer(BaseHTTPRequestHandler):
+    def reply(self, status, body):
+        data = json.dumps(body).encode()
+        self.send_response(status)
+        self.send_header("Content-Type", "application/json")
+        self.send_header("Content-Length", str(len(data)))
+        self.end_headers()
+        self.wfile.write(data)
+
+    def do_GET(self):
+        if self.path != "/health":
+            return self.reply(404, {"error": "not found"})
+        if time.time() - started < 0.3:
+            return self.reply(503, {"error": "loading model"})
+        self.reply(200, {"status": "ok"})
+
+    def do_POST(self):
+        request = json.loads(self.rfile.read(int(self.headers["Content-Length"])))
+        if request["prompt"] == "fail":
+            return self.reply(500, {"error": "failed on purpose"})
+        if request["prompt"] == "die":
+            os._exit(1)
+        content = "pid=%d prompt=%s n_predict=%s STOP ignored" % (os.getpid(), request["prompt"], request.get("n_predict"))
+        self.reply(200, {"content": content})
+
+    def log_message(self, *args):
+        pass
+
+HTTPServer(("127.0.0.1", port), FakeLlamaServer).serve_forever()
+"#;
+
+/// The worker pid the fake put into the completion
+fn worker_pid_in(response: &str) -> String {
+    let after_pid = response.split("pid=").nth(1).unwrap_or_else(|| panic!("no pid in response: {}", response));
+    after_pid.split_whitespace().next().unwrap().to_string()
+}
+
+/// A fiddler_crab in llamacpp server mode with the fake as its worker
+struct ServerWithFakeWorker {
+    _server: ServerProcess,
+    port: u16,
+    directory: PathBuf,
+}
+
+impl Drop for ServerWithFakeWorker {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.directory);
+    }
+}
+
+/// Starts the server, or None (test skipped) without python3
+fn start_server_with_fake_worker(test_name: &str) -> Option<ServerWithFakeWorker> {
+    if Command::new("python3").arg("--version").stdout(Stdio::null()).status().is_err() {
+        eprintln!("skipping llamacpp worker test: python3 not available");
+        return None;
+    }
+    let directory: PathBuf = std::env::temp_dir().join(format!("fiddler_crab_worker_test_{}_{}", test_name, std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let fake_path = directory.join("fake-llama-server");
+    std::fs::write(&fake_path, FAKE_LLAMA_SERVER_SCRIPT).unwrap();
+    std::fs::set_permissions(&fake_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let port = free_local_port();
+    let worker_port = free_local_port();
+    let server = ServerProcess(
+        Command::new(env!("CARGO_BIN_EXE_fiddler_crab"))
+            .arg("--bind")
+            .arg(format!("127.0.0.1:{}", port))
+            .arg("--pace-ms")
+            .arg("0")
+            .arg("--endpoint.llamacpp.mode=server")
+            .arg(format!("--endpoint.llamacpp.server_binary_path={}", fake_path.display()))
+            .arg(format!("--endpoint.llamacpp.server_port={}", worker_port))
+            .arg("--endpoint.llamacpp.server_startup_timeout_ms=10000")
+            .arg("--endpoint.llamacpp.model_path=/models/fake.gguf")
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+    wait_for_listener(port);
+    Some(ServerWithFakeWorker {
+        _server: server,
+        port,
+        directory,
+    })
+}
+
+#[test]
+fn worker_is_started_lazily_reused_and_restarted_after_dying() {
+    let Some(server) = start_server_with_fake_worker("restart") else {
+        return;
+    };
+    let port = server.port;
+
+    let request_body = r#"{"prompt":"hello","n_predict":3,"stop":"STOP"}"#;
+    let first_response = post_json(port, "/llamacpp", request_body);
+    assert!(first_response.starts_with("HTTP/1.1 200 OK\r\n"), "response: {}", first_response);
+    let first_worker_pid = worker_pid_in(&first_response);
+    assert!(
+        first_response.contains(&format!(r#""completion":"pid={} prompt=hello n_predict=3 ""#, first_worker_pid)),
+        "response: {}",
+        first_response
+    );
+
+    // Same worker for the next request
+    let second_response = post_json(port, "/llamacpp", request_body);
+    assert_eq!(worker_pid_in(&second_response), first_worker_pid, "response: {}", second_response);
+
+    // A worker that died between requests is replaced by the next request
+    Command::new("kill").arg("-9").arg(&first_worker_pid).status().unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    let third_response = post_json(port, "/llamacpp", request_body);
+    assert!(third_response.starts_with("HTTP/1.1 200 OK\r\n"), "response: {}", third_response);
+    let third_worker_pid = worker_pid_in(&third_response);
+    assert_ne!(third_worker_pid, first_worker_pid, "response: {}", third_response);
+
+    // Killed right after it answered, before the worker thread saw it exit: still replaced
+    Command::new("kill").arg("-9").arg(&third_worker_pid).status().unwrap();
+    let fourth_response = post_json(port, "/llamacpp", request_body);
+    assert!(fourth_response.starts_with("HTTP/1.1 200 OK\r\n"), "response: {}", fourth_response);
+    assert_ne!(worker_pid_in(&fourth_response), third_worker_pid, "response: {}", fourth_response);
+}
+
+#[test]
+fn worker_error_reply_or_death_fails_only_its_own_request() {
+    let Some(server) = start_server_with_fake_worker("errors") else {
+        return;
+    };
+    let port = server.port;
+    let request_body = r#"{"prompt":"hello"}"#;
+    let worker_pid = worker_pid_in(&post_json(port, "/llamacpp", request_body));
+
+    // A non-200 reply is a 500 for the client; the worker is kept
+    let failed_response = post_json(port, "/llamacpp", r#"{"prompt":"fail"}"#);
+    assert!(failed_response.starts_with("HTTP/1.1 500 Internal Server Error\r\n"), "response: {}", failed_response);
+    assert!(
+        failed_response.contains("llama-server answered POST /completion with status 500"),
+        "response: {}",
+        failed_response
+    );
+    let response = post_json(port, "/llamacpp", request_body);
+    assert_eq!(worker_pid_in(&response), worker_pid, "response: {}", response);
+
+    // A worker that dies mid-request fails that request, and the next one restarts it
+    let died_response = post_json(port, "/llamacpp", r#"{"prompt":"die"}"#);
+    assert!(died_response.starts_with("HTTP/1.1 500 Internal Server Error\r\n"), "response: {}", died_response);
+    assert!(died_response.contains("llama-server request failed"), "response: {}", died_response);
+    let response = post_json(port, "/llamacpp", request_body);
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "response: {}", response);
+    assert_ne!(worker_pid_in(&response), worker_pid, "response: {}", response);
+}
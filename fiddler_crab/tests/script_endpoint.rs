@@ -0,0 +1,88 @@
+/*
+A script endpoint declared only in config, against the real binary (Unix: the script is sh)
+
+The script echoes the method, query string and body back, exits 3 for the
+body "fail" and hangs for the body "sleep". So the test checks what the
+script receives, that a non-zero exit is a 500, and that a script past
+endpoint.NAME.processing_timeout_ms is a 504 without stalling the queue.
+A second endpoint reads the body from a temp file in its temp_dir and
+reports the file's permissions.
+*/
+#![cfg(unix)]
+
+mod common;
+
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use common::{free_local_port, post_json, wait_for_listener, ServerProcess};
+
+const SHOUT_SCRIPT: &str = r#"body=$(cat)
+case "$body" in
+  fail) echo "cannot shout" >&2; exit 3 ;;
+  sleep) sleep 30 ;;
+esac
+printf '%s %s %s' "$REQUEST_METHOD" "$QUERY_STRING" "$body"
+"#;
+
+const FILE_MODE_SCRIPT: &str = r#"printf '%s %s' "$(stat -c %a "$1")" "$(cat "$1")"
+"#;
+
+#[test]
+fn script_endpoint_echoes_body_and_maps_failures() {
+    let directory: PathBuf = std::env::temp_dir().join(format!("fiddler_crab_script_test_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let script_path = directory.join("shout.sh");
+    std::fs::write(&script_path, SHOUT_SCRIPT).unwrap();
+    let file_mode_script_path = directory.join("file_mode.sh");
+    std::fs::write(&file_mode_script_path, FILE_MODE_SCRIPT).unwrap();
+    let temp_dir = directory.join("tmp");
+    std::fs::create_dir_all(&temp_dir).unwrap();
+
+    let port = free_local_port();
+    let _server = ServerProcess(
+        Command::new(env!("CARGO_BIN_EXE_fiddler_crab"))
+            .arg("--bind")
+            .arg(format!("127.0.0.1:{}", port))
+            .arg("--pace-ms")
+            .arg("0")
+            .arg("--endpoint.shout.type=script")
+            .arg("--endpoint.shout.interpreter=sh")
+            .arg(format!("--endpoint.shout.script_path={}", script_path.display()))
+            .arg("--endpoint.shout.content_type=text/plain")
+            .arg("--endpoint.shout.processing_timeout_ms=500")
+            .arg("--endpoint.file_mode.type=script")
+            .arg("--endpoint.file_mode.interpreter=sh")
+            .arg(format!("--endpoint.file_mode.script_path={}", file_mode_script_path.display()))
+            .arg("--endpoint.file_mode.input=temp_file")
+            .arg(format!("--endpoint.file_mode.temp_dir={}", temp_dir.display()))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+    wait_for_listener(port);
+
+    let response = post_json(port, "/shout?volume=11", "hello");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "response: {}", response);
+    assert!(response.contains("Content-Type: text/plain\r\n"), "response: {}", response);
+    assert!(response.ends_with("\r\n\r\nPOST volume=11 hello"), "response: {}", response);
+
+    // Non-zero exit: 500 with the exit code and the start of stderr
+    let response = post_json(port, "/shout", "fail");
+    assert!(response.starts_with("HTTP/1.1 500 Internal Server Error\r\n"), "response: {}", response);
+    assert!(response.contains("exited with code 3: cannot shout"), "response: {}", response);
+
+    // Past the endpoint's deadline: 504, and the next request is served
+    let response = post_json(port, "/shout", "sleep");
+    assert!(response.starts_with("HTTP/1.1 504 Gateway Timeout\r\n"), "response: {}", response);
+    let response = post_json(port, "/shout", "again");
+    assert!(response.ends_with("POST  again"), "response: {}", response);
+
+    // temp_file input: private to the server's user, in temp_dir, gone afterwards
+    let response = post_json(port, "/file_mode", "from a file");
+    assert!(response.ends_with("\r\n\r\n600 from a file"), "response: {}", response);
+    assert_eq!(std::fs::read_dir(&temp_dir).unwrap().count(), 0);
+
+    let _ = std::fs::remove_dir_all(&directory);
+}
@@ -0,0 +1,68 @@
+/*
+A client that trickles its request, against the real binary
+
+The slow client sends a few bytes of its request line, one at a time, and
+then goes quiet without finishing it. It must get a 408 once
+request_read_timeout_ms has passed, and a second client that connects
+meanwhile must get its answer right away: requests are read off the
+stream-loop, so a slow one never holds up accept().
+*/
+
+mod common;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use common::{free_local_port, post_json, wait_for_listener, ServerProcess};
+
+#[test]
+fn trickling_client_gets_408_and_does_not_block_other_clients() {
+    let port = free_local_port();
+    let _server = ServerProcess(
+        Command::new(env!("CARGO_BIN_EXE_fiddler_crab"))
+            .arg("--bind")
+            .arg(format!("127.0.0.1:{}", port))
+            .arg("--pace-ms")
+            .arg("0")
+            .arg("--request_read_timeout_ms=3000")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+    wait_for_listener(port);
+
+    let slow_client = std::thread::spawn(move || {
+        let mut tcp_stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        tcp_stream.set_read_timeout(Some(Duration::from_secs(20))).unwrap();
+        for byte in b"POST /ech" {
+            tcp_stream.write_all(&[*byte]).unwrap();
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        let mut response = Vec::new();
+        let _ = tcp_stream.read_to_end(&mut response);
+        String::from_utf8_lossy(&response).into_owned()
+    });
+
+    // Connect while the slow client is still sending
+    std::thread::sleep(Duration::from_millis(300));
+    let started = Instant::now();
+    let response = post_json(port, "/echo_input_data", r#"{"input_string": "hello"}"#);
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "response: {}", response);
+    assert!(started.elapsed() < Duration::from_secs(1), "second client waited {:?}", started.elapsed());
+
+    // Probes too are answered while the slow client holds its connection
+    let mut probe_stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    probe_stream.write_all(b"GET /healthz HTTP/1.1\r\n\r\n").unwrap();
+    let mut probe_response = String::new();
+    probe_stream.read_to_string(&mut probe_response).unwrap();
+    assert!(probe_response.starts_with("HTTP/1.1 200 OK\r\n"), "response: {}", probe_response);
+    assert!(!slow_client.is_finished(), "slow client was answered before the probe");
+
+    let started = Instant::now();
+    let slow_response = slow_client.join().unwrap();
+    assert!(slow_response.starts_with("HTTP/1.1 408 Request Timeout\r\n"), "response: {}", slow_response);
+    assert!(started.elapsed() < Duration::from_secs(5), "408 took {:?}", started.elapsed());
+}
@@ -0,0 +1,126 @@
+/*
+HTTPS round-trip against the real binary (only with the `tls` feature)
+
+    cargo test --features tls
+
+Generates a throwaway self-signed certificate with the `openssl` command
+line tool, starts fiddler_crab with it, and POSTs to /echo_input_data
+over TLS. Skipped (passes with a note) if `openssl` is not installed.
+*/
+#![cfg(feature = "tls")]
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, ServerName};
+
+/// Kills the server when the test ends, pass or fail
+struct ServerProcess(Child);
+
+impl Drop for ServerProcess {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Writes cert.pem and key.pem for "localhost" into `directory`; false if openssl is unavailable
+fn generate_self_signed_certificate(directory: &Path) -> bool {
+    let status = Command::new("openssl")
+        .args(["req", "-x509", "-newkey", "ec", "-pkeyopt", "ec_paramgen_curve:prime256v1", "-nodes"])
+        .args(["-days", "1", "-subj", "/CN=localhost"])
+        .args(["-addext", "subjectAltName=DNS:localhost"])
+        .args(["-addext", "basicConstraints=critical,CA:FALSE"])
+        .arg("-keyout")
+        .arg(directory.join("key.pem"))
+        .arg("-out")
+        .arg(directory.join("cert.pem"))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    matches!(status, Ok(status) if status.success())
+}
+
+/// A port that was free a moment ago
+fn free_local_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+/// Waits until the server accepts connections
+fn wait_for_listener(port: u16) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while TcpStream::connect(("127.0.0.1", port)).is_err() {
+        assert!(Instant::now() < deadline, "server did not start listening");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn https_round_trip_to_echo_input_data() {
+    let directory: PathBuf = std::env::temp_dir().join(format!("fiddler_crab_tls_test_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    if !generate_self_signed_certificate(&directory) {
+        eprintln!("skipping HTTPS round-trip: openssl command not available");
+        return;
+    }
+
+    let port = free_local_port();
+    let _server = ServerProcess(
+        Command::new(env!("CARGO_BIN_EXE_fiddler_crab"))
+            .arg("--bind")
+            .arg(format!("127.0.0.1:{}", port))
+            .arg("--pace-ms")
+            .arg("0")
+            .arg(format!("--tls_cert_path={}", directory.join("cert.pem").display()))
+            .arg(format!("--tls_key_path={}", directory.join("key.pem").display()))
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+    wait_for_listener(port);
+
+    // Trust exactly the generated certificate
+    let mut root_cert_store = RootCertStore::empty();
+    for certificate in CertificateDer::pem_file_iter(directory.join("cert.pem")).unwrap() {
+        root_cert_store.add(certificate.unwrap()).unwrap();
+    }
+    let client_config = ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(root_cert_store)
+        .with_no_client_auth();
+    let client_connection = ClientConnection::new(
+        Arc::new(client_config),
+        ServerName::try_from("localhost").unwrap(),
+    )
+    .unwrap();
+    let tcp_stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    tcp_stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    let mut tls_stream = StreamOwned::new(client_connection, tcp_stream);
+
+    let body = r#"{"input_string":"hello over tls"}"#;
+    let request = format!(
+        "POST /echo_input_data HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    tls_stream.write_all(request.as_bytes()).unwrap();
+
+    let mut response = Vec::new();
+    // The server sends close_notify after the response; tolerate a bare close too
+    if let Err(e) = tls_stream.read_to_end(&mut response) {
+        assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof, "read failed: {}", e);
+    }
+    let response = String::from_utf8_lossy(&response);
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "response: {}", response);
+    assert!(response.contains(r#""echoed_string":"hello over tls""#), "response: {}", response);
+
+    let _ = std::fs::remove_dir_all(&directory);
+}